@@ -0,0 +1,48 @@
+// examples/compile_bench.rs
+
+// Time the tree walking evaluator against compiled expressions.
+// Run with `cargo run --release --example compile_bench`
+
+use std::time::{Duration, Instant};
+use mathy::expr::prelude::*;
+
+const POINTS: usize = 100_000;
+
+fn time<F: FnMut(f64) -> f64>(mut f: F) -> (Duration, f64) {
+    let start = Instant::now();
+    let mut total = 0.0;
+    for k in 0..POINTS {
+	total += f(k as f64 / POINTS as f64);
+    }
+    (start.elapsed(), total)
+}
+
+fn bench(source: &str) {
+    let f = Expr::parse(source).unwrap();
+    let compiled = f.compile(&['x']).unwrap();
+    println!("{} ({} instructions)", source, compiled.len());
+
+    let (tree, a) = time(|x| {
+	let env = Env::new().bind('x', real(x));
+	evaluate_env(&f, &env).unwrap().real()
+    });
+    let (number, b) = time(|x| compiled.eval(&[real(x)]).real());
+    let (float, c) = time(|x| compiled.eval_f64(&[x]));
+
+    // all three should agree, up to rounding
+    println!("  evaluate_env  {:>10.2?}  sum {}", tree, a);
+    println!("  eval          {:>10.2?}  sum {}", number, b);
+    println!("  eval_f64      {:>10.2?}  sum {}", float, c);
+    println!("  speedup       {:>9.1}x / {:.1}x",
+	     tree.as_secs_f64() / number.as_secs_f64(),
+	     tree.as_secs_f64() / float.as_secs_f64());
+}
+
+fn main() {
+    println!("Evaluating each expression at {} points\n", POINTS);
+    bench("3x^3 - 2x^2 + x - 7");
+    bench("sin(x) * cos(2x) + e^(-x^2) / (1 + x^2)");
+    bench("sqrt(x + 1) * ln(x + 2) + gamma(x + 1) * (2 pi)^0.5");
+}
+
+// end examples/compile_bench.rs
@@ -1,6 +1,6 @@
 // src/bin/newtons_sqrt.rs
 
-/// Attempt to guess the square root of a number.
+// Attempt to guess the square root of a number.
 
 use mathy::expr::prelude::*;
 use mathy::analysis::newton::*;
@@ -2,5 +2,6 @@
 
 pub mod limit;
 pub mod newton;
+pub mod quadrature;
 
 // end src/analysis/mod.rs
@@ -3,20 +3,33 @@
 
 use crate::calc::prelude::*;
 
-pub fn newton(f: Expr, sym: char, guess: f64, iters: usize) {
+pub fn newton<S: Into<Symbol>>(f: Expr, sym: S, guess: f64, iters: usize) {
+    let sym = sym.into();
+
+    // polynomials skip the expression tree and use Horner's scheme
+    if let Ok(p) = Polynomial::from_expr(&f, sym) {
+	let dp = p.derivative();
+	println!("Newton polynomial: {}", p);
+
+	let mut xp : Number = real(guess);
+	for x in 0..iters {
+	    let x1 = &xp - &(p.eval(xp.clone()) / dp.eval(xp.clone()));
+	    println!("cycle {}: {}", x, x1);
+	    xp = x1;
+	}
+	return;
+    }
+
     let f1 = simplify(derive(f.clone(), sym));
     let e = var(sym) - div(f.clone(), f1.clone());
-    println!("Newton function: {}", e.to_string());
+    println!("Newton function: {}", e);
 
     let mut xp : Number = real(guess);
     for x in 0..iters {
-	let x1 = evaluate(e.clone(), sym, xp);
-	println!("cycle {}: {}", x, x1.to_string());
+	let x1 = evaluate(e.clone(), sym, xp.clone());
+	println!("cycle {}: {}", x, x1);
 	xp = x1;
     }
 }
 
 // end src/analysis/newton.rs
-
-
-
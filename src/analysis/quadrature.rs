@@ -0,0 +1,384 @@
+// src/analysis/quadrature.rs
+// numeric and definite integration
+
+use std::f64::consts::PI;
+
+use crate::calc::prelude::*;
+
+// Default absolute/relative tolerance for adaptive integration
+const TOLERANCE: f64 = 1e-10;
+
+// How many subintervals the adaptive integrator may split into
+const MAX_INTERVALS: usize = 2000;
+
+// Gauss-Kronrod 7-15 nodes on [0, 1], the other half is mirrored.
+// The Gauss nodes are every other Kronrod node (odd indices).
+const KRONROD_NODES: [f64; 8] = [
+    0.9914553711208126,
+    0.9491079123427585,
+    0.8648644233597691,
+    0.7415311855993945,
+    0.5860872354676911,
+    0.4058451513773972,
+    0.20778495500789848,
+    0.0,
+];
+
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022935322010529224,
+    0.06309209262997856,
+    0.10479001032225019,
+    0.14065325971552592,
+    0.1690047266392679,
+    0.19035057806478542,
+    0.20443294007529889,
+    0.20948214108472782,
+];
+
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.1294849661688697,
+    0.27970539148927664,
+    0.3818300505051189,
+    0.4179591836734694,
+];
+
+/// The result of an adaptive integration along with an estimate
+/// of its absolute error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub value: Number,
+    pub error: f64,
+}
+
+// Evaluate an Expr at a point, treating NaN and infinite results
+// as errors since no quadrature rule can recover from them
+fn sample(e: &Expr, sym: Symbol, x: f64) -> Result<Number, MathError> {
+    let env = Env::new().bind(sym, real(x));
+    let y = try_evaluate(e, &env)?;
+    if y.is_finite() {
+	Ok(y)
+    } else {
+	Err(MathError::DomainError(format!("{} is not finite at {} = {}", e, sym, x)))
+    }
+}
+
+/// Composite Simpson's rule over `n` panels, rounded up to the
+/// next even number. Exact for polynomials up to degree three.
+///
+/// ```
+/// use mathy::calc::prelude::*;
+/// use mathy::analysis::quadrature::*;
+/// let area = simpson(&powf(var('x'), 2.0), 'x', 0.0, 3.0, 10).unwrap();
+/// assert!((area - real(9.0)).abs().real() < 1e-12);
+/// ```
+pub fn simpson<S: Into<Symbol>>(e: &Expr, sym: S, a: f64, b: f64, n: usize) -> Result<Number, MathError> {
+    let sym = sym.into();
+    if n == 0 {
+	return Err(MathError::DomainError("Simpson's rule needs at least one panel".to_string()));
+    }
+    let n = n + n % 2;
+    let h = (b - a) / n as f64;
+    let mut total = sample(e, sym, a)? + sample(e, sym, b)?;
+    for i in 1..n {
+	let w = if i % 2 == 1 { 4.0 } else { 2.0 };
+	total = total + real(w) * sample(e, sym, a + i as f64 * h)?;
+    }
+    Ok(total * real(h / 3.0))
+}
+
+// The Legendre polynomial P_n(x) and its derivative
+fn legendre(n: usize, x: f64) -> (f64, f64) {
+    let (mut p0, mut p1) = (1.0, x);
+    for k in 2..=n {
+	let k = k as f64;
+	let p2 = ((2.0 * k - 1.0) * x * p1 - (k - 1.0) * p0) / k;
+	p0 = p1;
+	p1 = p2;
+    }
+    let dp = n as f64 * (x * p1 - p0) / (x * x - 1.0);
+    (p1, dp)
+}
+
+// The nodes and weights of n-point Gauss-Legendre quadrature on
+// [-1, 1], finding each root of P_n with Newton's method
+fn legendre_nodes(n: usize) -> Vec<(f64, f64)> {
+    (0..n).map(|i| {
+	// Chebyshev-like initial guess close to the i-th root
+	let mut x = (PI * (i as f64 + 0.75) / (n as f64 + 0.5)).cos();
+	for _ in 0..100 {
+	    let (p, dp) = legendre(n, x);
+	    let dx = p / dp;
+	    x -= dx;
+	    if dx.abs() < 1e-15 {
+		break;
+	    }
+	}
+	let (_, dp) = legendre(n, x);
+	(x, 2.0 / ((1.0 - x * x) * dp * dp))
+    }).collect()
+}
+
+/// Gauss-Legendre quadrature with `n` points, exact for
+/// polynomials up to degree `2n - 1`. The integrand is never
+/// evaluated at the bounds themselves.
+pub fn gauss_legendre<S: Into<Symbol>>(e: &Expr, sym: S, a: f64, b: f64, n: usize) -> Result<Number, MathError> {
+    let sym = sym.into();
+    if n == 0 {
+	return Err(MathError::DomainError("Gauss-Legendre needs at least one point".to_string()));
+    }
+    let (mid, half) = ((a + b) / 2.0, (b - a) / 2.0);
+    let mut total = real(0.0);
+    for (x, w) in legendre_nodes(n) {
+	total = total + real(w) * sample(e, sym, mid + half * x)?;
+    }
+    Ok(total * real(half))
+}
+
+/// Romberg integration, repeatedly halving the trapezoid rule and
+/// extrapolating until two diagonal entries agree within `tol`.
+/// Gives up with `MathError::NotConverged` after `max_steps` halvings.
+pub fn romberg<S: Into<Symbol>>(e: &Expr, sym: S, a: f64, b: f64, tol: f64, max_steps: usize) -> Result<Number, MathError> {
+    let sym = sym.into();
+    let mut h = b - a;
+    let mut prev = vec![real(h / 2.0) * (sample(e, sym, a)? + sample(e, sym, b)?)];
+    for step in 1..=max_steps {
+	h /= 2.0;
+	// only the new midpoints need evaluating
+	let mut mids = real(0.0);
+	// 2^(step - 1) points stop fitting in a usize past 64 steps
+	let count = 1usize.checked_shl((step - 1).min(u32::MAX as usize) as u32).ok_or_else(|| {
+	    MathError::NotConverged(format!("Romberg integration of {} after {} steps", e, step - 1))
+	})?;
+	for i in 0..count {
+	    mids = mids + sample(e, sym, a + (2 * i + 1) as f64 * h)?;
+	}
+	let mut row = vec![real(0.5) * prev[0].clone() + real(h) * mids];
+	for k in 1..=step {
+	    let factor = 4f64.powi(k as i32);
+	    let r = &row[k - 1] + &((&row[k - 1] - &prev[k - 1]) / real(factor - 1.0));
+	    row.push(r);
+	}
+	let diff = (&row[step] - &prev[step - 1]).abs().real();
+	if diff <= tol.max(tol * row[step].abs().real()) {
+	    return Ok(row[step].clone());
+	}
+	prev = row;
+    }
+    Err(MathError::NotConverged(format!("Romberg integration of {} after {} steps", e, max_steps)))
+}
+
+// A single Gauss-Kronrod 7-15 panel, returning the Kronrod value
+// and the difference from the embedded Gauss rule
+fn kronrod_panel<F>(f: &F, a: f64, b: f64) -> Result<Estimate, MathError>
+where F: Fn(f64) -> Result<Number, MathError> {
+    let (mid, half) = ((a + b) / 2.0, (b - a) / 2.0);
+    let centre = f(mid)?;
+    let mut kronrod = real(KRONROD_WEIGHTS[7]) * centre.clone();
+    let mut gauss = real(GAUSS_WEIGHTS[3]) * centre;
+    for i in 0..7 {
+	let dx = half * KRONROD_NODES[i];
+	let pair = f(mid - dx)? + f(mid + dx)?;
+	kronrod = kronrod + real(KRONROD_WEIGHTS[i]) * pair.clone();
+	if i % 2 == 1 {
+	    gauss = gauss + real(GAUSS_WEIGHTS[i / 2]) * pair;
+	}
+    }
+    let value = &kronrod * &real(half);
+    let error = ((kronrod - gauss) * real(half)).abs().real();
+    Ok(Estimate { value, error })
+}
+
+// Adaptive Gauss-Kronrod over any integrand, always splitting the
+// panel with the largest error until the total error is in tolerance
+fn adaptive<F>(f: &F, a: f64, b: f64, tol: f64) -> Result<Estimate, MathError>
+where F: Fn(f64) -> Result<Number, MathError> {
+    let mut panels = vec![(a, b, kronrod_panel(f, a, b)?)];
+    loop {
+	let value = panels.iter().fold(real(0.0), |acc, p| acc + p.2.value.clone());
+	let error: f64 = panels.iter().map(|p| p.2.error).sum();
+	if error <= tol.max(tol * value.abs().real()) {
+	    return Ok(Estimate { value, error });
+	}
+	if panels.len() >= MAX_INTERVALS {
+	    return Err(MathError::NotConverged(
+		format!("adaptive integration with error {} after {} intervals", error, MAX_INTERVALS)));
+	}
+
+	let worst = (0..panels.len())
+	    .max_by(|&i, &j| panels[i].2.error.total_cmp(&panels[j].2.error))
+	    .unwrap();
+	let (lo, hi, _) = panels.swap_remove(worst);
+	let mid = (lo + hi) / 2.0;
+	panels.push((lo, mid, kronrod_panel(f, lo, mid)?));
+	panels.push((mid, hi, kronrod_panel(f, mid, hi)?));
+    }
+}
+
+/// Adaptive Gauss-Kronrod (G7-K15) integration over a finite
+/// interval, with an estimate of the absolute error. Since no
+/// nodes sit on the bounds, integrable singularities at either
+/// end like `1/sqrt(x)` on `[0, 1]` are fine.
+///
+/// ```
+/// use mathy::calc::prelude::*;
+/// use mathy::analysis::quadrature::*;
+/// let est = gauss_kronrod(&sin(var('x')), 'x', 0.0, std::f64::consts::PI, 1e-12).unwrap();
+/// assert!((est.value - real(2.0)).abs().real() < 1e-12);
+/// ```
+pub fn gauss_kronrod<S: Into<Symbol>>(e: &Expr, sym: S, a: f64, b: f64, tol: f64) -> Result<Estimate, MathError> {
+    let sym = sym.into();
+    adaptive(&|x| sample(e, sym, x), a, b, tol)
+}
+
+/// Numerically integrate between two bounds, either of which may
+/// be infinite. Infinite bounds are mapped onto a finite interval
+/// with a change of variables, ie `x = a + t/(1 - t)` for `[a, inf)`,
+/// before running adaptive Gauss-Kronrod on the result.
+pub fn integrate_numeric<S: Into<Symbol>>(e: &Expr, sym: S, a: f64, b: f64) -> Result<Estimate, MathError> {
+    let sym = sym.into();
+    if a.is_nan() || b.is_nan() {
+	return Err(MathError::DomainError("integration bounds are NaN".to_string()));
+    }
+    if a == b {
+	return Ok(Estimate { value: real(0.0), error: 0.0 });
+    }
+    if a > b {
+	let est = integrate_numeric(e, sym, b, a)?;
+	return Ok(Estimate { value: -est.value, ..est });
+    }
+
+    let f = |x: f64| sample(e, sym, x);
+    match (a.is_finite(), b.is_finite()) {
+	(true, true) => adaptive(&f, a, b, TOLERANCE),
+	(true, false) => {
+	    let g = |t: f64| Ok(f(a + t / (1.0 - t))? / real((1.0 - t) * (1.0 - t)));
+	    adaptive(&g, 0.0, 1.0, TOLERANCE)
+	},
+	(false, true) => {
+	    let g = |t: f64| Ok(f(b - (1.0 - t) / t)? / real(t * t));
+	    adaptive(&g, 0.0, 1.0, TOLERANCE)
+	},
+	(false, false) => {
+	    let g = |t: f64| {
+		let s = 1.0 - t * t;
+		Ok(f(t / s)? * real((1.0 + t * t) / (s * s)))
+	    };
+	    adaptive(&g, -1.0, 1.0, TOLERANCE)
+	},
+    }
+}
+
+/// Integrate between two bounds, preferring an exact answer from
+/// the symbolic integrator as `F(b) - F(a)` and falling back to
+/// `integrate_numeric` otherwise. The symbolic answer is checked
+/// against a quick numeric estimate first, since an antiderivative
+/// is no use across a pole inside the interval.
+///
+/// ```
+/// use mathy::calc::prelude::*;
+/// use mathy::analysis::quadrature::*;
+/// let f = Expr::parse("3x^2").unwrap();
+/// assert_eq!(integrate_definite(&f, 'x', 0.0, 2.0), Ok(real(8.0)));
+///
+/// // no antiderivative, so this is numeric
+/// let g = Expr::parse("e^(-x^2)").unwrap();
+/// let area = integrate_definite(&g, 'x', f64::NEG_INFINITY, f64::INFINITY).unwrap();
+/// assert!((area - real(std::f64::consts::PI.sqrt())).abs().real() < 1e-9);
+/// ```
+pub fn integrate_definite<S: Into<Symbol>>(e: &Expr, sym: S, a: f64, b: f64) -> Result<Number, MathError> {
+    let sym = sym.into();
+    if a.is_finite() && b.is_finite() {
+	if let Some(exact) = symbolic_definite(e, sym, a, b) {
+	    if let Ok(check) = adaptive(&|x| sample(e, sym, x), a, b, 1e-6) {
+		let diff = (&exact - &check.value).abs().real();
+		if diff <= 1e-5 * (1.0 + exact.abs().real()) {
+		    return Ok(exact);
+		}
+	    }
+	}
+    }
+    Ok(integrate_numeric(e, sym, a, b)?.value)
+}
+
+// F(b) - F(a) if the integrand has an antiderivative which is
+// finite at both bounds
+fn symbolic_definite(e: &Expr, sym: Symbol, a: f64, b: f64) -> Option<Number> {
+    let big_f = antiderivative(e.clone(), sym).ok()?;
+    let fa = sample(&big_f, sym, a).ok()?;
+    let fb = sample(&big_f, sym, b).ok()?;
+    fb.try_sub(fa).ok()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: Number, b: f64, tol: f64) -> bool {
+	(a - real(b)).abs().real() < tol
+    }
+
+    #[test]
+    fn test_fixed_rules() {
+	let cubic = Expr::parse("x^3 - 2x + 1").unwrap();
+	assert!(close(simpson(&cubic, 'x', 0.0, 2.0, 3).unwrap(), 2.0, 1e-12));
+
+	// 5 points is exact up to degree 9
+	let f = Expr::parse("x^9 + x^4").unwrap();
+	assert!(close(gauss_legendre(&f, 'x', -1.0, 1.0, 5).unwrap(), 0.4, 1e-13));
+	assert!(close(gauss_legendre(&exp(var('x')), 'x', 0.0, 1.0, 10).unwrap(),
+		      std::f64::consts::E - 1.0, 1e-14));
+
+	let r = romberg(&sin(var('x')), 'x', 0.0, PI, 1e-12, 20).unwrap();
+	assert!(close(r, 2.0, 1e-10));
+	assert!(matches!(romberg(&sqrt(var('x')), 'x', 0.0, 1.0, 1e-14, 3),
+			 Err(MathError::NotConverged(_))));
+    }
+
+    #[test]
+    fn test_adaptive() {
+	let est = gauss_kronrod(&pow(var('x'), con(-0.5)), 'x', 0.0, 1.0, 1e-10).unwrap();
+	assert!(close(est.value, 2.0, 1e-9));
+	assert!(est.error < 1e-9);
+
+	let log = gauss_kronrod(&ln(var('x')), 'x', 0.0, 1.0, 1e-10).unwrap();
+	assert!(close(log.value, -1.0, 1e-9));
+
+	// the bounds can be backwards
+	let back = integrate_numeric(&var('x'), 'x', 1.0, 0.0).unwrap();
+	assert!(close(back.value, -0.5, 1e-12));
+    }
+
+    #[test]
+    fn test_infinite_bounds() {
+	let f = Expr::parse("1 / x^2").unwrap();
+	assert!(close(integrate_numeric(&f, 'x', 1.0, f64::INFINITY).unwrap().value, 1.0, 1e-9));
+
+	let g = Expr::parse("e^x").unwrap();
+	assert!(close(integrate_numeric(&g, 'x', f64::NEG_INFINITY, 0.0).unwrap().value, 1.0, 1e-9));
+
+	let h = Expr::parse("1 / (1 + x^2)").unwrap();
+	assert!(close(integrate_numeric(&h, 'x', f64::NEG_INFINITY, f64::INFINITY).unwrap().value, PI, 1e-9));
+    }
+
+    #[test]
+    fn test_integrate_definite() {
+	let f = Expr::parse("x * sin(x)").unwrap();
+	assert!(close(integrate_definite(&f, 'x', 0.0, PI).unwrap(), PI, 1e-12));
+
+	let g = Expr::parse("e^(-x^2)").unwrap();
+	assert!(close(integrate_definite(&g, 'x', 0.0, 1.0).unwrap(), 0.746824132812427, 1e-10));
+
+	// the antiderivative -1/x would give -2 here, so the pole
+	// has to be caught by the numeric check instead
+	let pole = Expr::parse("1 / x^2").unwrap();
+	assert_eq!(symbolic_definite(&pole, Symbol::from('x'), -1.0, 1.0), Some(real(-2.0)));
+	assert!(integrate_definite(&pole, 'x', -1.0, 1.0).is_err());
+
+	let unbound = mul(var('x'), var('y'));
+	assert_eq!(integrate_definite(&unbound, 'x', 0.0, 1.0),
+		   Err(MathError::UnboundVariable(vec![Symbol::from('y')])));
+    }
+}
+
+// end src/analysis/quadrature.rs
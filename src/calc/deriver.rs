@@ -2,12 +2,26 @@
 
 use crate::expr::expr::*;
 use crate::expr::expr::Expr::*;
+use crate::expr::symbol::*;
+use crate::number::number::*;
+use crate::error::*;
 
 /// Execute a one-variable differentiaton.
-/// Hold other variables constant at zero.
-pub fn derive(e: Expr, sym: char) -> Expr {
-    match e {
-        Const(_) => con(0.0),
+/// Every other variable is held constant, so this is
+/// the partial derivative by `sym`.
+/// Expressions without a derivative rule give back a NaN
+/// constant; use `try_derive` to find out why.
+pub fn derive<S: Into<Symbol>>(e: Expr, sym: S) -> Expr {
+    try_derive(e, sym).unwrap_or_else(|_| Const(nan()))
+}
+
+/// Execute a one-variable differentiation, reporting an
+/// `Unsupported` error for any part of the tree which has
+/// no derivative, like a polygamma with a variable order.
+pub fn try_derive<S: Into<Symbol>>(e: Expr, sym: S) -> Result<Expr, MathError> {
+    let sym = sym.into();
+    let d = match e {
+        Const(_) | Named(_) => con(0.0),
         Var(s) => {
             if s == sym {
                 con(1.0)
@@ -22,20 +36,20 @@ pub fn derive(e: Expr, sym: char) -> Expr {
 	    match inner {
 		Const(_) => con(0.0),
 		Var(c) if c == sym => neg(con(1.0)),
-		f => neg(derive(f, sym)),
+		f => neg(try_derive(f, sym)?),
 	    }
 	},
 
         Add(ref l, ref r) => {
             let left = unpack(l);
             let right = unpack(r);
-            add(derive(left, sym), derive(right, sym))
+            add(try_derive(left, sym)?, try_derive(right, sym)?)
         },
 
         Sub(ref l, ref r) => {
             let left = unpack(l);
             let right = unpack(r);
-            sub(derive(left, sym), derive(right, sym))
+            sub(try_derive(left, sym)?, try_derive(right, sym)?)
         },
 
         Mul(ref l, ref r) => {
@@ -49,7 +63,7 @@ pub fn derive(e: Expr, sym: char) -> Expr {
                 (a, b) => {
                     let a1 = a.clone();
                     let b1 = b.clone();
-                    add(mul(a, derive(b, sym)), mul(b1, derive(a1, sym)))
+                    add(mul(a, try_derive(b, sym)?), mul(b1, try_derive(a1, sym)?))
                 },
             } 
         },
@@ -61,8 +75,8 @@ pub fn derive(e: Expr, sym: char) -> Expr {
             let g2 = unpack(r);
             let g3 = unpack(r);
 
-            let fp = derive(f, sym);
-            let gp = derive(g, sym);
+            let fp = try_derive(f, sym)?;
+            let gp = try_derive(g, sym)?;
 
             div(sub(mul(g2, fp), mul(gp, f2)), pow(g3, con(2.0)))
         },
@@ -81,7 +95,7 @@ pub fn derive(e: Expr, sym: char) -> Expr {
                 },
                 f => {
                     let f2 = f.clone();
-                    mul(derive(f, sym), cos(f2))
+                    mul(try_derive(f, sym)?, cos(f2))
                 },
             }
         },
@@ -100,7 +114,7 @@ pub fn derive(e: Expr, sym: char) -> Expr {
                 }
                 f => {
                     let f2 = f.clone();
-                    mul(neg(derive(f, sym)), sin(f2))
+                    mul(neg(try_derive(f, sym)?), sin(f2))
                 },
             }
         },
@@ -113,24 +127,36 @@ pub fn derive(e: Expr, sym: char) -> Expr {
 		// var raised to a power
 		(Var(c), Const(n)) if c == sym => {
 		    if n.real_eq(1.0) {
-			con(0.0)
+			con(1.0)
 		    } else if n.real_eq(2.0) {
 			varf(sym, 2.0)
 		    } else {
-			Const(n) * Pow(pack(Var(sym)), pack(con(n.real()-1.0)))
+			Const(n.clone()) * Pow(pack(Var(sym)), pack(Const(n - integer(1))))
 		    }
 		},
-		// function raised to a power
-		(f, Const(n)) => {
-		    let fp = derive(f.clone(), sym);
-		    let np = n.clone().real() - 1.0;
-
-		    mul(mul(Const(n), pow(f, con(np))), fp)
+		// function raised to a constant power
+		(f, g) if !g.has_var(sym) => {
+		    let fp = try_derive(f.clone(), sym)?;
+		    let gm1 = match g {
+			Const(ref n) => Const(n - &integer(1)),
+			ref g => sub(g.clone(), con(1.0)),
+		    };
+
+		    mul(mul(g, pow(f, gm1)), fp)
+		},
+		// constant raised to a function, d/dx a^g = a^g ln(a) g'
+		(f, g) if !f.has_var(sym) => {
+		    let gp = try_derive(g.clone(), sym)?;
+		    mul(mul(pow(f.clone(), g), ln(f)), gp)
 		},
+		// logarithmic differentiation, f^g (g' ln(f) + g f' / f)
 		(f, g) => {
-		    mul(f, g)
-		}
-
+		    let fp = try_derive(f.clone(), sym)?;
+		    let gp = try_derive(g.clone(), sym)?;
+		    let inner = add(mul(gp, ln(f.clone())),
+				    div(mul(g.clone(), fp), f.clone()));
+		    mul(pow(f, g), inner)
+		},
 	    }
 	},
 
@@ -141,17 +167,117 @@ pub fn derive(e: Expr, sym: char) -> Expr {
 		Const(_) => con(0.0),
 		Var(c) if c == sym => exp(var(sym)),
 		f => {
-		    let fp = derive(f.clone(), sym);
+		    let fp = try_derive(f.clone(), sym)?;
 		    mul(fp, exp(f.clone()))
 		}
 	    }
 	},
 
-	
+	// d/dx ln(f) = f' / f
+	Ln(ref i) => {
+	    let f = unpack(i);
+	    div(try_derive(f.clone(), sym)?, f)
+	},
 
-        _ => con(0.0),
-    }
+	Sinh(ref i) => {
+	    let f = unpack(i);
+	    mul(try_derive(f.clone(), sym)?, cosh(f))
+	},
 
+	Cosh(ref i) => {
+	    let f = unpack(i);
+	    mul(try_derive(f.clone(), sym)?, sinh(f))
+	},
+
+	// d/dx asin(f) = f' / sqrt(1 - f^2)
+	Asin(ref i) => {
+	    let f = unpack(i);
+	    let fp = try_derive(f.clone(), sym)?;
+	    div(fp, sqrt(sub(con(1.0), powf(f, 2.0))))
+	},
+
+	// d/dx acos(f) = -f' / sqrt(1 - f^2)
+	Acos(ref i) => {
+	    let f = unpack(i);
+	    let fp = try_derive(f.clone(), sym)?;
+	    neg(div(fp, sqrt(sub(con(1.0), powf(f, 2.0)))))
+	},
+
+	// d/dx asinh(f) = f' / sqrt(f^2 + 1)
+	Asinh(ref i) => {
+	    let f = unpack(i);
+	    let fp = try_derive(f.clone(), sym)?;
+	    div(fp, sqrt(add(powf(f, 2.0), con(1.0))))
+	},
+
+	// d/dx acosh(f) = f' / sqrt(f^2 - 1)
+	Acosh(ref i) => {
+	    let f = unpack(i);
+	    let fp = try_derive(f.clone(), sym)?;
+	    div(fp, sqrt(sub(powf(f, 2.0), con(1.0))))
+	},
+
+	// d/dx Γ(f) = Γ(f) ψ(f) f'
+	Gamma(ref i) => {
+	    let f = unpack(i);
+	    let fp = try_derive(f.clone(), sym)?;
+	    mul(mul(gamma(f.clone()), digamma(f)), fp)
+	},
+
+	// d/dx f! = f! ψ(f + 1) f'
+	Factorial(ref i) => {
+	    let f = unpack(i);
+	    let fp = try_derive(f.clone(), sym)?;
+	    mul(mul(factorial(f.clone()), digamma(add(f, con(1.0)))), fp)
+	},
+
+	// d/dx |f| = f / |f| * f'
+	Abs(ref i) => {
+	    let f = unpack(i);
+	    mul(div(f.clone(), abs(f.clone())), try_derive(f, sym)?)
+	},
+
+	// d/dx lnΓ(f) = ψ(f) * f'
+	LnGamma(ref i) => {
+	    let f = unpack(i);
+	    mul(try_derive(f.clone(), sym)?, digamma(f))
+	},
+
+	// d/dx ψ(f) = ψ(1, f) * f'
+	Digamma(ref i) => {
+	    let f = unpack(i);
+	    mul(try_derive(f.clone(), sym)?, polygamma(con(1.0), f))
+	},
+
+	// d/dx ψ(n, f) = ψ(n+1, f) * f'
+	Polygamma(ref n, ref i) => {
+	    let order = unpack(n);
+	    if order.has_var(sym) {
+		return Err(MathError::Unsupported(
+		    format!("derivative of polygamma with a variable order {}", order)
+		));
+	    }
+	    let next = match order {
+		Const(k) => Const(k + integer(1)),
+		k => add(k, con(1.0)),
+	    };
+	    let f = unpack(i);
+	    mul(try_derive(f.clone(), sym)?, polygamma(next, f))
+	},
+
+	// d/dx B(a, b) = B(a, b) * ((ψ(a) - ψ(a+b)) a' + (ψ(b) - ψ(a+b)) b')
+	Beta(ref l, ref r) => {
+	    let a = unpack(l);
+	    let b = unpack(r);
+	    let ap = try_derive(a.clone(), sym)?;
+	    let bp = try_derive(b.clone(), sym)?;
+	    let psi_ab = digamma(add(a.clone(), b.clone()));
+	    let da = mul(sub(digamma(a.clone()), psi_ab.clone()), ap);
+	    let db = mul(sub(digamma(b.clone()), psi_ab), bp);
+	    mul(beta(a, b), add(da, db))
+	},
+    };
+    Ok(d)
 }
 
 
@@ -161,6 +287,7 @@ mod test {
 
     use super::*;
     use crate::expr::simplify::*;
+    use crate::expr::evaluator::*;
 
     #[test]
     fn derive_square_test() {
@@ -198,6 +325,81 @@ mod test {
 
 	assert_eq!(f1d, f2);
     }
+
+    #[test]
+    fn derive_gamma_family_test() {
+	let f = ln_gamma(powf(var('x'), 2.0));
+	let expected = mul(varf('x', 2.0), digamma(powf(var('x'), 2.0)));
+	assert_eq!(derive(f, 'x'), expected);
+
+	let g = derive(digamma(var('x')), 'x');
+	assert_eq!(g, mul(con(1.0), polygamma(con(1.0), var('x'))));
+
+	let h = derive(polygamma(con(1.0), var('x')), 'x');
+	assert_eq!(h, mul(con(1.0), polygamma(con(2.0), var('x'))));
+
+	// check the beta rule numerically against a central difference
+	let b = beta(var('x'), con(2.5));
+	let db = derive(b.clone(), 'x');
+	let x0 = 1.7;
+	let step = 1e-6;
+	let numeric = (evaluate(b.clone(), 'x', real(x0 + step))
+		       - evaluate(b, 'x', real(x0 - step))).real() / (2.0 * step);
+	let exact = evaluate(db, 'x', real(x0)).real();
+	assert!((numeric - exact).abs() < 1e-6);
+
+	assert!(try_derive(polygamma(var('x'), var('x')), 'x').is_err());
+    }
+
+    // compare derive against a central difference at a few points
+    fn assert_numeric_derivative(f: Expr) {
+	let df = derive(f.clone(), 'x');
+	for x0 in [1.2, 1.5, 2.3, 3.1].iter() {
+	    let step = 1e-6;
+	    let numeric = (evaluate(f.clone(), 'x', real(x0 + step))
+			   - evaluate(f.clone(), 'x', real(x0 - step))).real() / (2.0 * step);
+	    let exact = evaluate(df.clone(), 'x', real(*x0)).real();
+	    assert!((numeric - exact).abs() < 1e-5 * (1.0 + exact.abs()),
+		    "d/dx {} = {} but expected {} at x = {}", f, exact, numeric, x0);
+	}
+    }
+
+    #[test]
+    fn derive_elementary_test() {
+	assert_eq!(derive(ln(var('x')), 'x'), div(con(1.0), var('x')));
+	assert_eq!(derive(powf(var('x'), 1.0), 'x'), con(1.0));
+
+	let fs = [
+	    "ln(x^2 + 1)", "sinh(2x)", "cosh(x^2)", "asin(x / 4)", "acos(x / 4)",
+	    "asinh(x^3)", "acosh(2x)", "gamma(x)", "factorial(x)", "(x^2)!",
+	];
+	for f in fs.iter() {
+	    assert_numeric_derivative(Expr::parse(f).unwrap());
+	}
+    }
+
+    #[test]
+    fn derive_power_test() {
+	assert_numeric_derivative(Expr::parse("2^x").unwrap());
+	assert_numeric_derivative(Expr::parse("x^x").unwrap());
+	assert_numeric_derivative(Expr::parse("sin(x)^(x^2)").unwrap());
+	assert_numeric_derivative(Expr::parse("(x + 1)^2.5").unwrap());
+
+	// a constant symbolic exponent uses the power rule
+	let f = pow(var('x'), var('n'));
+	let expected = mul(mul(var('n'), pow(var('x'), sub(var('n'), con(1.0)))), con(1.0));
+	assert_eq!(derive(f, 'x'), expected);
+    }
+
+    #[test]
+    fn try_derive_test() {
+	let f = add(sin(var('x')), polygamma(var('x'), var('x')));
+	assert!(matches!(try_derive(f.clone(), 'x'), Err(MathError::Unsupported(_))));
+	assert_eq!(derive(f, 'x'), Const(nan()));
+
+	let g = sin(var('x'));
+	assert_eq!(try_derive(g, 'x'), Ok(cos(var('x'))));
+    }
 }
 
 
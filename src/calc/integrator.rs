@@ -1,16 +1,432 @@
 // src/calc/integrator.rs
 
+use crate::calc::deriver::*;
+use crate::error::*;
 use crate::expr::expr::*;
 use crate::expr::expr::Expr::*;
+use crate::expr::simplify::*;
+use crate::expr::symbol::*;
+use crate::poly::polynomial::*;
 
-// integrate with respect to a single variable
-pub fn integrate(e: Expr, sym: char) -> Expr {
+// How deep integration by parts and substitution may recurse
+// before giving up on an expression
+const MAX_DEPTH: usize = 32;
+
+/// Integrate with respect to a single variable, giving back the
+/// antiderivative plus a constant of integration `C`. Expressions
+/// which have no rule return a `MathError::Unsupported` instead
+/// of a wrong answer.
+///
+/// ```
+/// use mathy::calc::prelude::*;
+/// let f = integrate(cos(var('x')), 'x').unwrap();
+/// assert_eq!(f, add(sin(var('x')), var('C')));
+/// assert!(integrate(gamma(var('x')), 'x').is_err());
+/// ```
+pub fn integrate<S: Into<Symbol>>(e: Expr, sym: S) -> Result<Expr, MathError> {
+    Ok(add(antiderivative(e, sym)?, var('C')))
+}
+
+/// Find an antiderivative `F` of an expression such that
+/// `derive(F, sym) = e`, without any constant of integration.
+///
+/// The rules tried, in order, are: constants, linearity over
+/// `Add`, `Sub`, `Neg` and constant factors, a table of integrals
+/// for each function (with linear arguments like `sin(3x + 1)`),
+/// u-substitution for `f(u(x)) * u'(x)` shapes, and integration by
+/// parts for a polynomial times exp, sin, cos, sinh, cosh or
+/// another polynomial. Polynomials with numeric coefficients skip
+/// all of that and are integrated term by term.
+pub fn antiderivative<S: Into<Symbol>>(e: Expr, sym: S) -> Result<Expr, MathError> {
+    let sym = sym.into();
+    if let Ok(p) = Polynomial::from_expr(&e, sym) {
+	return Ok(p.integral().to_expr());
+    }
+    let result = integ(&e, sym, 0)?;
+    Ok(simplify(result))
+}
+
+fn cannot_integrate(e: &Expr) -> MathError {
+    MathError::Unsupported(format!("cannot integrate {}", e))
+}
+
+fn integ(e: &Expr, x: Symbol, depth: usize) -> Result<Expr, MathError> {
+    if depth > MAX_DEPTH {
+	return Err(cannot_integrate(e));
+    }
+
+    // anything without x is a constant
+    if !e.has_var(x) {
+	return Ok(mul(e.clone(), var(x)));
+    }
+
+    match e {
+	// linearity
+	Neg(ref f) => return Ok(neg(integ(f, x, depth + 1)?)),
+	Add(ref f, ref g) => {
+	    return Ok(add(integ(f, x, depth + 1)?, integ(g, x, depth + 1)?));
+	},
+	Sub(ref f, ref g) => {
+	    return Ok(sub(integ(f, x, depth + 1)?, integ(g, x, depth + 1)?));
+	},
+	Mul(ref c, ref f) if !c.has_var(x) => {
+	    return Ok(mul(unpack(c), integ(f, x, depth + 1)?));
+	},
+	Mul(ref f, ref c) if !c.has_var(x) => {
+	    return Ok(mul(unpack(c), integ(f, x, depth + 1)?));
+	},
+	Div(ref f, ref c) if !c.has_var(x) => {
+	    return Ok(div(integ(f, x, depth + 1)?, unpack(c)));
+	},
+	// f / g is the same as f * g^-1, which substitution can handle
+	Div(ref f, ref g) => {
+	    return integ(&mul(unpack(f), pow(unpack(g), con(-1.0))), x, depth + 1);
+	},
+	// (u^n)^m is u^(n m) for whole m, so 1 / x^2 reaches the
+	// power rule as x^-2
+	Pow(ref b, ref m) => {
+	    if let (Pow(ref u, ref n), Const(k)) = (b.as_ref(), m.as_ref()) {
+		if k.is_whole() && !n.has_var(x) {
+		    let p = simplify(mul(unpack(n), unpack(m)));
+		    return integ(&pow(unpack(u), p), x, depth + 1);
+		}
+	    }
+	},
+	_ => {},
+    }
+
+    // table integrals of f(u) where u is linear in x
+    if let Some((big_f, u)) = table(e, x) {
+	if let Some(a) = linear_coefficient(&u, x) {
+	    return Ok(scale_by_inverse(big_f, a));
+	}
+    }
+
+    if let Mul(ref l, ref r) = e {
+	// constants buried inside both factors, ie 2x * (sin(x) / 2)
+	let (c, rest) = split_constant(e, x);
+	if rest != *e {
+	    let c = simplify(c);
+	    let big_f = integ(&rest, x, depth + 1)?;
+	    return Ok(if is_one(&c) { big_f } else { mul(c, big_f) });
+	}
+
+	let (l, r) = (unpack(l), unpack(r));
+
+	// u-substitution: f(u) * k*u' integrates to k * F(u)
+	for (g, h) in [(&l, &r), (&r, &l)].iter() {
+	    if let Some((big_f, u)) = table(g, x) {
+		if let Ok(du) = try_derive(u, x) {
+		    if let Some(k) = constant_ratio(h, &du, x) {
+			return Ok(mul(k, big_f));
+		    }
+		}
+	    }
+	}
+
+	// integration by parts: p * g = p * G - integral(p' * G)
+	for (p, g) in [(&l, &r), (&r, &l)].iter() {
+	    if is_polynomial(p, x) && parts_friendly(g, x) {
+		let big_g = integ(g, x, depth + 1)?;
+		let dp = simplify(try_derive((*p).clone(), x)?);
+		let rest = integ(&mul(dp, big_g.clone()), x, depth + 1)?;
+		return Ok(sub(mul((*p).clone(), big_g), rest));
+	    }
+	}
+    }
+
+    Err(cannot_integrate(e))
+}
+
+// Look up the antiderivative of a function applied to an inner
+// expression u, treating u as if it were the variable. Gives back
+// the antiderivative F(u) along with u, or None with no table entry.
+fn table(e: &Expr, x: Symbol) -> Option<(Expr, Expr)> {
+    let entry = match e {
+	Var(s) if *s == x => (mul(con(0.5), powf(var(x), 2.0)), var(x)),
+	Exp(ref u) => (exp(unpack(u)), unpack(u)),
+	Sin(ref u) => (neg(cos(unpack(u))), unpack(u)),
+	Cos(ref u) => (sin(unpack(u)), unpack(u)),
+	Sinh(ref u) => (cosh(unpack(u)), unpack(u)),
+	Cosh(ref u) => (sinh(unpack(u)), unpack(u)),
+	Ln(ref u) => {
+	    let u = unpack(u);
+	    (sub(mul(u.clone(), ln(u.clone())), u.clone()), u)
+	},
+	Asin(ref u) => {
+	    let u = unpack(u);
+	    let root = sqrt(sub(con(1.0), powf(u.clone(), 2.0)));
+	    (add(mul(u.clone(), asin(u.clone())), root), u)
+	},
+	Acos(ref u) => {
+	    let u = unpack(u);
+	    let root = sqrt(sub(con(1.0), powf(u.clone(), 2.0)));
+	    (sub(mul(u.clone(), acos(u.clone())), root), u)
+	},
+	Asinh(ref u) => {
+	    let u = unpack(u);
+	    let root = sqrt(add(powf(u.clone(), 2.0), con(1.0)));
+	    (sub(mul(u.clone(), asinh(u.clone())), root), u)
+	},
+	Acosh(ref u) => {
+	    let u = unpack(u);
+	    let root = sqrt(sub(powf(u.clone(), 2.0), con(1.0)));
+	    (sub(mul(u.clone(), acosh(u.clone())), root), u)
+	},
+	Abs(ref u) => {
+	    let u = unpack(u);
+	    (div(mul(u.clone(), abs(u.clone())), con(2.0)), u)
+	},
+	Digamma(ref u) => (ln_gamma(unpack(u)), unpack(u)),
+	Polygamma(ref n, ref u) => {
+	    match unpack(n) {
+		Const(k) if k.is_whole() && k.real() >= 1.0 => {
+		    (polygamma(con(k.real() - 1.0), unpack(u)), unpack(u))
+		},
+		_ => return None,
+	    }
+	},
+	// power rule, with u^-1 becoming ln|u|
+	Pow(ref u, ref n) if !n.has_var(x) => {
+	    let u = unpack(u);
+	    match unpack(n) {
+		Const(k) if k.real_eq(-1.0) => (ln(abs(u.clone())), u),
+		n => {
+		    let n1 = simplify(add(n, con(1.0)));
+		    (div(pow(u.clone(), n1.clone()), n1), u)
+		},
+	    }
+	},
+	// a^u = a^u / ln(a)
+	Pow(ref a, ref u) if !a.has_var(x) => {
+	    let u = unpack(u);
+	    (div(pow(unpack(a), u.clone()), ln(unpack(a))), u)
+	},
+	_ => return None,
+    };
+    Some(entry)
+}
+
+// The derivative of u if it doesn't depend on x (and isn't zero),
+// meaning u is linear in x
+fn linear_coefficient(u: &Expr, x: Symbol) -> Option<Expr> {
+    let du = simplify(try_derive(u.clone(), x).ok()?);
+    match du {
+	ref d if d.has_var(x) => None,
+	Const(c) if c.is_zero() || c.is_nan() => None,
+	d => Some(d),
+    }
+}
+
+fn is_one(e: &Expr) -> bool {
+    matches!(e, Const(c) if c.real_eq(1.0))
+}
+
+// F / a, skipping the division when a is one
+fn scale_by_inverse(f: Expr, a: Expr) -> Expr {
+    match a {
+	ref a if is_one(a) => f,
+	a => div(f, a),
+    }
+}
+
+// Split an expression into a factor without x and the rest,
+// so 3 * (2x) becomes (6, x)
+fn split_constant(e: &Expr, x: Symbol) -> (Expr, Expr) {
+    if !e.has_var(x) {
+	return (e.clone(), one());
+    }
+    match e {
+	Neg(ref f) => {
+	    let (c, rest) = split_constant(f, x);
+	    (neg(c), rest)
+	},
+	Mul(ref l, ref r) => {
+	    let (cl, rl) = split_constant(l, x);
+	    let (cr, rr) = split_constant(r, x);
+	    let rest = match (rl, rr) {
+		(Const(c), f) | (f, Const(c)) if c.real_eq(1.0) => f,
+		(a, b) => mul(a, b),
+	    };
+	    (mul(cl, cr), rest)
+	},
+	Div(ref f, ref c) if !c.has_var(x) => {
+	    let (cf, rest) = split_constant(f, x);
+	    (div(cf, unpack(c)), rest)
+	},
+	_ => (one(), e.clone()),
+    }
+}
+
+// Find k with a = k * b where k doesn't depend on x
+fn constant_ratio(a: &Expr, b: &Expr, x: Symbol) -> Option<Expr> {
+    let (ca, ra) = split_constant(&simplify(a.clone()), x);
+    let (cb, rb) = split_constant(&simplify(b.clone()), x);
+    if simplify(ra) == simplify(rb) {
+	let k = simplify(div(ca, cb));
+	match k {
+	    Const(c) if c.is_nan() || c.is_zero() => None,
+	    k => Some(k),
+	}
+    } else {
+	None
+    }
+}
+
+// Polynomials in x: constants, x, and sums, products and whole
+// positive powers of polynomials
+fn is_polynomial(e: &Expr, x: Symbol) -> bool {
+    if !e.has_var(x) {
+	return true;
+    }
+    match e {
+	Var(_) => true,
+	Neg(ref f) => is_polynomial(f, x),
+	Add(ref l, ref r) | Sub(ref l, ref r) | Mul(ref l, ref r) => {
+	    is_polynomial(l, x) && is_polynomial(r, x)
+	},
+	Div(ref f, ref c) => !c.has_var(x) && is_polynomial(f, x),
+	Pow(ref f, ref n) => {
+	    match n.as_ref() {
+		Const(k) => k.is_whole() && k.real() >= 0.0 && is_polynomial(f, x),
+		_ => false,
+	    }
+	},
+	_ => false,
+    }
+}
+
+// Functions which integrate to the same family over and over, so
+// repeated integration by parts against a polynomial terminates
+fn parts_friendly(e: &Expr, x: Symbol) -> bool {
+    let linear_inner = |u: &Expr| linear_coefficient(u, x).is_some();
     match e {
-        Const(c) => add(mul(Const(c), Var(sym)), var('C')),
-        _ => con(0.0),
+	Exp(ref u) | Sin(ref u) | Cos(ref u) | Sinh(ref u) | Cosh(ref u) => linear_inner(u),
+	Pow(ref a, ref u) if !a.has_var(x) => linear_inner(u),
+	e => is_polynomial(e, x),
     }
 }
 
 
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::expr::canonical::*;
+    use crate::expr::evaluator::*;
+    use crate::number::number::*;
+
+    // Check that derive(F) matches f at a handful of points
+    fn assert_antiderivative(f: Expr) {
+	let big_f = antiderivative(f.clone(), 'x')
+	    .unwrap_or_else(|e| panic!("failed to integrate {}: {}", f, e));
+	let df = derive(big_f.clone(), 'x');
+	for x0 in [0.3, 0.7, 1.1, 1.9, 2.6].iter() {
+	    let expected = evaluate(f.clone(), 'x', real(*x0));
+	    let got = evaluate(df.clone(), 'x', real(*x0));
+	    let diff = (&expected - &got).abs().real() / (1.0 + expected.abs().real());
+	    assert!(diff < 1e-9, "d/dx {} = {} but expected {} at x = {}", big_f, got, expected, x0);
+	}
+    }
+
+    #[test]
+    fn test_constants_and_powers() {
+	assert_antiderivative(con(3.0));
+	assert_antiderivative(var('x'));
+	assert_antiderivative(powf(var('x'), 3.0));
+	assert_antiderivative(sqrt(var('x')));
+	assert_antiderivative(powf(var('x'), -2.0));
+	assert_antiderivative(powf(add(varf('x', 3.0), con(1.0)), 4.0));
+
+	// high powers go through the polynomial path without a huge
+	// expansion, and come back exact
+	for s in ["(x - 1)^17", "(2x + 1)^30 - x"].iter() {
+	    let p = Polynomial::from_expr(&Expr::parse(s).unwrap(), 'x').unwrap();
+	    let big_f = antiderivative(Expr::parse(s).unwrap(), 'x').unwrap();
+	    assert_eq!(Polynomial::from_expr(&big_f, 'x').unwrap().derivative(), p);
+	}
+
+	let recip = antiderivative(div(con(1.0), var('x')), 'x').unwrap();
+	assert_eq!(recip, ln(abs(var('x'))));
+    }
+
+    #[test]
+    fn test_linearity() {
+	let f = Expr::parse("3x^2 - 4x + 7 + 2sin(x) - cos(x)/2").unwrap();
+	assert_antiderivative(f);
+	assert_antiderivative(neg(exp(var('x'))));
+	assert_antiderivative(mul(var('y'), var('x')));
+    }
+
+    #[test]
+    fn test_table_with_linear_argument() {
+	assert_antiderivative(sin(varf('x', 3.0)));
+	assert_antiderivative(cos(add(varf('x', 2.0), con(1.0))));
+	assert_antiderivative(exp(sub(con(1.0), var('x'))));
+	assert_antiderivative(pow(con(2.0), var('x')));
+
+	assert_antiderivative(ln(add(varf('x', 2.0), con(1.0))));
+	assert_antiderivative(asin(div(var('x'), con(4.0))));
+	assert_antiderivative(acos(div(var('x'), con(4.0))));
+	assert_antiderivative(asinh(varf('x', 3.0)));
+	assert_antiderivative(acosh(add(var('x'), con(2.0))));
+	assert_antiderivative(sinh(varf('x', 2.0)));
+	assert_antiderivative(cosh(var('x')));
+	assert_antiderivative(div(con(1.0), add(varf('x', 3.0), con(1.0))));
+
+	let l = antiderivative(ln(var('x')), 'x').unwrap();
+	assert_eq!(l, sub(mul(var('x'), ln(var('x'))), var('x')));
+	let d = antiderivative(digamma(var('x')), 'x').unwrap();
+	assert_eq!(d, ln_gamma(var('x')));
+    }
+
+    #[test]
+    fn test_substitution() {
+	assert_antiderivative(Expr::parse("2x * e^(x^2)").unwrap());
+	assert_antiderivative(Expr::parse("x * cos(x^2)").unwrap());
+	assert_antiderivative(Expr::parse("cos(x)^3 * sin(x)").unwrap());
+	assert_antiderivative(Expr::parse("x^2 * (x^3 + 1)^5").unwrap());
+	assert_antiderivative(Expr::parse("cos(x) / sin(x)").unwrap());
+	assert_antiderivative(Expr::parse("2x / (x^2 + 1)").unwrap());
+    }
+
+    #[test]
+    fn test_by_parts() {
+	assert_antiderivative(Expr::parse("x * sin(x)").unwrap());
+	assert_antiderivative(Expr::parse("x^2 * e^x").unwrap());
+	assert_antiderivative(Expr::parse("(x^2 + 1) * cos(2x)").unwrap());
+	assert_antiderivative(Expr::parse("x * (x + 1)").unwrap());
+	assert_antiderivative(Expr::parse("x * 2^x").unwrap());
+    }
+
+    #[test]
+    fn test_reciprocal_powers() {
+	assert_antiderivative(Expr::parse("1 / x^2").unwrap());
+	assert_antiderivative(Expr::parse("1 / (2x + 1)^2").unwrap());
+	assert_antiderivative(Expr::parse("3 / (x - 1)^3").unwrap());
+
+	let f = antiderivative(Expr::parse("1 / x^2").unwrap(), 'x').unwrap();
+	assert_eq!(f, neg(powf(var('x'), -1.0)));
+	assert_eq!(f, antiderivative(powf(var('x'), -2.0), 'x').unwrap());
+    }
+
+    #[test]
+    fn test_named_constants() {
+	// pi is kept as it is instead of being rounded to a float
+	let f = antiderivative(Expr::parse("pi x").unwrap(), 'x').unwrap();
+	assert!(equivalent(&f, &Expr::parse("pi/2 x^2").unwrap()), "{}", f);
+	let g = antiderivative(Expr::parse("e x^2 + 1").unwrap(), 'x').unwrap();
+	assert_eq!(g.to_string(), "e*(x^3/3) + x");
+    }
+
+    #[test]
+    fn test_cannot_integrate() {
+	assert!(matches!(antiderivative(gamma(var('x')), 'x'), Err(MathError::Unsupported(_))));
+	assert!(antiderivative(exp(powf(var('x'), 2.0)), 'x').is_err());
+	assert!(antiderivative(div(sin(var('x')), var('x')), 'x').is_err());
+
+	let c = integrate(con(2.0), 'x').unwrap();
+	assert_eq!(c, add(mul(con(2.0), var('x')), var('C')));
+    }
+}
 
 // end src/calc/integrator.rs
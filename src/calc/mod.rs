@@ -1,3 +1,4 @@
 pub mod deriver;
 pub mod integrator;
+pub mod partial;
 pub mod prelude;
@@ -0,0 +1,156 @@
+// src/calc/partial.rs
+// partial derivatives of multivariate expressions
+
+use crate::calc::deriver::*;
+use crate::error::*;
+use crate::expr::expr::*;
+use crate::expr::simplify::*;
+use crate::expr::symbol::*;
+use crate::expr::evaluator::*;
+use crate::number::number::*;
+
+/// Take mixed partial derivatives in the order given, where each
+/// pair is a variable and how many times to differentiate by it,
+/// ie `[('x', 2), ('y', 1)]` is `d^3 e / dx^2 dy`. Every other
+/// variable is held constant. The result is simplified after
+/// each step to keep it from growing.
+///
+/// ```
+/// use mathy::calc::prelude::*;
+/// let f = Expr::parse("x^3 * y").unwrap();
+/// let d = derive_n(&f, &[('x', 2), ('y', 1)]).unwrap();
+/// let env = Env::new().bind('x', real(2.0)).bind('y', real(5.0));
+/// assert_eq!(try_evaluate(&d, &env), Ok(real(12.0)));
+/// ```
+pub fn derive_n<S: Into<Symbol> + Copy>(e: &Expr, orders: &[(S, usize)]) -> Result<Expr, MathError> {
+    let mut d = e.clone();
+    for &(sym, order) in orders.iter() {
+	let sym = sym.into();
+	for _ in 0..order {
+	    d = simplify(try_derive(d, sym)?);
+	}
+    }
+    Ok(d)
+}
+
+/// The gradient of a scalar expression, one partial derivative
+/// for each of the given variables.
+///
+/// ```
+/// use mathy::calc::prelude::*;
+/// let f = Expr::parse("x^2 + 3y").unwrap();
+/// let g = gradient(&f, &['x', 'y']).unwrap();
+/// assert_eq!(g, vec![varf('x', 2.0), con(3.0)]);
+/// ```
+pub fn gradient<S: Into<Symbol> + Copy>(e: &Expr, syms: &[S]) -> Result<Vec<Expr>, MathError> {
+    syms.iter().map(|&s| derive_n(e, &[(s, 1)])).collect()
+}
+
+/// The Jacobian matrix of a list of expressions, where row `i`
+/// is the gradient of `exprs[i]` over the given variables.
+pub fn jacobian<S: Into<Symbol> + Copy>(exprs: &[Expr], syms: &[S]) -> Result<Vec<Vec<Expr>>, MathError> {
+    exprs.iter().map(|e| gradient(e, syms)).collect()
+}
+
+/// The Hessian matrix of second partial derivatives of a scalar
+/// expression. Mixed partials are only derived once and mirrored,
+/// since they are equal for any expression mathy can represent.
+pub fn hessian<S: Into<Symbol> + Copy>(e: &Expr, syms: &[S]) -> Result<Vec<Vec<Expr>>, MathError> {
+    let first = gradient(e, syms)?;
+    let n = syms.len();
+    let mut h = vec![vec![zero(); n]; n];
+    for i in 0..n {
+	for j in i..n {
+	    let d = derive_n(&first[i], &[(syms[j], 1)])?;
+	    h[j][i] = d.clone();
+	    h[i][j] = d;
+	}
+    }
+    Ok(h)
+}
+
+/// Evaluate each expression of a vector, like a gradient, at the
+/// point given by an `Env`. Fails on the first expression which
+/// can't be evaluated.
+pub fn evaluate_vector(v: &[Expr], env: &Env) -> Result<Vec<Number>, MathError> {
+    v.iter().map(|e| try_evaluate(e, env)).collect()
+}
+
+/// Evaluate each expression of a matrix, like a Jacobian or a
+/// Hessian, at the point given by an `Env`.
+///
+/// ```
+/// use mathy::calc::prelude::*;
+/// let fs = [Expr::parse("x * y").unwrap(), Expr::parse("x + y^2").unwrap()];
+/// let j = jacobian(&fs, &['x', 'y']).unwrap();
+/// let env = Env::new().bind('x', real(2.0)).bind('y', real(3.0));
+/// let m = evaluate_matrix(&j, &env).unwrap();
+/// assert_eq!(m, vec![vec![real(3.0), real(2.0)], vec![real(1.0), real(6.0)]]);
+/// ```
+pub fn evaluate_matrix(m: &[Vec<Expr>], env: &Env) -> Result<Vec<Vec<Number>>, MathError> {
+    m.iter().map(|row| evaluate_vector(row, env)).collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn point(x: f64, y: f64, z: f64) -> Env {
+	Env::new().bind('x', real(x)).bind('y', real(y)).bind('z', real(z))
+    }
+
+    #[test]
+    fn test_gradient() {
+	let f = Expr::parse("x^2 y + sin(z) + y").unwrap();
+	let g = gradient(&f, &['x', 'y', 'z']).unwrap();
+	let at = evaluate_vector(&g, &point(1.5, 2.0, 0.0)).unwrap();
+	assert_eq!(at, vec![real(6.0), real(3.25), real(1.0)]);
+
+	// variables not in the list are constants
+	let h = gradient(&f, &['x']).unwrap();
+	assert!(!h[0].has_var('z'));
+    }
+
+    #[test]
+    fn test_jacobian() {
+	// polar to cartesian coordinates, with determinant r
+	let fs = [Expr::parse("r cos(t)").unwrap(), Expr::parse("r sin(t)").unwrap()];
+	let j = jacobian(&fs, &["r", "t"]).unwrap();
+	let env = Env::new().bind("r", real(2.0)).bind("t", real(0.7));
+	let m = evaluate_matrix(&j, &env).unwrap();
+	let det = &(&m[0][0] * &m[1][1]) - &(&m[0][1] * &m[1][0]);
+	assert!((det - real(2.0)).abs().real() < 1e-12);
+
+	let missing = evaluate_matrix(&j, &Env::new().bind("r", real(1.0)));
+	assert!(matches!(missing, Err(MathError::UnboundVariable(_))));
+    }
+
+    #[test]
+    fn test_hessian() {
+	let f = Expr::parse("x^3 + x y^2 + e^z").unwrap();
+	let h = hessian(&f, &['x', 'y', 'z']).unwrap();
+	let m = evaluate_matrix(&h, &point(1.0, 2.0, 0.0)).unwrap();
+	let expected = [
+	    [6.0, 4.0, 0.0],
+	    [4.0, 2.0, 0.0],
+	    [0.0, 0.0, 1.0],
+	];
+	for i in 0..3 {
+	    for j in 0..3 {
+		assert_eq!(m[i][j], real(expected[i][j]));
+	    }
+	}
+    }
+
+    #[test]
+    fn test_derive_n() {
+	let f = Expr::parse("sin(x) * y^3").unwrap();
+	let d = derive_n(&f, &[('x', 4), ('y', 3)]).unwrap();
+	let v = try_evaluate(&d, &point(0.5, 7.0, 0.0)).unwrap();
+	assert!((v - real(6.0 * 0.5f64.sin())).abs().real() < 1e-12);
+	assert_eq!(derive_n(&f, &[('x', 0)]).unwrap(), f);
+    }
+}
+
+// end src/calc/partial.rs
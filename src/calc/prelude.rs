@@ -2,9 +2,26 @@
 
 pub use crate::calc::deriver::*;
 pub use crate::calc::integrator::*;
+pub use crate::calc::partial::*;
+pub use crate::poly::polynomial::*;
+pub use crate::poly::multivariate::*;
 pub use crate::expr::expr::*;
+pub use crate::expr::visit::*;
+pub use crate::expr::symbol::*;
+pub use crate::expr::constant::*;
 pub use crate::expr::simplify::*;
+pub use crate::expr::canonical::*;
+pub use crate::expr::rewrite::*;
+pub use crate::expr::algebra::*;
 pub use crate::expr::evaluator::*;
+pub use crate::expr::parser::*;
+pub use crate::expr::display::*;
+pub use crate::expr::pretty::*;
+pub use crate::expr::compile::*;
+pub use crate::expr::batch::*;
+pub use crate::expr::dag::*;
+pub use crate::expr::cse::*;
 pub use crate::number::number::*;
+pub use crate::error::*;
 
 // end prelude
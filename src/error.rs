@@ -0,0 +1,45 @@
+// src/error.rs
+
+use std::fmt;
+
+use crate::expr::symbol::*;
+
+/// The error type shared by the `try_` functions across the crate,
+/// used to tell a legitimate NaN result apart from an operation
+/// that failed or isn't implemented.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathError {
+    /// The input is outside of where the function is defined, ie ln(0)
+    DomainError(String),
+    DivisionByZero,
+    /// The result is too large to be represented
+    Overflow(String),
+    /// The listed variables had no value during evaluation
+    UnboundVariable(Vec<Symbol>),
+    /// The operation is not implemented for the given input
+    Unsupported(String),
+    /// An iterative method ran out of steps before reaching its tolerance
+    NotConverged(String),
+}
+
+use self::MathError::*;
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    DomainError(s) => write!(f, "domain error: {}", s),
+	    DivisionByZero => write!(f, "division by zero"),
+	    Overflow(s) => write!(f, "overflow: {}", s),
+	    UnboundVariable(syms) => {
+		let names: Vec<&str> = syms.iter().map(|s| s.name()).collect();
+		write!(f, "unbound variables: {}", names.join(", "))
+	    },
+	    Unsupported(s) => write!(f, "unsupported: {}", s),
+	    NotConverged(s) => write!(f, "did not converge: {}", s),
+	}
+    }
+}
+
+impl std::error::Error for MathError {}
+
+// end src/error.rs
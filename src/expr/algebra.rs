@@ -0,0 +1,711 @@
+// src/expr/algebra.rs
+// expansion, factoring and collection of polynomials
+
+use std::convert::TryFrom;
+
+use crate::error::*;
+use crate::expr::canonical::*;
+use crate::expr::evaluator::*;
+use crate::expr::expr::*;
+use crate::expr::expr::Expr::*;
+use crate::expr::symbol::*;
+use crate::number::bigint::*;
+use crate::number::number::*;
+use crate::number::rational::*;
+
+/// Multiply out every product of sums and every whole power of a
+/// sum, giving back a sum of collected terms in canonical order,
+/// highest degree first. Anything inside a
+/// function is expanded too, but the function itself is left
+/// alone, so `sin(2(x + 1))` becomes `sin(2x + 2)`.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("(x + 1)^3").unwrap();
+/// assert_eq!(expand(f), Expr::parse("x^3 + 3x^2 + 3x + 1").unwrap());
+/// ```
+pub fn expand(e: Expr) -> Expr {
+    tidy(&expand_canonical(&e))
+}
+
+// expand, leaving the result in canonical form with negative
+// coefficients rather than subtraction
+fn expand_canonical(e: &Expr) -> Expr {
+    canonical(balanced_sum(expand_terms(e)))
+}
+
+// Turn the negative coefficients of a canonical form back into
+// subtraction and negation, so x^2 + (-1) reads as x^2 - 1
+fn tidy(e: &Expr) -> Expr {
+    let negative = |k: &Number| k.imag() == 0.0 && k.real() < 0.0;
+    match e.map_children(tidy) {
+	Add(ref l, ref r) => {
+	    match unpack(r) {
+		Const(k) if negative(&k) => sub(unpack(l), Const(-k)),
+		Neg(ref m) => sub(unpack(l), unpack(m)),
+		r => add(unpack(l), r),
+	    }
+	},
+	Mul(ref l, ref r) => {
+	    match (unpack(l), unpack(r)) {
+		(l, Div(ref n, ref d)) if **n == one() => div(l, unpack(d)),
+		(Const(k), r) if k.real_eq(-1.0) => neg(r),
+		(Const(k), r) if negative(&k) => neg(mul(Const(-k), r)),
+		(l, r) => mul(l, r),
+	    }
+	},
+	// and negative powers into division, so x y^-1 reads as x / y
+	Pow(ref b, ref p) => {
+	    match unpack(p) {
+		Const(k) if k.real_eq(-1.0) => div(one(), unpack(b)),
+		Const(k) if negative(&k) => div(one(), pow(unpack(b), Const(-k))),
+		p => pow(unpack(b), p),
+	    }
+	},
+	e => e,
+    }
+}
+
+fn sum_of(terms: Vec<Expr>) -> Expr {
+    terms.into_iter().reduce(add).unwrap_or_else(zero)
+}
+
+// A sum nested evenly rather than down the left, so walking a
+// long sum doesn't recurse once per term
+fn balanced_sum(mut terms: Vec<Expr>) -> Expr {
+    if terms.len() <= 1 {
+	return terms.pop().unwrap_or_else(zero);
+    }
+    let right = terms.split_off(terms.len() / 2);
+    add(balanced_sum(terms), balanced_sum(right))
+}
+
+fn product_of(factors: Vec<Expr>) -> Expr {
+    factors.into_iter().reduce(mul).unwrap_or_else(one)
+}
+
+// Every term of the fully expanded sum, without collecting them
+fn expand_terms(e: &Expr) -> Vec<Expr> {
+    match e {
+	Add(ref l, ref r) => {
+	    let mut t = expand_terms(l);
+	    t.extend(expand_terms(r));
+	    t
+	},
+	Sub(ref l, ref r) => {
+	    let mut t = expand_terms(l);
+	    t.extend(expand_terms(r).into_iter().map(|x| mul(Const(integer(-1)), x)));
+	    t
+	},
+	Neg(ref i) => expand_terms(i).into_iter().map(|x| mul(Const(integer(-1)), x)).collect(),
+	Mul(ref l, ref r) => distribute(&expand_terms(l), &expand_terms(r)),
+	Div(ref l, ref r) => {
+	    let d = expand_canonical(r);
+	    expand_terms(l).into_iter().map(|x| div(x, d.clone())).collect()
+	},
+	Pow(ref b, ref p) => {
+	    match p.as_ref() {
+		Const(n) if n.is_whole() && n.real() >= 1.0 => {
+		    // collect like terms after each multiplication, so
+		    // (x + 1)^n has n + 1 terms rather than 2^n
+		    let base = expand_terms(b);
+		    let mut t = base.clone();
+		    for _ in 1..(n.real() as usize) {
+			t = terms_of(&canonical(balanced_sum(distribute(&t, &base))));
+		    }
+		    t
+		},
+		p => vec![pow(expand_canonical(b), expand_canonical(p))],
+	    }
+	},
+	Const(_) | Var(_) => vec![e.clone()],
+	e => vec![e.map_children(expand_canonical)],
+    }
+}
+
+fn distribute(a: &[Expr], b: &[Expr]) -> Vec<Expr> {
+    a.iter().flat_map(|x| b.iter().map(move |y| mul(x.clone(), y.clone()))).collect()
+}
+
+fn terms_of(e: &Expr) -> Vec<Expr> {
+    match e {
+	Add(ref l, ref r) => {
+	    let mut t = terms_of(l);
+	    t.extend(terms_of(r));
+	    t
+	},
+	e => vec![e.clone()],
+    }
+}
+
+fn factors_of(e: &Expr) -> Vec<Expr> {
+    match e {
+	Mul(ref l, ref r) => {
+	    let mut f = factors_of(l);
+	    f.extend(factors_of(r));
+	    f
+	},
+	e => vec![e.clone()],
+    }
+}
+
+// Split an expanded term into its power of sym and the
+// coefficient, or None if sym appears in any other way
+fn term_power(t: &Expr, sym: Symbol) -> Option<(usize, Expr)> {
+    let mut power = 0;
+    let mut rest = Vec::new();
+    for f in factors_of(t) {
+	match f {
+	    Var(s) if s == sym => power += 1,
+	    Pow(ref b, ref p) if **b == Var(sym) => {
+		match p.as_ref() {
+		    Const(n) if n.is_whole() && n.real() >= 0.0 => power += n.real() as usize,
+		    _ => return None,
+		}
+	    },
+	    f if f.has_var(sym) => return None,
+	    f => rest.push(f),
+	}
+    }
+    Some((power, canonical(product_of(rest))))
+}
+
+/// Find the coefficients of a polynomial in `sym`, lowest power
+/// first, so `3x^2 + 2` gives `[2, 0, 3]`. The coefficients can
+/// contain other variables. Gives back an empty list for anything
+/// which isn't a polynomial in `sym`; use `try_coefficients`
+/// to find out why.
+pub fn coefficients<S: Into<Symbol>>(e: Expr, sym: S) -> Vec<Expr> {
+    try_coefficients(e, sym).unwrap_or_default()
+}
+
+/// Find the coefficients of a polynomial in `sym`, lowest power
+/// first, reporting an `Unsupported` error if the expression
+/// isn't a polynomial in `sym`.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("(a x + 1)^2").unwrap();
+/// let c = try_coefficients(f, 'x').unwrap();
+/// assert_eq!(c, vec![con(1.0), varf('a', 2.0), powf(var('a'), 2.0)]);
+/// assert!(try_coefficients(Expr::parse("sin(x)").unwrap(), 'x').is_err());
+/// ```
+pub fn try_coefficients<S: Into<Symbol>>(e: Expr, sym: S) -> Result<Vec<Expr>, MathError> {
+    let sym = sym.into();
+    let expanded = expand_canonical(&e);
+    let mut sums: Vec<Vec<Expr>> = Vec::new();
+    for t in terms_of(&expanded) {
+	let (n, c) = term_power(&t, sym).ok_or_else(|| {
+	    MathError::Unsupported(format!("{} is not a polynomial in {}", tidy(&expanded), sym))
+	})?;
+	if sums.len() <= n {
+	    sums.resize(n + 1, Vec::new());
+	}
+	sums[n].push(c);
+    }
+    if sums.is_empty() {
+	return Ok(vec![zero()]);
+    }
+    Ok(sums.into_iter().map(|c| tidy(&canonical(sum_of(c)))).collect())
+}
+
+/// Group the terms of an expression by powers of `sym`, highest
+/// power first, ie `a x + b x + c` becomes `(a + b) x + c`. Terms
+/// where `sym` appears some other way, like `sin(x)`, are kept
+/// at the end.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("a x^2 + x + b x^2 + x + 3").unwrap();
+/// assert_eq!(collect(f, 'x'), Expr::parse("(a + b) x^2 + 2x + 3").unwrap());
+/// ```
+pub fn collect<S: Into<Symbol>>(e: Expr, sym: S) -> Expr {
+    let sym = sym.into();
+    let mut sums: Vec<Vec<Expr>> = Vec::new();
+    let mut others = Vec::new();
+    for t in terms_of(&expand_canonical(&e)) {
+	match term_power(&t, sym) {
+	    Some((n, c)) => {
+		if sums.len() <= n {
+		    sums.resize(n + 1, Vec::new());
+		}
+		sums[n].push(c);
+	    },
+	    None => others.push(t),
+	}
+    }
+
+    let mut out = Vec::new();
+    for (n, cs) in sums.into_iter().enumerate().rev() {
+	let c = canonical(sum_of(cs));
+	if c.is_const() && c == zero() {
+	    continue;
+	}
+	out.push(monomial(c, sym, n));
+    }
+    out.extend(others);
+    tidy(&sum_of(out))
+}
+
+// c * sym^n, leaving out what isn't needed
+fn monomial(c: Expr, sym: Symbol, n: usize) -> Expr {
+    let xn = match n {
+	0 => return c,
+	1 => var(sym),
+	n => powf(var(sym), n as f64),
+    };
+    match c {
+	Const(k) if k.real_eq(1.0) => xn,
+	Const(k) if k.real_eq(-1.0) => neg(xn),
+	c => mul(c, xn),
+    }
+}
+
+/// Factor an expression over the rationals. Polynomials in one
+/// variable with rational coefficients are split into their
+/// content, linear factors from the rational root theorem, real
+/// quadratic factors, and whatever is left. The coefficients are
+/// used exactly, so a polynomial with a floating point coefficient
+/// which isn't a whole number is left alone. Otherwise common
+/// factors of every term are pulled out, and differences of
+/// squares are split. Anything which can't be factored comes
+/// back expanded.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("2x^3 - 2x").unwrap();
+/// assert_eq!(factor(f), Expr::parse("2x (x - 1) (x + 1)").unwrap());
+///
+/// let g = Expr::parse("x^2 y - y^3").unwrap();
+/// assert_eq!(factor(g), Expr::parse("y (x - y) (x + y)").unwrap());
+/// ```
+pub fn factor(e: Expr) -> Expr {
+    tidy(&factor_canonical(&e))
+}
+
+fn factor_canonical(e: &Expr) -> Expr {
+    let expanded = expand_canonical(e);
+    let vars = Env::new().unbound(&expanded);
+    if vars.len() == 1 {
+	if let Some(f) = factor_univariate(&expanded, vars[0]) {
+	    return f;
+	}
+    }
+    factor_terms(expanded)
+}
+
+// An exact coefficient as a fraction. Whole floats are taken as
+// they are, but any other float can't be trusted to be the
+// fraction it looks like.
+fn exact_coefficient(n: &Number) -> Option<BigRational> {
+    match n {
+	Number::Real(v) if v.fract() == 0.0 => BigRational::from_f64(*v),
+	n => n.to_rational(),
+    }
+}
+
+// Split n into k^2 m, taking out the small square factors
+fn square_part(n: i128) -> (i128, i128) {
+    let (mut k, mut m) = (1, n);
+    let mut i = 2;
+    while i * i <= m && i < 100_000 {
+	while m % (i * i) == 0 {
+	    m /= i * i;
+	    k *= i;
+	}
+	i += 1;
+    }
+    (k, m)
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+	let t = a % b;
+	a = b;
+	b = t;
+    }
+    a
+}
+
+fn divisors(n: i128) -> Vec<i128> {
+    let n = n.abs();
+    let mut d = Vec::new();
+    let mut i = 1;
+    while i * i <= n {
+	if n % i == 0 {
+	    d.push(i);
+	    if i != n / i {
+		d.push(n / i);
+	    }
+	}
+	i += 1;
+    }
+    d.sort();
+    d
+}
+
+// The univariate path of factor, with polynomials as integer
+// coefficients lowest power first. Gives back None when the
+// coefficients aren't all rational, or grow too big to write out.
+fn factor_univariate(e: &Expr, x: Symbol) -> Option<Expr> {
+    let coeffs = try_coefficients(e.clone(), x).ok()?;
+    let mut fracs = Vec::new();
+    for c in coeffs.iter() {
+	match c {
+	    Const(n) => match exact_coefficient(n) {
+		Some(q) => fracs.push(q),
+		// floating point coefficients are left alone
+		None => return Some(e.clone()),
+	    },
+	    _ => return None,
+	}
+    }
+
+    // scale up to integers, then pull out the content with the
+    // sign of the leading coefficient
+    let lcm = fracs.iter().fold(BigInt::one(), |l, q| {
+	let d = q.denom();
+	&(&l / &l.gcd(d)) * d
+    });
+    let mut p: Vec<i128> = Vec::new();
+    for q in fracs.iter() {
+	let n = &(q.numer() * &lcm) / q.denom();
+	p.push(n.to_i64()? as i128);
+    }
+    while p.len() > 1 && *p.last().unwrap() == 0 {
+	p.pop();
+    }
+    let mut content = p.iter().fold(0, |g, &c| gcd(g, c));
+    if content == 0 {
+	return Some(zero());
+    }
+    if *p.last().unwrap() < 0 {
+	content = -content;
+    }
+    p.iter_mut().for_each(|c| *c /= content);
+
+    let mut factors: Vec<(Expr, usize)> = Vec::new();
+
+    // powers of x
+    let low = p.iter().take_while(|&&c| c == 0).count();
+    p.drain(..low);
+    if low > 0 {
+	factors.push((var(x), low));
+    }
+
+    // rational roots p/q, where p divides the constant term
+    // and q divides the leading coefficient
+    if p.len() > 2 && p[0].abs() < 1_000_000_000_000 && p.last().unwrap().abs() < 1_000_000_000_000 {
+	let mut candidates = Vec::new();
+	for q in divisors(*p.last().unwrap()) {
+	    for r in divisors(p[0]) {
+		if gcd(r, q) == 1 {
+		    candidates.push((r, q));
+		    candidates.push((-r, q));
+		}
+	    }
+	}
+	candidates.sort_by(|a, b| {
+	    let (x, y) = (a.0 as f64 / a.1 as f64, b.0 as f64 / b.1 as f64);
+	    x.abs().total_cmp(&y.abs()).then(y.total_cmp(&x))
+	});
+	for (r, q) in candidates {
+	    let mut count = 0;
+	    while p.len() > 1 {
+		match deflate(&p, r, q) {
+		    Some(b) => { p = b; count += 1; },
+		    None => break,
+		}
+	    }
+	    if count > 0 {
+		factors.push((linear(x, r, q)?, count));
+	    }
+	}
+    } else if p.len() == 2 {
+	// already linear, keep it as one factor
+	factors.push((linear(x, -p[0], p[1])?, 1));
+	p = vec![1];
+    }
+
+    // a quadratic left over has irrational or complex roots
+    if p.len() == 3 {
+	let (c, b, a) = (p[0], p[1], p[2]);
+	let disc = b.checked_mul(b)?.checked_sub(a.checked_mul(c)?.checked_mul(4)?)?;
+	if disc > 0 {
+	    // the roots are (-b +- k sqrt(m)) / 2a, in lowest terms
+	    let (k, m) = square_part(disc);
+	    let g = gcd(gcd(b, k), 2 * a);
+	    let (b, k, d) = (b / g, k / g, 2 * a / g);
+	    let surd = if k == 1 { sqrt(int(m)?) } else { mul(int(k)?, sqrt(int(m)?)) };
+	    let over = |t: Expr| if d == 1 { Some(t) } else { Some(div(t, int(d)?)) };
+	    content *= a;
+	    if b == 0 {
+		factors.push((sub(var(x), over(surd.clone())?), 1));
+		factors.push((add(var(x), over(surd)?), 1));
+	    } else {
+		factors.push((sub(var(x), over(add(int(-b)?, surd.clone()))?), 1));
+		factors.push((sub(var(x), over(sub(int(-b)?, surd))?), 1));
+	    }
+	    p = vec![1];
+	}
+    }
+
+    let mut out = Vec::new();
+    let k = Number::from(BigRational::new(BigInt::from(i64::try_from(content).ok()?), lcm));
+    if !k.real_eq(1.0) {
+	out.push(Const(k));
+    }
+    for (f, n) in factors {
+	out.push(if n == 1 { f } else { powf(f, n as f64) });
+    }
+    if p.len() > 1 || out.is_empty() {
+	let rest = p.iter().enumerate().map(|(i, &c)| Some(mul(int(c)?, powf(var(x), i as f64))));
+	out.push(canonical(sum_of(rest.collect::<Option<_>>()?)));
+    }
+    Some(product_of(out))
+}
+
+// Divide an integer polynomial by (q x - r), if it divides exactly
+fn deflate(p: &[i128], r: i128, q: i128) -> Option<Vec<i128>> {
+    let n = p.len() - 1;
+    let mut b = vec![0i128; n];
+    // working from the highest power down, a_k = q b_(k-1) - r b_k
+    let mut carry = 0i128;
+    for k in (1..=n).rev() {
+	let top = p[k].checked_add(r.checked_mul(carry)?)?;
+	if top % q != 0 {
+	    return None;
+	}
+	b[k - 1] = top / q;
+	carry = b[k - 1];
+    }
+    if p[0].checked_add(r.checked_mul(carry)?)? != 0 {
+	return None;
+    }
+    Some(b)
+}
+
+// The factor q x - r
+fn linear(x: Symbol, r: i128, q: i128) -> Option<Expr> {
+    let qx = if q == 1 { var(x) } else { mul(int(q)?, var(x)) };
+    Some(match r {
+	0 => qx,
+	r if r > 0 => sub(qx, int(r)?),
+	r => add(qx, int(-r)?),
+    })
+}
+
+// An exact integer constant, or None when it doesn't fit an i64
+fn int(n: i128) -> Option<Expr> {
+    Some(Const(integer(i64::try_from(n).ok()?)))
+}
+
+// A term of a canonical sum as its numeric coefficient and
+// its factors with their numeric powers
+fn split_term(t: &Expr) -> (Number, Vec<(Expr, f64)>) {
+    let mut k = integer(1);
+    let mut fs = Vec::new();
+    for f in factors_of(t) {
+	match f {
+	    Const(c) => k = k * c,
+	    Pow(ref b, ref p) => match p.as_ref() {
+		Const(n) if n.imag() == 0.0 => fs.push((unpack(b), n.real())),
+		_ => fs.push((f.clone(), 1.0)),
+	    },
+	    f => fs.push((f, 1.0)),
+	}
+    }
+    (k, fs)
+}
+
+fn join_term(k: Number, fs: &[(Expr, f64)]) -> Expr {
+    let mut parts = Vec::new();
+    if !k.real_eq(1.0) || fs.is_empty() {
+	parts.push(Const(k));
+    }
+    for (b, n) in fs.iter() {
+	if *n == 1.0 {
+	    parts.push(b.clone());
+	} else if *n != 0.0 {
+	    parts.push(powf(b.clone(), *n));
+	}
+    }
+    product_of(parts)
+}
+
+// The multivariate path of factor: pull the common factor out of
+// every term, then try a difference of squares on what's left
+fn factor_terms(e: Expr) -> Expr {
+    let terms: Vec<(Number, Vec<(Expr, f64)>)> = terms_of(&e).iter().map(split_term).collect();
+    if terms.len() < 2 {
+	return e;
+    }
+
+    // bases in every term, with the smallest positive power
+    let mut common: Vec<(Expr, f64)> = Vec::new();
+    for (b, n) in terms[0].1.iter() {
+	let lowest = terms.iter().map(|(_, fs)| {
+	    fs.iter().find(|(fb, _)| fb == b).map(|(_, m)| *m).unwrap_or(0.0)
+	}).fold(*n, f64::min);
+	if lowest > 0.0 {
+	    common.push((b.clone(), lowest));
+	}
+    }
+
+    // numeric content when every coefficient is a whole number
+    let mut content = terms.iter().try_fold(0i128, |g, (k, _)| {
+	if k.is_whole() && k.real().abs() < 1e15 {
+	    Some(gcd(g, k.real() as i128))
+	} else {
+	    None
+	}
+    }).unwrap_or(1);
+    if terms[0].0.real() < 0.0 {
+	content = -content;
+    }
+    let whole = match i64::try_from(content) {
+	Ok(c) => integer(c),
+	Err(_) => return e,
+    };
+
+    if common.is_empty() && content == 1 {
+	return difference_of_squares(&terms).unwrap_or(e);
+    }
+
+    let rest: Vec<Expr> = terms.iter().map(|(k, fs)| {
+	let reduced: Vec<(Expr, f64)> = fs.iter().map(|(b, n)| {
+	    let c = common.iter().find(|(cb, _)| cb == b).map(|(_, m)| *m).unwrap_or(0.0);
+	    (b.clone(), n - c)
+	}).collect();
+	join_term(k / &whole, &reduced)
+    }).collect();
+
+    let mut out = Vec::new();
+    if content != 1 {
+	out.push(Const(whole));
+    }
+    for (b, n) in common {
+	out.push(if n == 1.0 { b } else { powf(b, n) });
+    }
+    for f in factors_of(&factor_canonical(&sum_of(rest))) {
+	out.push(f);
+    }
+    product_of(out)
+}
+
+// a^2 - b^2 = (a - b)(a + b), factoring each side further
+fn difference_of_squares(terms: &[(Number, Vec<(Expr, f64)>)]) -> Option<Expr> {
+    if terms.len() != 2 {
+	return None;
+    }
+    let (pos, negt) = if terms[1].0.real() < 0.0 { (&terms[0], &terms[1]) } else { (&terms[1], &terms[0]) };
+    let a = square_root(pos.0.clone(), &pos.1)?;
+    let b = square_root(-&negt.0, &negt.1)?;
+    let left = factor_canonical(&sub(a.clone(), b.clone()));
+    let right = factor_canonical(&add(a, b));
+    let mut out = factors_of(&left);
+    out.extend(factors_of(&right));
+    Some(product_of(out))
+}
+
+fn square_root(k: Number, fs: &[(Expr, f64)]) -> Option<Expr> {
+    if k.imag() != 0.0 || k.real() <= 0.0 {
+	return None;
+    }
+    let r = k.real().sqrt();
+    if r.fract() != 0.0 || fs.iter().any(|(_, n)| n % 2.0 != 0.0) {
+	return None;
+    }
+    let halves: Vec<(Expr, f64)> = fs.iter().map(|(b, n)| (b.clone(), n / 2.0)).collect();
+    Some(join_term(real(r), &halves))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(s: &str) -> Expr {
+	Expr::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_expand() {
+	assert_eq!(expand(parse("(x + 1)(x - 1)")), parse("x^2 - 1"));
+	let xy = mul(con(2.0), mul(var('x'), var('y')));
+	assert_eq!(expand(parse("(x + y)^2")), add(add(powf(var('x'), 2.0), xy), powf(var('y'), 2.0)));
+	assert_eq!(expand(parse("2(x + 1) - 2x")), con(2.0));
+	assert_eq!(expand(parse("(x + 1) / y")), parse("x / y + 1 / y"));
+	assert_eq!(expand(parse("sin(2(x + 1))")), parse("sin(2x + 2)"));
+	assert!(equivalent(&expand(parse("(a + b)^3 - a^3 - b^3")), &parse("3a^2 b + 3a b^2")));
+
+	// high powers stay collected along the way
+	let c = coefficients(parse("(x + 1)^30"), 'x');
+	assert_eq!(c.len(), 31);
+	assert_eq!(c[15], Const(integer(155117520)));
+	assert_eq!(c[30], one());
+	assert_eq!(terms_of(&expand(parse("(x + y + 1)^20"))).len(), 231);
+    }
+
+    #[test]
+    fn test_coefficients() {
+	assert_eq!(coefficients(parse("3x^2 + 2"), 'x'), vec![con(2.0), zero(), con(3.0)]);
+	assert_eq!(coefficients(parse("y"), 'x'), vec![var('y')]);
+	assert_eq!(coefficients(parse("0"), 'x'), vec![zero()]);
+	assert!(coefficients(parse("x^0.5"), 'x').is_empty());
+	assert!(matches!(try_coefficients(parse("1 / x"), 'x'), Err(MathError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_collect() {
+	assert_eq!(collect(parse("x y + x z + y"), 'x'), parse("(y + z) x + y"));
+	assert_eq!(collect(parse("x - x^2 + sin(x)"), 'x'), parse("-x^2 + x + sin(x)"));
+	assert_eq!(collect(parse("x - x"), 'x'), zero());
+    }
+
+    #[test]
+    fn test_factor_univariate() {
+	assert_eq!(factor(parse("x^2 - 1")), parse("(x - 1)(x + 1)"));
+	assert_eq!(factor(parse("x^2 + 2x + 1")), parse("(x + 1)^2"));
+	assert_eq!(factor(parse("2x^2 - 3x + 1")), parse("(2x - 1)(x - 1)"));
+	assert_eq!(factor(parse("x^4 - 1")), parse("(x - 1)(x + 1)(x^2 + 1)"));
+	assert_eq!(factor(parse("x^2/2 - 1/2")), mul(mul(Const(rational(1, 2)), parse("x - 1")), parse("x + 1")));
+	assert_eq!(factor(parse("x^2/3 + x/7 + 1/11")).to_string(), "(1/231)(77x^2 + 33x + 21)");
+	assert_eq!(factor(parse("3x + 6")), parse("3 (x + 2)"));
+	// irreducible over the reals
+	assert_eq!(factor(parse("x^2 + x + 1")), parse("x^2 + x + 1"));
+
+	let q = factor(parse("x^2 - 2"));
+	let roots = [2f64.sqrt(), -(2f64.sqrt())];
+	for r in roots.iter() {
+	    assert!(evaluate(q.clone(), 'x', real(*r)).abs().real() < 1e-12);
+	}
+	assert_eq!(q.to_string(), "(x - 2^0.5)*(x + 2^0.5)");
+	assert_eq!(factor(parse("x^2 - 1/3")).to_string(), "(x - 3^0.5/3)*(x + 3^0.5/3)");
+	assert_eq!(factor(parse("x^2 - 2x - 1")).to_string(), "(x - (1 + 2^0.5))*(x - (1 - 2^0.5))");
+
+	// floating point coefficients aren't trusted to be fractions
+	let f = add(powf(var('x'), 2.0), con(-0.3));
+	assert_eq!(factor(f).to_string(), "x^2 - 0.3");
+
+	// roots over 2^63 + 2 don't fit an i64, so the sum is kept
+	let big = parse("4611686018427387905x^2 + 3x - 1");
+	assert_eq!(factor(big.clone()), big);
+    }
+
+    #[test]
+    fn test_factor_terms() {
+	assert_eq!(factor(parse("2x y + 4x")), parse("2x (y + 2)"));
+	assert_eq!(factor(parse("x^2 - y^2")), parse("(x - y)(x + y)"));
+	assert_eq!(factor(parse("4a^2 - 9b^2")), parse("(2a - 3b)(2a + 3b)"));
+	assert_eq!(factor(parse("sin(x) y + sin(x)")), parse("sin(x) (y + 1)"));
+	assert_eq!(factor(parse("x + y")), parse("x + y"));
+
+	// factoring and expanding again gives back the same sum
+	let f = parse("x^3 y - x y^3");
+	assert!(equivalent(&expand(factor(f.clone())), &expand(f)));
+    }
+}
+
+// end src/expr/algebra.rs
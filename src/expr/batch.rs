@@ -0,0 +1,165 @@
+// src/expr/batch.rs
+
+use std::thread;
+
+use crate::error::*;
+use crate::expr::expr::*;
+use crate::expr::symbol::*;
+use crate::number::number::*;
+
+
+/// Evaluate an `Expr` of one variable at every point in a slice.
+/// The tree is compiled once and shared by every point, so this is
+/// much faster than calling `evaluate` in a loop. A point where the
+/// evaluation fails gives NaN, and if the expression has any other
+/// variable every point is NaN, the same as with `evaluate`.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("1 / x").unwrap();
+/// let ys = evaluate_many(&f, 'x', &[real(2.0), real(0.0), real(4.0)]);
+/// assert_eq!(ys[0], real(0.5));
+/// assert!(ys[1].is_nan());
+/// assert_eq!(ys[2], real(0.25));
+/// ```
+pub fn evaluate_many<S: Into<Symbol>>(e: &Expr, sym: S, xs: &[Number]) -> Vec<Number> {
+    evaluate_many_threaded(e, sym, xs, 1)
+}
+
+/// Like `evaluate_many`, but splits the points into chunks evaluated
+/// on up to `threads` threads at once. The results are in the same
+/// order as the points.
+pub fn evaluate_many_threaded<S: Into<Symbol>>(e: &Expr, sym: S, xs: &[Number], threads: usize) -> Vec<Number> {
+    match e.compile(&[sym.into()]) {
+	Ok(f) => chunked(xs, threads, |x| f.eval(std::slice::from_ref(x))),
+	Err(_) => vec![nan(); xs.len()],
+    }
+}
+
+/// Evaluate at every point in a slice, reporting what went wrong at
+/// each point like `try_evaluate` instead of giving NaN.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("ln(x)").unwrap();
+/// let ys = try_evaluate_many(&f, 'x', &[real(1.0), real(0.0)]);
+/// assert_eq!(ys[0], Ok(real(0.0)));
+/// assert!(matches!(ys[1], Err(MathError::DomainError(_))));
+/// ```
+pub fn try_evaluate_many<S: Into<Symbol>>(e: &Expr, sym: S, xs: &[Number]) -> Vec<Result<Number, MathError>> {
+    match e.compile(&[sym.into()]) {
+	Ok(f) => xs.iter().map(|x| f.try_eval(std::slice::from_ref(x))).collect(),
+	Err(err) => vec![Err(err); xs.len()],
+    }
+}
+
+/// Evaluate an `Expr` of two variables at every pair of an x and a
+/// y value, giving one row per x value so that `grid[i][j]` is the
+/// value at `(xs[i], ys[j])`. Failures give NaN as in `evaluate_many`.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("x * y").unwrap();
+/// let grid = evaluate_grid(&f, 'x', &[real(1.0), real(2.0)], 'y', &[real(3.0), real(4.0), real(5.0)]);
+/// assert_eq!(grid.len(), 2);
+/// assert_eq!(grid[1], vec![real(6.0), real(8.0), real(10.0)]);
+/// ```
+pub fn evaluate_grid<S, T>(e: &Expr, x: S, xs: &[Number], y: T, ys: &[Number]) -> Vec<Vec<Number>>
+where S: Into<Symbol>, T: Into<Symbol> {
+    evaluate_grid_threaded(e, x, xs, y, ys, 1)
+}
+
+/// Like `evaluate_grid`, but with the rows shared out between up
+/// to `threads` threads.
+pub fn evaluate_grid_threaded<S, T>(e: &Expr, x: S, xs: &[Number], y: T, ys: &[Number], threads: usize) -> Vec<Vec<Number>>
+where S: Into<Symbol>, T: Into<Symbol> {
+    match e.compile(&[x.into(), y.into()]) {
+	Ok(f) => {
+	    chunked(xs, threads, |x| {
+		ys.iter().map(|y| f.eval(&[x.clone(), y.clone()])).collect()
+	    })
+	},
+	Err(_) => vec![vec![nan(); ys.len()]; xs.len()],
+    }
+}
+
+// Map f over the items, splitting them into even chunks run on
+// separate threads and joined back up in order
+fn chunked<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where T: Sync, R: Send, F: Fn(&T) -> R + Sync {
+    if threads <= 1 || items.len() < 2 {
+	return items.iter().map(f).collect();
+    }
+    let size = items.len().div_ceil(threads);
+    let f = &f;
+    thread::scope(|s| {
+	let handles: Vec<_> = items.chunks(size)
+	    .map(|chunk| s.spawn(move || chunk.iter().map(f).collect::<Vec<R>>()))
+	    .collect();
+	handles.into_iter()
+	    .flat_map(|h| h.join().unwrap())
+	    .collect()
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::expr::evaluator::*;
+
+    fn points(n: usize) -> Vec<Number> {
+	(0..n).map(|k| real(k as f64 / 4.0 - 3.0)).collect()
+    }
+
+    #[test]
+    fn test_matches_evaluate() {
+	let f = Expr::parse("sqrt(x) / (x - 1) + gamma(x)").unwrap();
+	let xs = points(41);
+	let expected: Vec<Number> = xs.iter().map(|x| evaluate(f.clone(), 'x', x.clone())).collect();
+	let serial = evaluate_many(&f, 'x', &xs);
+	for (a, b) in serial.iter().zip(expected.iter()) {
+	    assert!(a == b || (a.is_nan() && b.is_nan()), "{} vs {}", a, b);
+	}
+	for threads in [2, 3, 8, 100].iter() {
+	    let many = evaluate_many_threaded(&f, 'x', &xs, *threads);
+	    assert_eq!(format!("{:?}", many), format!("{:?}", serial));
+	}
+
+	// the strict version agrees with try_evaluate point by point
+	for (x, r) in xs.iter().zip(try_evaluate_many(&f, 'x', &xs)) {
+	    assert_eq!(r, try_evaluate(&f, &Env::new().bind('x', x.clone())));
+	}
+    }
+
+    #[test]
+    fn test_grid() {
+	let f = Expr::parse("x^2 - y / x").unwrap();
+	let xs = points(9);
+	let ys = points(5);
+	let grid = evaluate_grid(&f, 'x', &xs, 'y', &ys);
+	assert_eq!(grid.len(), 9);
+	for (i, row) in grid.iter().enumerate() {
+	    assert_eq!(row.len(), 5);
+	    for (j, v) in row.iter().enumerate() {
+		let env = Env::new().bind('x', xs[i].clone()).bind('y', ys[j].clone());
+		assert_eq!(*v, evaluate_env(&f, &env).unwrap());
+	    }
+	}
+	let threaded = evaluate_grid_threaded(&f, 'x', &xs, 'y', &ys, 4);
+	assert_eq!(format!("{:?}", threaded), format!("{:?}", grid));
+    }
+
+    #[test]
+    fn test_unbound() {
+	let f = Expr::parse("x + z").unwrap();
+	assert!(evaluate_many(&f, 'x', &points(3)).iter().all(|v| v.is_nan()));
+	assert!(try_evaluate_many(&f, 'x', &points(3)).iter()
+		.all(|r| *r == Err(MathError::UnboundVariable(vec![Symbol::from('z')]))));
+	assert!(evaluate_many(&f, 'x', &[]).is_empty());
+	let grid = evaluate_grid(&f, 'x', &points(2), 'y', &points(3));
+	assert!(grid.iter().flatten().all(|v| v.is_nan()));
+    }
+}
+
+// end src/expr/batch.rs
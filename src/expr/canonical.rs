@@ -0,0 +1,396 @@
+// src/expr/canonical.rs
+
+use std::cmp::Ordering;
+
+use crate::expr::expr::*;
+use crate::expr::expr::Expr::*;
+use crate::number::number::*;
+
+/// Rewrite an expression into a canonical form, where sums and
+/// products are flattened, like terms and like bases are collected,
+/// numeric coefficients are folded together and operands are sorted
+/// into a deterministic order. Two expressions which only differ by
+/// reordering or regrouping have the same canonical form.
+///
+/// Sums come out as left-nested `Add` nodes with the constant term
+/// last, and products as `Mul(coefficient, factors)` with the
+/// coefficient left out when it's one. Subtraction and negation
+/// become negative coefficients. Nothing is expanded, so `(x + 1)^2`
+/// stays as it is.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("x + 2 + x").unwrap();
+/// assert_eq!(canonical(f), add(varf('x', 2.0), con(2.0)));
+///
+/// let g = Expr::parse("x * y * x").unwrap();
+/// assert_eq!(canonical(g), mul(powf(var('x'), 2.0), var('y')));
+/// ```
+pub fn canonical(e: Expr) -> Expr {
+    match e {
+	Const(_) | Named(_) | Var(_) => e,
+	Neg(_) | Add(_, _) | Sub(_, _) => {
+	    let mut terms = Vec::new();
+	    collect_terms(&e, integer(1), &mut terms);
+	    build_sum(terms)
+	},
+	Mul(_, _) | Div(_, _) => {
+	    let mut coeff = integer(1);
+	    let mut factors = Vec::new();
+	    collect_factors(&e, false, &mut coeff, &mut factors);
+	    build_product(coeff, factors)
+	},
+	Pow(ref b, ref p) => canonical_pow(canonical(unpack(b)), canonical(unpack(p))),
+	e => e.map_children(|c| canonical(c.clone())),
+    }
+}
+
+/// Check if two expressions are equal up to reordering and
+/// regrouping of sums and products, by comparing their
+/// canonical forms.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let a = Expr::parse("x + y").unwrap();
+/// let b = Expr::parse("y + x").unwrap();
+/// assert_ne!(a, b);
+/// assert!(equivalent(&a, &b));
+/// ```
+pub fn equivalent(a: &Expr, b: &Expr) -> bool {
+    canonical(a.clone()) == canonical(b.clone())
+}
+
+/// A total, deterministic order on expressions used to sort the
+/// operands of canonical sums and products. Numbers sort before
+/// named constants like pi, which sort before variables, variables
+/// by name, and everything else by node type and then by its children.
+pub fn canonical_order(a: &Expr, b: &Expr) -> Ordering {
+    rank(a).cmp(&rank(b)).then_with(|| {
+	match (a, b) {
+	    (Const(x), Const(y)) => {
+		x.real().total_cmp(&y.real()).then(x.imag().total_cmp(&y.imag()))
+	    },
+	    (Named(x), Named(y)) => x.cmp(y),
+	    (Var(x), Var(y)) => x.cmp(y),
+	    _ => {
+		let (ca, cb) = (a.children(), b.children());
+		for (x, y) in ca.iter().zip(cb.iter()) {
+		    let o = canonical_order(x, y);
+		    if o != Ordering::Equal {
+			return o;
+		    }
+		}
+		ca.len().cmp(&cb.len())
+	    },
+	}
+    })
+}
+
+// Position of each node type in the canonical order
+fn rank(e: &Expr) -> u8 {
+    match e {
+	Const(_) => 0, Named(_) => 1, Var(_) => 2, Pow(_, _) => 3,
+	Mul(_, _) => 4, Add(_, _) => 5, Sub(_, _) => 6, Div(_, _) => 7,
+	Neg(_) => 8, Abs(_) => 9, Sin(_) => 10, Cos(_) => 11,
+	Sinh(_) => 12, Cosh(_) => 13, Asin(_) => 14, Acos(_) => 15,
+	Asinh(_) => 16, Acosh(_) => 17, Ln(_) => 18, Exp(_) => 19,
+	Factorial(_) => 20, Gamma(_) => 21, LnGamma(_) => 22, Digamma(_) => 23,
+	Polygamma(_, _) => 24, Beta(_, _) => 25,
+    }
+}
+
+// The total degree of a monomial in its variables, used so that
+// sums come out with the highest powers first
+fn degree(e: &Expr) -> f64 {
+    match e {
+	Var(_) => 1.0,
+	Mul(ref l, ref r) => degree(l) + degree(r),
+	Pow(ref b, ref p) => {
+	    match p.as_ref() {
+		Const(n) => degree(b) * n.real(),
+		_ => 0.0,
+	    }
+	},
+	_ => 0.0,
+    }
+}
+
+// The bases of a monomial with their numeric powers, in order
+fn powers(e: &Expr) -> Vec<(&Expr, f64)> {
+    match e {
+	Mul(ref l, ref r) => {
+	    let mut p = powers(l);
+	    p.extend(powers(r));
+	    p
+	},
+	Pow(ref b, ref n) => {
+	    match n.as_ref() {
+		Const(n) => vec![(b.as_ref(), n.real())],
+		_ => vec![(e, 1.0)],
+	    }
+	},
+	e => vec![(e, 1.0)],
+    }
+}
+
+// Lexicographic order of monomials, where whichever has the
+// higher power of the first base comes first, so x^2 sorts
+// before x y, which sorts before y^2
+fn lex_order(a: &Expr, b: &Expr) -> Ordering {
+    let (pa, pb) = (powers(a), powers(b));
+    let (mut i, mut j) = (0, 0);
+    while i < pa.len() && j < pb.len() {
+	match canonical_order(pa[i].0, pb[j].0) {
+	    Ordering::Less => return Ordering::Less,
+	    Ordering::Greater => return Ordering::Greater,
+	    Ordering::Equal => {
+		let o = pb[j].1.total_cmp(&pa[i].1);
+		if o != Ordering::Equal {
+		    return o;
+		}
+		i += 1;
+		j += 1;
+	    },
+	}
+    }
+    (pb.len() - j).cmp(&(pa.len() - i))
+}
+
+// Walk the Add/Sub/Neg structure of a tree, canonicalising each
+// term and recording it as a coefficient and a monomial, where
+// None is the constant term
+fn collect_terms(e: &Expr, scale: Number, terms: &mut Vec<(Number, Option<Expr>)>) {
+    match e {
+	Add(ref l, ref r) => {
+	    collect_terms(l, scale.clone(), terms);
+	    collect_terms(r, scale, terms);
+	},
+	Sub(ref l, ref r) => {
+	    collect_terms(l, scale.clone(), terms);
+	    collect_terms(r, -scale, terms);
+	},
+	Neg(ref i) => collect_terms(i, -scale, terms),
+	leaf => push_term(canonical(leaf.clone()), scale, terms),
+    }
+}
+
+fn push_term(c: Expr, scale: Number, terms: &mut Vec<(Number, Option<Expr>)>) {
+    let (k, m) = match c {
+	// a product or power could have canonicalised into a sum
+	Add(ref l, ref r) => {
+	    push_term(unpack(l), scale.clone(), terms);
+	    push_term(unpack(r), scale, terms);
+	    return;
+	},
+	Const(k) => (scale * k, None),
+	Mul(ref l, ref r) => {
+	    match unpack(l) {
+		Const(k) => (scale * k, Some(unpack(r))),
+		_ => (scale, Some(c.clone())),
+	    }
+	},
+	m => (scale, Some(m)),
+    };
+    match terms.iter_mut().find(|t| t.1 == m) {
+	Some(t) => t.0 = &t.0 + &k,
+	None => terms.push((k, m)),
+    }
+}
+
+fn build_sum(terms: Vec<(Number, Option<Expr>)>) -> Expr {
+    let mut constant = integer(0);
+    let mut monomials: Vec<(Number, Expr)> = Vec::new();
+    for (k, m) in terms {
+	match m {
+	    None => constant = constant + k,
+	    Some(_) if k.is_zero() => {},
+	    Some(m) => monomials.push((k, m)),
+	}
+    }
+    monomials.sort_by(|a, b| {
+	degree(&b.1).total_cmp(&degree(&a.1))
+	    .then_with(|| lex_order(&a.1, &b.1))
+	    .then_with(|| canonical_order(&a.1, &b.1))
+    });
+
+    let mut parts: Vec<Expr> = monomials.into_iter().map(|(k, m)| scaled(k, m)).collect();
+    if !constant.is_zero() || parts.is_empty() {
+	parts.push(Const(constant));
+    }
+    parts.into_iter().reduce(add).unwrap()
+}
+
+// k * m, keeping the coefficient at the front of the product
+fn scaled(k: Number, m: Expr) -> Expr {
+    if k.real_eq(1.0) {
+	m
+    } else {
+	mul(Const(k), m)
+    }
+}
+
+// Walk the Mul/Div structure of a tree, canonicalising each
+// factor and recording it as a base and exponent, with numbers
+// folded into the coefficient
+fn collect_factors(e: &Expr, invert: bool, coeff: &mut Number, factors: &mut Vec<(Expr, Expr)>) {
+    match e {
+	Mul(ref l, ref r) => {
+	    collect_factors(l, invert, coeff, factors);
+	    collect_factors(r, invert, coeff, factors);
+	},
+	Div(ref l, ref r) => {
+	    collect_factors(l, invert, coeff, factors);
+	    collect_factors(r, !invert, coeff, factors);
+	},
+	leaf => push_factor(canonical(leaf.clone()), invert, coeff, factors),
+    }
+}
+
+fn push_factor(c: Expr, invert: bool, coeff: &mut Number, factors: &mut Vec<(Expr, Expr)>) {
+    let (base, power) = match c {
+	Mul(ref l, ref r) => {
+	    push_factor(unpack(l), invert, coeff, factors);
+	    push_factor(unpack(r), invert, coeff, factors);
+	    return;
+	},
+	Const(k) => {
+	    *coeff = if invert { &*coeff / &k } else { &*coeff * &k };
+	    return;
+	},
+	Pow(ref b, ref p) => (unpack(b), unpack(p)),
+	b => (b, one()),
+    };
+    let power = if invert { negate(power) } else { power };
+    match factors.iter_mut().find(|f| f.0 == base) {
+	Some(f) => f.1 = add_powers(f.1.clone(), power),
+	None => factors.push((base, power)),
+    }
+}
+
+fn negate(p: Expr) -> Expr {
+    match p {
+	Const(n) => Const(-n),
+	p => canonical(neg(p)),
+    }
+}
+
+fn add_powers(a: Expr, b: Expr) -> Expr {
+    match (a, b) {
+	(Const(x), Const(y)) => Const(x + y),
+	(a, b) => canonical(add(a, b)),
+    }
+}
+
+fn build_product(mut coeff: Number, factors: Vec<(Expr, Expr)>) -> Expr {
+    let mut parts: Vec<Expr> = Vec::new();
+    for (base, power) in factors {
+	match canonical_pow(base, power) {
+	    Const(k) => coeff = coeff * k,
+	    f => parts.push(f),
+	}
+    }
+    if coeff.is_zero() {
+	return zero();
+    }
+    if parts.is_empty() {
+	return Const(coeff);
+    }
+    // sort by base so that x^2 y comes out ahead of y x^2
+    parts.sort_by(|a, b| canonical_order(base_of(a), base_of(b)).then_with(|| canonical_order(a, b)));
+    let product = parts.into_iter().reduce(mul).unwrap();
+    scaled(coeff, product)
+}
+
+fn base_of(e: &Expr) -> &Expr {
+    match e {
+	Pow(ref b, _) => b,
+	e => e,
+    }
+}
+
+fn canonical_pow(b: Expr, p: Expr) -> Expr {
+    match (b, p) {
+	(_, Const(y)) if y.is_zero() => one(),
+	(b, Const(y)) if y.real_eq(1.0) => b,
+	// powers of numbers fold when they stay exact, like 8^(1/3),
+	// or when there was nothing exact to lose, but sqrt(2) stays
+	(Const(x), Const(y)) => {
+	    let v = x.pow(y.clone());
+	    if v.is_exact() || (!x.is_exact() && !y.is_exact()) {
+		Const(v)
+	    } else {
+		pow(Const(x), Const(y))
+	    }
+	},
+	// (b^n)^m = b^(nm) only holds in general for whole m
+	(Pow(ref bb, ref n), Const(m)) if m.is_whole() => {
+	    match unpack(n) {
+		Const(n) => canonical_pow(unpack(bb), Const(&n * &m)),
+		n => pow(pow(unpack(bb), n), Const(m)),
+	    }
+	},
+	// (ab)^m = a^m b^m, also only for whole m
+	(Mul(ref l, ref r), Const(m)) if m.is_whole() => {
+	    canonical(mul(pow(unpack(l), Const(m.clone())), pow(unpack(r), Const(m))))
+	},
+	(b, p) => pow(b, p),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::expr::symbol::*;
+
+    fn parse(s: &str) -> Expr {
+	Expr::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_collect_terms() {
+	assert_eq!(canonical(parse("x + 2 + x")), parse("2x + 2"));
+	assert_eq!(canonical(parse("3x - 3x + 1")), con(1.0));
+	assert_eq!(canonical(parse("x - x")), zero());
+	assert_eq!(canonical(parse("-(y - x)")), add(var('x'), mul(con(-1.0), var('y'))));
+	assert_eq!(canonical(parse("1 + x^2 + 2x + x^2")), parse("2x^2 + 2x + 1"));
+    }
+
+    #[test]
+    fn test_collect_factors() {
+	assert_eq!(canonical(parse("x * y * x")), parse("x^2 * y"));
+	assert_eq!(canonical(parse("3 * (2x)")), parse("6x"));
+	assert_eq!(canonical(parse("x / x")), one());
+	assert_eq!(canonical(parse("x^a * x^b")), pow(var('x'), add(var('a'), var('b'))));
+	assert_eq!(canonical(parse("(x^2)^3")), powf(var('x'), 6.0));
+	assert_eq!(canonical(parse("(2x y)^2")), mul(con(4.0), parse("x^2 * y^2")));
+	// not safe in general for a fractional outer power
+	assert_eq!(canonical(parse("(x^2)^0.5")), sqrt(powf(var('x'), 2.0)));
+	// powers of numbers only fold when nothing exact is lost
+	assert_eq!(canonical(parse("sqrt(2) x")), mul(sqrt(Const(integer(2))), var('x')));
+	assert_eq!(canonical(parse("8^(1/3) x")), mul(Const(integer(2)), var('x')));
+	assert_eq!(canonical(powf(con(4.0), 0.5)), con(2.0));
+	assert!(matches!(canonical(powf(con(2.0), 0.5)), Const(_)));
+    }
+
+    #[test]
+    fn test_equivalent() {
+	assert!(equivalent(&parse("x + y"), &parse("y + x")));
+	assert!(equivalent(&parse("a b c"), &parse("c (b a)")));
+	assert!(equivalent(&parse("sin(x + 1) * 2"), &parse("2 sin(1 + x)")));
+	assert!(equivalent(&parse("x - y"), &parse("-y + x")));
+	assert!(!equivalent(&parse("x - y"), &parse("y - x")));
+	// no expansion is done
+	assert!(!equivalent(&parse("(x + 1)^2"), &parse("x^2 + 2x + 1")));
+    }
+
+    #[test]
+    fn test_order() {
+	let mut es = vec![var('z'), con(2.0), sin(var('x')), var("alpha"), con(-1.0)];
+	es.sort_by(canonical_order);
+	assert_eq!(es, vec![con(-1.0), con(2.0), var("alpha"), var('z'), sin(var('x'))]);
+	assert_eq!(canonical_order(&var(Symbol::new("q")), &var('q')), Ordering::Equal);
+    }
+}
+
+// end src/expr/canonical.rs
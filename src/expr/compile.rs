@@ -0,0 +1,392 @@
+// src/expr/compile.rs
+
+use crate::error::*;
+use crate::expr::expr::*;
+use crate::expr::expr::Expr::*;
+use crate::expr::symbol::*;
+use crate::expr::evaluator::*;
+use crate::number::number::*;
+
+
+// Evaluations needing no more stack than this run without allocating
+const INLINE_STACK: usize = 32;
+
+// A single instruction of a compiled function. Every instruction
+// pops its operands off the value stack and pushes its result.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Constant(usize),
+    Arg(usize),
+    Neg,
+    Abs,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    // a power with a whole constant exponent, kept as a constant
+    // too for evaluating on Number
+    Powi(i32, usize),
+    Sin,
+    Cos,
+    Sinh,
+    Cosh,
+    Asin,
+    Acos,
+    Asinh,
+    Acosh,
+    Ln,
+    Exp,
+    Factorial,
+    Gamma,
+    LnGamma,
+    Digamma,
+    Polygamma,
+    Beta,
+}
+
+/// An `Expr` flattened into stack bytecode for fast repeated
+/// evaluation, made with `Expr::compile`. Arguments are given by
+/// position in the order of the symbols passed to `compile`.
+///
+/// Any part of the tree without a variable is folded into a
+/// constant ahead of time. Evaluating keeps its stack on the call
+/// stack for all but very deeply nested expressions, so the only
+/// allocations are those made by exact `Number` arithmetic.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("x^2 + sin(y) * (1 + 2)").unwrap();
+/// let c = f.compile(&['x', 'y']).unwrap();
+/// assert_eq!(c.eval_f64(&[3.0, 0.0]), 9.0);
+/// assert_eq!(c.eval(&[integer(3), integer(0)]), real(9.0));
+/// assert_eq!(c.len(), 7);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFn {
+    vars: Vec<Symbol>,
+    ops: Vec<Op>,
+    constants: Vec<Number>,
+    floats: Vec<f64>,
+    stack_size: usize,
+}
+
+impl Expr {
+    /// Compile an expression into a `CompiledFn` taking the given
+    /// variables as arguments, in order. Any other variable in the
+    /// tree is reported as a `MathError::UnboundVariable`.
+    pub fn compile<S: Into<Symbol> + Copy>(&self, vars: &[S]) -> Result<CompiledFn, MathError> {
+	let vars: Vec<Symbol> = vars.iter().map(|&s| s.into()).collect();
+	let mut env = Env::new();
+	for &v in vars.iter() {
+	    env.set(v, nan());
+	}
+	let missing = env.unbound(self);
+	if !missing.is_empty() {
+	    return Err(MathError::UnboundVariable(missing));
+	}
+
+	let mut c = CompiledFn { vars, ops: Vec::new(), constants: Vec::new(), floats: Vec::new(), stack_size: 0 };
+	c.emit(self, 0);
+	Ok(c)
+    }
+}
+
+impl CompiledFn {
+    /// The variables taken as arguments, in order.
+    pub fn vars(&self) -> &[Symbol] {
+	&self.vars
+    }
+
+    /// The number of instructions.
+    pub fn len(&self) -> usize {
+	self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.ops.is_empty()
+    }
+
+    // The slot of a constant, reusing the slot of an equal one
+    fn constant(&mut self, n: Number) -> usize {
+	match self.constants.iter().position(|c| *c == n) {
+	    Some(i) => i,
+	    None => {
+		self.floats.push(if n.imag() == 0.0 { n.real() } else { f64::NAN });
+		self.constants.push(n);
+		self.constants.len() - 1
+	    },
+	}
+    }
+
+    // Emit the instructions for e, where `depth` values are already
+    // on the stack, keeping track of the deepest the stack gets
+    fn emit(&mut self, e: &Expr, depth: usize) {
+	self.stack_size = self.stack_size.max(depth + 1);
+
+	// fold anything without a variable, unless it fails so that
+	// the error is still reported when the function is evaluated
+	if !e.is_const() && self.vars.iter().all(|&v| !e.has_var(v)) {
+	    if let Ok(n) = try_evaluate(e, &Env::new()) {
+		let slot = self.constant(n);
+		self.ops.push(Op::Constant(slot));
+		return;
+	    }
+	}
+
+	let op = match e {
+	    Const(n) => Op::Constant(self.constant(n.clone())),
+	    Named(c) => Op::Constant(self.constant(c.value())),
+	    Var(s) => Op::Arg(self.vars.iter().position(|v| v == s).unwrap()),
+	    Pow(ref b, ref p) if whole_power(p).is_some() => {
+		self.emit(b, depth);
+		let slot = match p.as_ref() {
+		    Const(n) => self.constant(n.clone()),
+		    _ => unreachable!(),
+		};
+		Op::Powi(whole_power(p).unwrap(), slot)
+	    },
+	    _ => {
+		for (i, c) in e.children().into_iter().enumerate() {
+		    self.emit(c, depth + i);
+		}
+		match e {
+		    Neg(_) => Op::Neg,
+		    Abs(_) => Op::Abs,
+		    Add(_, _) => Op::Add,
+		    Sub(_, _) => Op::Sub,
+		    Mul(_, _) => Op::Mul,
+		    Div(_, _) => Op::Div,
+		    Pow(_, _) => Op::Pow,
+		    Sin(_) => Op::Sin,
+		    Cos(_) => Op::Cos,
+		    Sinh(_) => Op::Sinh,
+		    Cosh(_) => Op::Cosh,
+		    Asin(_) => Op::Asin,
+		    Acos(_) => Op::Acos,
+		    Asinh(_) => Op::Asinh,
+		    Acosh(_) => Op::Acosh,
+		    Ln(_) => Op::Ln,
+		    Exp(_) => Op::Exp,
+		    Factorial(_) => Op::Factorial,
+		    Gamma(_) => Op::Gamma,
+		    LnGamma(_) => Op::LnGamma,
+		    Digamma(_) => Op::Digamma,
+		    Polygamma(_, _) => Op::Polygamma,
+		    _ => Op::Beta,
+		}
+	    },
+	};
+	self.ops.push(op);
+    }
+
+    fn check_args(&self, given: usize) {
+	assert_eq!(given, self.vars.len(), "compiled function takes {} arguments", self.vars.len());
+    }
+
+    /// Evaluate on plain `f64` arguments. This only works over the
+    /// reals, so anything which would be complex, like `ln(-1)`,
+    /// comes out as NaN. Panics when given the wrong number of
+    /// arguments.
+    pub fn eval_f64(&self, args: &[f64]) -> f64 {
+	self.check_args(args.len());
+	if self.stack_size <= INLINE_STACK {
+	    let mut stack = [0.0; INLINE_STACK];
+	    self.run_f64(args, &mut stack)
+	} else {
+	    let mut stack = vec![0.0; self.stack_size];
+	    self.run_f64(args, &mut stack)
+	}
+    }
+
+    fn run_f64(&self, args: &[f64], stack: &mut [f64]) -> f64 {
+	let mut top = 0;
+	for op in self.ops.iter() {
+	    // unary operations replace the top, binary ones combine
+	    // the top two values into one
+	    let x = if top > 0 { stack[top - 1] } else { 0.0 };
+	    let y = if top > 1 { stack[top - 2] } else { 0.0 };
+	    let (pops, value) = match *op {
+		Op::Constant(i) => (0, self.floats[i]),
+		Op::Arg(i) => (0, args[i]),
+		Op::Neg => (1, -x),
+		Op::Abs => (1, x.abs()),
+		Op::Add => (2, y + x),
+		Op::Sub => (2, y - x),
+		Op::Mul => (2, y * x),
+		Op::Div => (2, y / x),
+		Op::Pow => (2, y.powf(x)),
+		Op::Powi(n, _) => (1, x.powi(n)),
+		Op::Sin => (1, x.sin()),
+		Op::Cos => (1, x.cos()),
+		Op::Sinh => (1, x.sinh()),
+		Op::Cosh => (1, x.cosh()),
+		Op::Asin => (1, x.asin()),
+		Op::Acos => (1, x.acos()),
+		Op::Asinh => (1, x.asinh()),
+		Op::Acosh => (1, x.acosh()),
+		Op::Ln => (1, x.ln()),
+		Op::Exp => (1, x.exp()),
+		Op::Factorial => (1, real_only(real(x).factorial())),
+		Op::Gamma => (1, real_only(real(x).gamma())),
+		Op::LnGamma => (1, real_only(real(x).ln_gamma())),
+		Op::Digamma => (1, real_only(real(x).digamma())),
+		Op::Polygamma => {
+		    let order = if y >= 0.0 && y.fract() == 0.0 {
+			real_only(real(x).polygamma(y as u32))
+		    } else {
+			f64::NAN
+		    };
+		    (2, order)
+		},
+		Op::Beta => (2, real_only(real(y).beta(real(x)))),
+	    };
+	    top -= pops;
+	    stack[top] = value;
+	    top += 1;
+	}
+	stack[0]
+    }
+
+    /// Evaluate on `Number` arguments with the same rules as
+    /// `evaluate_env`, so numeric failures give NaN. Panics when
+    /// given the wrong number of arguments.
+    pub fn eval(&self, args: &[Number]) -> Number {
+	self.try_eval(args).unwrap_or(nan())
+    }
+
+    /// Evaluate on `Number` arguments, reporting domain errors,
+    /// division by zero and overflow like `try_evaluate`.
+    pub fn try_eval(&self, args: &[Number]) -> Result<Number, MathError> {
+	self.check_args(args.len());
+	if self.stack_size <= INLINE_STACK {
+	    let mut stack: [Number; INLINE_STACK] = std::array::from_fn(|_| nan());
+	    self.run(args, &mut stack)
+	} else {
+	    let mut stack = vec![nan(); self.stack_size];
+	    self.run(args, &mut stack)
+	}
+    }
+
+    fn run(&self, args: &[Number], stack: &mut [Number]) -> Result<Number, MathError> {
+	let mut top = 0;
+	for op in self.ops.iter() {
+	    let value = match *op {
+		Op::Constant(i) => self.constants[i].clone(),
+		Op::Arg(i) => args[i].clone(),
+		_ => {
+		    top -= 1;
+		    let x = std::mem::replace(&mut stack[top], nan());
+		    match *op {
+			Op::Neg => -x,
+			Op::Abs => x.abs(),
+			Op::Powi(_, i) => x.try_pow(self.constants[i].clone())?,
+			Op::Sin => x.try_sin()?,
+			Op::Cos => x.try_cos()?,
+			Op::Sinh => x.try_sinh()?,
+			Op::Cosh => x.try_cosh()?,
+			Op::Asin => x.try_asin()?,
+			Op::Acos => x.try_acos()?,
+			Op::Asinh => x.try_asinh()?,
+			Op::Acosh => x.try_acosh()?,
+			Op::Ln => x.try_ln()?,
+			Op::Exp => x.try_exp()?,
+			Op::Factorial => x.try_factorial()?,
+			Op::Gamma => x.try_gamma()?,
+			Op::LnGamma => x.try_ln_gamma()?,
+			Op::Digamma => x.try_digamma()?,
+			_ => {
+			    top -= 1;
+			    let y = std::mem::replace(&mut stack[top], nan());
+			    match *op {
+				Op::Add => y.try_add(x)?,
+				Op::Sub => y.try_sub(x)?,
+				Op::Mul => y.try_mul(x)?,
+				Op::Div => y.try_div(x)?,
+				Op::Pow => y.try_pow(x)?,
+				Op::Beta => y.try_beta(x)?,
+				_ => {
+				    if !y.is_whole() || y.imag() != 0.0 || y.real() < 0.0 {
+					return Err(MathError::DomainError(format!("polygamma of order {}", y)));
+				    }
+				    x.try_polygamma(y.real() as u32)?
+				},
+			    }
+			},
+		    }
+		},
+	    };
+	    stack[top] = value;
+	    top += 1;
+	}
+	Ok(std::mem::replace(&mut stack[0], nan()))
+    }
+}
+
+// A constant whole exponent small enough for powi
+fn whole_power(p: &Expr) -> Option<i32> {
+    match p {
+	Const(n) if n.is_whole() && n.imag() == 0.0 && n.real().abs() <= 64.0 => Some(n.real() as i32),
+	_ => None,
+    }
+}
+
+// The f64 value of a result, or NaN when it came out complex
+fn real_only(n: Number) -> f64 {
+    if n.imag() == 0.0 { n.real() } else { f64::NAN }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matches_evaluator() {
+	let exprs = [
+	    "x^2 - 3x + 2",
+	    "sin(x) * cos(y) / (1 + y^2)",
+	    "e^(-x^2) + ln(y + 10)",
+	    "sqrt(x + 5) - x^-2",
+	    "gamma(y + 4) + beta(2, y + 3)",
+	    "sinh(x) - acosh(y + 2) + |x - y|",
+	    "polygamma(1, y + 3) + (y + 3)!",
+	];
+	let points = [(-2.0, 0.5), (0.3, 1.0), (1.7, 2.0), (4.0, 3.0)];
+	for s in exprs.iter() {
+	    let f = Expr::parse(s).unwrap();
+	    let c = f.compile(&['x', 'y']).unwrap();
+	    for &(x, y) in points.iter() {
+		let env = Env::new().bind('x', real(x)).bind('y', real(y));
+		let expected = evaluate_env(&f, &env).unwrap();
+		let fast = c.eval_f64(&[x, y]);
+		assert!((fast - expected.real()).abs() <= 1e-12 * (1.0 + expected.real().abs()),
+			"{} at ({}, {}): {} vs {}", s, x, y, fast, expected);
+		assert_eq!(c.eval(&[real(x), real(y)]), expected);
+	    }
+	}
+    }
+
+    #[test]
+    fn test_folding_and_errors() {
+	// the constant part is folded down to a single push
+	let f = Expr::parse("x * (2 + 3) * sin(pi / 2)").unwrap();
+	assert_eq!(f.compile(&['x']).unwrap().len(), 5);
+
+	let g = Expr::parse("x + y").unwrap();
+	assert!(matches!(g.compile(&['x']), Err(MathError::UnboundVariable(_))));
+
+	// failures in constant parts are left for evaluation to report
+	let h = Expr::parse("x + ln(0)").unwrap().compile(&['x']).unwrap();
+	assert!(matches!(h.try_eval(&[real(1.0)]), Err(MathError::DomainError(_))));
+	assert!(h.eval(&[real(1.0)]).is_nan());
+	assert!(h.eval_f64(&[1.0]).is_infinite());
+
+	// the f64 path stays on the reals
+	let l = Expr::parse("ln(x)").unwrap().compile(&['x']).unwrap();
+	assert!(l.eval_f64(&[-1.0]).is_nan());
+	assert!(l.eval(&[real(-1.0)]).imag() != 0.0);
+    }
+}
+
+// end src/expr/compile.rs
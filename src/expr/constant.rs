@@ -0,0 +1,54 @@
+// src/expr/constant.rs
+
+use std::fmt;
+use crate::number::number::*;
+
+
+/// Mathematical constants kept as named nodes in an `Expr` tree
+/// rather than as float approximations, so that identities like
+/// `sin(pi) = 0` or `ln(e) = 1` can be recognised exactly. The
+/// numeric value is only used once the tree is evaluated.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// assert_eq!(Constant::Pi.value(), real(std::f64::consts::PI));
+/// assert_eq!(pi().to_string(), "pi");
+/// assert_eq!(simplify(sin(pi())), zero());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Constant {
+    Pi,
+    E,
+    ImaginaryUnit,
+    Infinity,
+}
+
+impl Constant {
+    /// The value of the constant at full `f64` precision.
+    pub fn value(&self) -> Number {
+	match self {
+	    Constant::Pi => real(std::f64::consts::PI),
+	    Constant::E => real(std::f64::consts::E),
+	    Constant::ImaginaryUnit => i(),
+	    Constant::Infinity => real(f64::INFINITY),
+	}
+    }
+
+    /// The name the constant prints and parses as.
+    pub fn name(&self) -> &'static str {
+	match self {
+	    Constant::Pi => "pi",
+	    Constant::E => "e",
+	    Constant::ImaginaryUnit => "i",
+	    Constant::Infinity => "inf",
+	}
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f, "{}", self.name())
+    }
+}
+
+// end src/expr/constant.rs
@@ -0,0 +1,215 @@
+// src/expr/cse.rs
+
+use std::fmt;
+use std::collections::{HashMap, HashSet};
+
+use crate::error::*;
+use crate::expr::expr::*;
+use crate::expr::expr::Expr::*;
+use crate::expr::symbol::*;
+use crate::expr::evaluator::*;
+use crate::expr::dag::*;
+use crate::number::number::*;
+
+
+/// A list of expressions with their repeated subexpressions pulled
+/// out into temporaries, made with `cse`. Each temporary is defined
+/// in terms of the variables and the temporaries before it.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("sin(x^2) / cos(x^2) + cos(x^2)").unwrap();
+/// let c = cse(&[f]);
+/// assert_eq!(c.to_string(), "let t1 = x^2;\nlet t2 = cos(t1);\nsin(t1)/t2 + t2");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cse {
+    bindings: Vec<(Symbol, Expr)>,
+    results: Vec<Expr>,
+}
+
+/// Common subexpression elimination over a list of expressions,
+/// like the entries of a Jacobian. Every subexpression which shows
+/// up more than once, in one expression or across several, gets a
+/// temporary named `t1`, `t2` and so on, skipping any name already
+/// used as a variable.
+pub fn cse(exprs: &[Expr]) -> Cse {
+    let mut dag = Dag::new();
+    let roots: Vec<NodeId> = exprs.iter().map(|e| dag.insert(e)).collect();
+
+    // count the uses of each node, once for each parent and
+    // once for each time it is one of the expressions
+    let mut uses: HashMap<NodeId, usize> = HashMap::new();
+    let mut seen = HashSet::new();
+    for &root in roots.iter() {
+	*uses.entry(root).or_insert(0) += 1;
+	for n in dag.reachable(root) {
+	    if seen.insert(n) {
+		for c in dag.children(n) {
+		    *uses.entry(c).or_insert(0) += 1;
+		}
+	    }
+	}
+    }
+
+    // sorting by id puts every temporary after the ones it uses
+    let mut shared: Vec<NodeId> = uses.iter()
+	.filter(|&(&n, &k)| k > 1 && !dag.children(n).is_empty())
+	.map(|(&n, _)| n)
+	.collect();
+    shared.sort();
+
+    let mut taken = Vec::new();
+    for e in exprs.iter() {
+	taken.extend(e.free_symbols());
+    }
+    let mut names: HashMap<NodeId, Symbol> = HashMap::new();
+    let mut k = 0;
+    for &n in shared.iter() {
+	let name = loop {
+	    k += 1;
+	    let s = Symbol::new(&format!("t{}", k));
+	    if !taken.contains(&s) {
+		break s;
+	    }
+	};
+	names.insert(n, name);
+    }
+
+    let replace = |i: NodeId| names.get(&i).map(|&s| Var(s));
+    Cse {
+	bindings: shared.iter().map(|&n| (names[&n], dag.rebuild(n, &replace))).collect(),
+	results: roots.iter().map(|&r| replace(r).unwrap_or_else(|| dag.rebuild(r, &replace))).collect(),
+    }
+}
+
+impl Cse {
+    /// The temporaries in the order they are defined.
+    pub fn bindings(&self) -> &[(Symbol, Expr)] {
+	&self.bindings
+    }
+
+    /// The expressions, written in terms of the temporaries.
+    pub fn results(&self) -> &[Expr] {
+	&self.results
+    }
+
+    /// Substitute every temporary back in, giving the original
+    /// expressions.
+    pub fn expand(&self) -> Vec<Expr> {
+	let mut values: HashMap<Symbol, Expr> = HashMap::new();
+	for (s, e) in self.bindings.iter() {
+	    let full = replace_vars(e, &values);
+	    values.insert(*s, full);
+	}
+	self.results.iter().map(|e| replace_vars(e, &values)).collect()
+    }
+
+    /// Evaluate every expression at the point given by an `Env`,
+    /// working out each temporary only once. Like `evaluate_vector`
+    /// this fails on the first thing which can't be evaluated.
+    ///
+    /// ```
+    /// use mathy::calc::prelude::*;
+    /// let f = Expr::parse("x / (x^2 + y^2)").unwrap();
+    /// let j = gradient(&f, &['x', 'y']).unwrap();
+    /// let env = Env::new().bind('x', real(1.0)).bind('y', real(2.0));
+    /// assert_eq!(cse(&j).evaluate(&env), evaluate_vector(&j, &env));
+    /// ```
+    pub fn evaluate(&self, env: &Env) -> Result<Vec<Number>, MathError> {
+	let mut env = env.clone();
+	for (s, e) in self.bindings.iter() {
+	    let v = try_evaluate(e, &env)?;
+	    env.set(*s, v);
+	}
+	self.results.iter().map(|e| try_evaluate(e, &env)).collect()
+    }
+}
+
+// Swap variables for the expressions they stand for
+fn replace_vars(e: &Expr, values: &HashMap<Symbol, Expr>) -> Expr {
+    match e {
+	Var(s) if values.contains_key(s) => values[s].clone(),
+	e => e.map_children(|c| replace_vars(c, values)),
+    }
+}
+
+/// Print each temporary as `let t1 = ...;` on its own line,
+/// followed by each expression on a line of its own.
+impl fmt::Display for Cse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	let mut lines: Vec<String> = self.bindings.iter()
+	    .map(|(s, e)| format!("let {} = {};", s, e))
+	    .collect();
+	lines.extend(self.results.iter().map(|e| e.to_string()));
+	write!(f, "{}", lines.join("\n"))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::calc::deriver::*;
+    use crate::calc::partial::*;
+
+    #[test]
+    fn test_quotient_rule() {
+	let f = Expr::parse("sin(x) / (x^2 + 1)").unwrap();
+	let d = derive(f, 'x');
+	let c = cse(std::slice::from_ref(&d));
+	// the denominator shows up three times in the quotient rule
+	assert!(c.bindings().iter().any(|(_, e)| e.to_string() == "x^2 + 1"));
+	assert_eq!(c.expand(), vec![d.clone()]);
+	for x in [-1.5, 0.3, 2.0].iter() {
+	    let env = Env::new().bind('x', real(*x));
+	    assert_eq!(c.evaluate(&env).unwrap(), vec![try_evaluate(&d, &env).unwrap()]);
+	}
+    }
+
+    #[test]
+    fn test_jacobian() {
+	let fs = [
+	    Expr::parse("x^2 y / (x^2 + y^2)").unwrap(),
+	    Expr::parse("exp(x y) * sin(x y) / (1 + x y)").unwrap(),
+	];
+	let j = jacobian(&fs, &['x', 'y']).unwrap();
+	let entries: Vec<Expr> = j.concat();
+	let c = cse(&entries);
+	assert_eq!(c.results().len(), 4);
+	assert_eq!(c.expand(), entries);
+	let env = Env::new().bind('x', real(0.5)).bind('y', real(1.5));
+	assert_eq!(c.evaluate(&env), evaluate_vector(&entries, &env));
+
+	assert!(c.bindings().iter().any(|(_, e)| e.to_string() == "x*y"));
+
+	// second derivatives repeat far more, so the shared form
+	// prints in less than half the space
+	let h = hessian(&fs[1], &['x', 'y']).unwrap().concat();
+	let full: usize = h.iter().map(|e| e.to_string().len()).sum();
+	assert!(2 * cse(&h).to_string().len() < full);
+    }
+
+    #[test]
+    fn test_names_and_edge_cases() {
+	// t1 is already a variable so the first temporary is t2
+	let f = Expr::parse("(t1 + x)^2 * (t1 + x)").unwrap();
+	let c = cse(&[f.clone(), var('y')]);
+	assert_eq!(c.to_string(), "let t2 = t1 + x;\nt2^2*t2\ny");
+
+	// an expression which is also part of another becomes a temporary
+	let c = cse(&[sin(var('x')), add(sin(var('x')), con(1.0))]);
+	assert_eq!(c.to_string(), "let t1 = sin(x);\nt1\nt1 + 1");
+
+	// nothing repeated, nothing pulled out
+	let c = cse(&[Expr::parse("x + y").unwrap()]);
+	assert!(c.bindings().is_empty());
+	assert!(cse(&[]).results().is_empty());
+
+	let c = cse(&[Expr::parse("ln(x) * ln(x)").unwrap()]);
+	let env = Env::new().bind('x', real(0.0));
+	assert!(c.evaluate(&env).is_err());
+    }
+}
+
+// end src/expr/cse.rs
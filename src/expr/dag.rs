@@ -0,0 +1,726 @@
+// src/expr/dag.rs
+
+use std::collections::HashMap;
+
+use crate::error::*;
+use crate::expr::expr::*;
+use crate::expr::expr::Expr::*;
+use crate::expr::symbol::*;
+use crate::expr::constant::*;
+use crate::expr::evaluator::*;
+use crate::number::number::*;
+use crate::number::bigint::*;
+use crate::number::rational::*;
+
+
+/// A handle to a node stored in a `Dag`. Handles are only
+/// meaningful for the `Dag` which gave them out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+// A single operation in the arena, pointing at its operands. Since
+// operands are always stored first, every child has a smaller id
+// than its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    Const(usize),
+    Named(Constant),
+    Var(Symbol),
+    Neg(NodeId),
+    Abs(NodeId),
+    Sin(NodeId),
+    Cos(NodeId),
+    Sinh(NodeId),
+    Cosh(NodeId),
+    Asin(NodeId),
+    Acos(NodeId),
+    Asinh(NodeId),
+    Acosh(NodeId),
+    Ln(NodeId),
+    Exp(NodeId),
+    Factorial(NodeId),
+    Gamma(NodeId),
+    LnGamma(NodeId),
+    Digamma(NodeId),
+    Add(NodeId, NodeId),
+    Sub(NodeId, NodeId),
+    Mul(NodeId, NodeId),
+    Div(NodeId, NodeId),
+    Pow(NodeId, NodeId),
+    Polygamma(NodeId, NodeId),
+    Beta(NodeId, NodeId),
+}
+
+impl Node {
+    fn children(&self) -> Vec<NodeId> {
+	match *self {
+	    Node::Const(_) | Node::Named(_) | Node::Var(_) => vec![],
+	    Node::Neg(i) | Node::Abs(i) | Node::Sin(i) | Node::Cos(i) | Node::Sinh(i) |
+	    Node::Cosh(i) | Node::Asin(i) | Node::Acos(i) | Node::Asinh(i) | Node::Acosh(i) |
+	    Node::Ln(i) | Node::Exp(i) | Node::Factorial(i) | Node::Gamma(i) |
+	    Node::LnGamma(i) | Node::Digamma(i) => vec![i],
+	    Node::Add(l, r) | Node::Sub(l, r) | Node::Mul(l, r) | Node::Div(l, r) |
+	    Node::Pow(l, r) | Node::Polygamma(l, r) | Node::Beta(l, r) => vec![l, r],
+	}
+    }
+}
+
+// A hashable stand in for a Number, with floats compared by their bits
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum NumberKey {
+    NaN,
+    Integer(BigInt),
+    Rational(BigRational),
+    Real(u64),
+    Complex(u64, u64),
+}
+
+fn number_key(n: &Number) -> NumberKey {
+    match n {
+	Number::NaN => NumberKey::NaN,
+	Number::Integer(k) => NumberKey::Integer(k.clone()),
+	Number::Rational(q) => NumberKey::Rational(q.clone()),
+	Number::Real(x) => NumberKey::Real(x.to_bits()),
+	Number::Complex(x, z) => NumberKey::Complex(x.to_bits(), z.to_bits()),
+    }
+}
+
+/// An arena of expression nodes where every distinct subtree is
+/// stored exactly once, so equal subtrees are shared instead of
+/// copied. Convert an `Expr` in with `insert` and back out with
+/// `to_expr`.
+///
+/// Derivatives taken in a `Dag` reuse the nodes they are built from
+/// and are remembered, so repeated differentiation grows the arena
+/// slowly where the same derivatives as `Expr` trees would grow
+/// exponentially. Evaluating a node visits each shared node once.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// // the Taylor coefficients of e^sin(x) about 0
+/// let mut dag = Dag::new();
+/// let mut f = dag.insert(&Expr::parse("e^sin(x)").unwrap());
+/// let env = Env::new().bind('x', real(0.0));
+/// let mut coefficients = Vec::new();
+/// let mut factorial = 1.0;
+/// for k in 0..=6 {
+///     if k > 0 {
+///         f = dag.derive(f, 'x');
+///         factorial *= k as f64;
+///     }
+///     coefficients.push(dag.evaluate(f, &env).unwrap().real() / factorial);
+/// }
+/// assert_eq!(coefficients[2], 0.5);
+/// assert_eq!(coefficients[4], -0.125);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Dag {
+    nodes: Vec<Node>,
+    numbers: Vec<Number>,
+    index: HashMap<Node, NodeId>,
+    number_index: HashMap<NumberKey, usize>,
+    derivatives: HashMap<(NodeId, Symbol), NodeId>,
+}
+
+impl Dag {
+    /// Create an empty arena.
+    pub fn new() -> Dag {
+	Dag::default()
+    }
+
+    /// The number of distinct nodes stored.
+    pub fn len(&self) -> usize {
+	self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.nodes.is_empty()
+    }
+
+    /// Add an `Expr` tree, sharing any subtree which is already
+    /// stored, and give back the handle of its root.
+    pub fn insert(&mut self, e: &Expr) -> NodeId {
+	let kids: Vec<NodeId> = e.children().into_iter().map(|c| self.insert(c)).collect();
+	let node = match e {
+	    Const(n) => return self.number(n.clone()),
+	    Named(c) => Node::Named(*c),
+	    Var(s) => Node::Var(*s),
+	    Neg(_) => Node::Neg(kids[0]),
+	    Abs(_) => Node::Abs(kids[0]),
+	    Sin(_) => Node::Sin(kids[0]),
+	    Cos(_) => Node::Cos(kids[0]),
+	    Sinh(_) => Node::Sinh(kids[0]),
+	    Cosh(_) => Node::Cosh(kids[0]),
+	    Asin(_) => Node::Asin(kids[0]),
+	    Acos(_) => Node::Acos(kids[0]),
+	    Asinh(_) => Node::Asinh(kids[0]),
+	    Acosh(_) => Node::Acosh(kids[0]),
+	    Ln(_) => Node::Ln(kids[0]),
+	    Exp(_) => Node::Exp(kids[0]),
+	    Factorial(_) => Node::Factorial(kids[0]),
+	    Gamma(_) => Node::Gamma(kids[0]),
+	    LnGamma(_) => Node::LnGamma(kids[0]),
+	    Digamma(_) => Node::Digamma(kids[0]),
+	    Add(_, _) => Node::Add(kids[0], kids[1]),
+	    Sub(_, _) => Node::Sub(kids[0], kids[1]),
+	    Mul(_, _) => Node::Mul(kids[0], kids[1]),
+	    Div(_, _) => Node::Div(kids[0], kids[1]),
+	    Pow(_, _) => Node::Pow(kids[0], kids[1]),
+	    Polygamma(_, _) => Node::Polygamma(kids[0], kids[1]),
+	    Beta(_, _) => Node::Beta(kids[0], kids[1]),
+	};
+	self.intern(node)
+    }
+
+    /// Rebuild the `Expr` tree for a node. Shared nodes are copied
+    /// out in full, so this can be far larger than the `Dag`.
+    pub fn to_expr(&self, id: NodeId) -> Expr {
+	self.rebuild(id, &|_| None)
+    }
+
+    // Rebuild the tree for a node, where any operand for which
+    // `replace` gives an Expr is swapped for it rather than rebuilt
+    pub(crate) fn rebuild<F: Fn(NodeId) -> Option<Expr>>(&self, id: NodeId, replace: &F) -> Expr {
+	let e = |i: NodeId| pack(replace(i).unwrap_or_else(|| self.rebuild(i, replace)));
+	match self.nodes[id.0] {
+	    Node::Const(k) => Const(self.numbers[k].clone()),
+	    Node::Named(c) => Named(c),
+	    Node::Var(s) => Var(s),
+	    Node::Neg(i) => Neg(e(i)),
+	    Node::Abs(i) => Abs(e(i)),
+	    Node::Sin(i) => Sin(e(i)),
+	    Node::Cos(i) => Cos(e(i)),
+	    Node::Sinh(i) => Sinh(e(i)),
+	    Node::Cosh(i) => Cosh(e(i)),
+	    Node::Asin(i) => Asin(e(i)),
+	    Node::Acos(i) => Acos(e(i)),
+	    Node::Asinh(i) => Asinh(e(i)),
+	    Node::Acosh(i) => Acosh(e(i)),
+	    Node::Ln(i) => Ln(e(i)),
+	    Node::Exp(i) => Exp(e(i)),
+	    Node::Factorial(i) => Factorial(e(i)),
+	    Node::Gamma(i) => Gamma(e(i)),
+	    Node::LnGamma(i) => LnGamma(e(i)),
+	    Node::Digamma(i) => Digamma(e(i)),
+	    Node::Add(l, r) => Add(e(l), e(r)),
+	    Node::Sub(l, r) => Sub(e(l), e(r)),
+	    Node::Mul(l, r) => Mul(e(l), e(r)),
+	    Node::Div(l, r) => Div(e(l), e(r)),
+	    Node::Pow(l, r) => Pow(e(l), e(r)),
+	    Node::Polygamma(l, r) => Polygamma(e(l), e(r)),
+	    Node::Beta(l, r) => Beta(e(l), e(r)),
+	}
+    }
+
+    /// The number of distinct nodes making up the expression
+    /// at a node, counting shared subtrees once.
+    pub fn count(&self, id: NodeId) -> usize {
+	self.reachable(id).len()
+    }
+
+    // Every node below and including id, in increasing order so
+    // that children always come before their parents
+    pub(crate) fn reachable(&self, id: NodeId) -> Vec<NodeId> {
+	let mut seen = vec![false; id.0 + 1];
+	let mut todo = vec![id];
+	while let Some(i) = todo.pop() {
+	    if !seen[i.0] {
+		seen[i.0] = true;
+		todo.extend(self.nodes[i.0].children());
+	    }
+	}
+	(0..=id.0).filter(|&i| seen[i]).map(NodeId).collect()
+    }
+
+    // The operands of a node
+    pub(crate) fn children(&self, id: NodeId) -> Vec<NodeId> {
+	self.nodes[id.0].children()
+    }
+
+    fn intern(&mut self, node: Node) -> NodeId {
+	if let Some(&id) = self.index.get(&node) {
+	    return id;
+	}
+	let id = NodeId(self.nodes.len());
+	self.nodes.push(node);
+	self.index.insert(node, id);
+	id
+    }
+
+    fn number(&mut self, n: Number) -> NodeId {
+	let key = number_key(&n);
+	let slot = match self.number_index.get(&key) {
+	    Some(&k) => k,
+	    None => {
+		self.numbers.push(n);
+		self.number_index.insert(key, self.numbers.len() - 1);
+		self.numbers.len() - 1
+	    },
+	};
+	self.intern(Node::Const(slot))
+    }
+
+    fn value(&self, id: NodeId) -> Option<&Number> {
+	match self.nodes[id.0] {
+	    Node::Const(k) => Some(&self.numbers[k]),
+	    _ => None,
+	}
+    }
+
+    fn is_zero(&self, id: NodeId) -> bool {
+	self.value(id).is_some_and(|n| n.is_zero())
+    }
+
+    fn is_one(&self, id: NodeId) -> bool {
+	self.value(id).is_some_and(|n| n.real_eq(1.0))
+    }
+
+    // Store an arithmetic node, working it out when every operand is
+    // a constant and the result stays exact, ie 2 * 3 but not 2^0.5
+    fn fold(&mut self, node: Node) -> NodeId {
+	let kids = node.children();
+	if kids.iter().all(|&k| self.value(k).is_some_and(|n| n.is_exact())) {
+	    if let Ok(n) = self.apply(node, &Env::new(), |k| self.value(k).unwrap().clone()) {
+		if n.is_exact() {
+		    return self.number(n);
+		}
+	    }
+	}
+	self.intern(node)
+    }
+
+    // The constructors used to build derivatives, which drop the
+    // zeros and ones the derivative rules leave behind
+    fn zero(&mut self) -> NodeId {
+	self.number(integer(0))
+    }
+
+    fn one(&mut self) -> NodeId {
+	self.number(integer(1))
+    }
+
+    fn neg(&mut self, a: NodeId) -> NodeId {
+	match self.nodes[a.0] {
+	    Node::Neg(i) => i,
+	    _ => self.fold(Node::Neg(a)),
+	}
+    }
+
+    fn add(&mut self, a: NodeId, b: NodeId) -> NodeId {
+	if self.is_zero(a) {
+	    b
+	} else if self.is_zero(b) {
+	    a
+	} else {
+	    self.fold(Node::Add(a, b))
+	}
+    }
+
+    fn sub(&mut self, a: NodeId, b: NodeId) -> NodeId {
+	if self.is_zero(b) {
+	    a
+	} else if self.is_zero(a) {
+	    self.neg(b)
+	} else {
+	    self.fold(Node::Sub(a, b))
+	}
+    }
+
+    fn mul(&mut self, a: NodeId, b: NodeId) -> NodeId {
+	if self.is_zero(a) || self.is_one(b) {
+	    a
+	} else if self.is_zero(b) || self.is_one(a) {
+	    b
+	} else {
+	    self.fold(Node::Mul(a, b))
+	}
+    }
+
+    fn div(&mut self, a: NodeId, b: NodeId) -> NodeId {
+	if self.is_zero(a) || self.is_one(b) {
+	    a
+	} else {
+	    self.fold(Node::Div(a, b))
+	}
+    }
+
+    fn pow(&mut self, a: NodeId, b: NodeId) -> NodeId {
+	if self.is_zero(b) {
+	    self.one()
+	} else if self.is_one(b) {
+	    a
+	} else {
+	    self.fold(Node::Pow(a, b))
+	}
+    }
+
+    fn sqrt(&mut self, a: NodeId) -> NodeId {
+	let half = self.number(real(0.5));
+	self.pow(a, half)
+    }
+
+    fn square(&mut self, a: NodeId) -> NodeId {
+	let two = self.number(integer(2));
+	self.pow(a, two)
+    }
+
+    /// Differentiate the expression at a node by `sym`, with the
+    /// same rules as `derive`. Anything without a derivative rule
+    /// gives a NaN constant; use `try_derive` to find out why.
+    pub fn derive<S: Into<Symbol>>(&mut self, id: NodeId, sym: S) -> NodeId {
+	match self.try_derive(id, sym) {
+	    Ok(d) => d,
+	    Err(_) => self.number(nan()),
+	}
+    }
+
+    /// Differentiate the expression at a node by `sym`, reporting
+    /// an `Unsupported` error for a polygamma with a variable order.
+    /// Every derivative worked out is remembered, so asking again,
+    /// or for a derivative of a shared subtree, costs nothing.
+    pub fn try_derive<S: Into<Symbol>>(&mut self, id: NodeId, sym: S) -> Result<NodeId, MathError> {
+	let sym = sym.into();
+	if let Some(&d) = self.derivatives.get(&(id, sym)) {
+	    return Ok(d);
+	}
+
+	// every rule ends by multiplying with the derivative of each
+	// operand, so a part without sym always comes out as zero
+	let d = match self.nodes[id.0] {
+	    Node::Const(_) | Node::Named(_) => self.zero(),
+	    Node::Var(s) if s == sym => self.one(),
+	    Node::Var(_) => self.zero(),
+	    Node::Neg(f) => {
+		let fp = self.try_derive(f, sym)?;
+		self.neg(fp)
+	    },
+	    Node::Add(f, g) => {
+		let fp = self.try_derive(f, sym)?;
+		let gp = self.try_derive(g, sym)?;
+		self.add(fp, gp)
+	    },
+	    Node::Sub(f, g) => {
+		let fp = self.try_derive(f, sym)?;
+		let gp = self.try_derive(g, sym)?;
+		self.sub(fp, gp)
+	    },
+	    Node::Mul(f, g) => {
+		let fp = self.try_derive(f, sym)?;
+		let gp = self.try_derive(g, sym)?;
+		let a = self.mul(f, gp);
+		let b = self.mul(g, fp);
+		self.add(a, b)
+	    },
+	    // (g f' - g' f) / g^2
+	    Node::Div(f, g) => {
+		let fp = self.try_derive(f, sym)?;
+		let gp = self.try_derive(g, sym)?;
+		let a = self.mul(g, fp);
+		let b = self.mul(gp, f);
+		let top = self.sub(a, b);
+		let bottom = self.square(g);
+		self.div(top, bottom)
+	    },
+	    Node::Sin(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let c = self.intern(Node::Cos(f));
+		self.mul(fp, c)
+	    },
+	    Node::Cos(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let s = self.intern(Node::Sin(f));
+		let d = self.mul(fp, s);
+		self.neg(d)
+	    },
+	    Node::Pow(f, g) => {
+		let fp = self.try_derive(f, sym)?;
+		let gp = self.try_derive(g, sym)?;
+		if self.is_zero(gp) {
+		    // g f^(g - 1) f'
+		    let one = self.one();
+		    let gm1 = self.sub(g, one);
+		    let p = self.pow(f, gm1);
+		    let a = self.mul(g, p);
+		    self.mul(a, fp)
+		} else if self.is_zero(fp) {
+		    // f^g ln(f) g'
+		    let l = self.intern(Node::Ln(f));
+		    let a = self.mul(id, l);
+		    self.mul(a, gp)
+		} else {
+		    // f^g (g' ln(f) + g f' / f)
+		    let l = self.intern(Node::Ln(f));
+		    let a = self.mul(gp, l);
+		    let b = self.mul(g, fp);
+		    let b = self.div(b, f);
+		    let inner = self.add(a, b);
+		    self.mul(id, inner)
+		}
+	    },
+	    Node::Exp(f) => {
+		let fp = self.try_derive(f, sym)?;
+		self.mul(fp, id)
+	    },
+	    Node::Ln(f) => {
+		let fp = self.try_derive(f, sym)?;
+		self.div(fp, f)
+	    },
+	    Node::Sinh(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let c = self.intern(Node::Cosh(f));
+		self.mul(fp, c)
+	    },
+	    Node::Cosh(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let s = self.intern(Node::Sinh(f));
+		self.mul(fp, s)
+	    },
+	    // ±f' / sqrt(1 - f^2)
+	    Node::Asin(f) | Node::Acos(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let one = self.one();
+		let f2 = self.square(f);
+		let r = self.sub(one, f2);
+		let r = self.sqrt(r);
+		let d = self.div(fp, r);
+		match self.nodes[id.0] {
+		    Node::Acos(_) => self.neg(d),
+		    _ => d,
+		}
+	    },
+	    // f' / sqrt(f^2 ± 1)
+	    Node::Asinh(f) | Node::Acosh(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let one = self.one();
+		let f2 = self.square(f);
+		let r = match self.nodes[id.0] {
+		    Node::Asinh(_) => self.add(f2, one),
+		    _ => self.sub(f2, one),
+		};
+		let r = self.sqrt(r);
+		self.div(fp, r)
+	    },
+	    // Γ(f) ψ(f) f'
+	    Node::Gamma(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let psi = self.intern(Node::Digamma(f));
+		let a = self.mul(id, psi);
+		self.mul(a, fp)
+	    },
+	    // f! ψ(f + 1) f'
+	    Node::Factorial(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let one = self.one();
+		let f1 = self.add(f, one);
+		let psi = self.intern(Node::Digamma(f1));
+		let a = self.mul(id, psi);
+		self.mul(a, fp)
+	    },
+	    // f / |f| f'
+	    Node::Abs(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let a = self.div(f, id);
+		self.mul(a, fp)
+	    },
+	    Node::LnGamma(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let psi = self.intern(Node::Digamma(f));
+		self.mul(fp, psi)
+	    },
+	    Node::Digamma(f) => {
+		let fp = self.try_derive(f, sym)?;
+		let one = self.one();
+		let psi = self.intern(Node::Polygamma(one, f));
+		self.mul(fp, psi)
+	    },
+	    Node::Polygamma(n, f) => {
+		let np = self.try_derive(n, sym)?;
+		if !self.is_zero(np) {
+		    return Err(MathError::Unsupported(
+			format!("derivative of polygamma with a variable order {}", self.to_expr(n))
+		    ));
+		}
+		let fp = self.try_derive(f, sym)?;
+		let one = self.one();
+		let next = self.add(n, one);
+		let psi = self.intern(Node::Polygamma(next, f));
+		self.mul(fp, psi)
+	    },
+	    // B(a, b) ((ψ(a) - ψ(a+b)) a' + (ψ(b) - ψ(a+b)) b')
+	    Node::Beta(a, b) => {
+		let ap = self.try_derive(a, sym)?;
+		let bp = self.try_derive(b, sym)?;
+		let ab = self.add(a, b);
+		let psi_ab = self.intern(Node::Digamma(ab));
+		let psi_a = self.intern(Node::Digamma(a));
+		let psi_b = self.intern(Node::Digamma(b));
+		let da = self.sub(psi_a, psi_ab);
+		let da = self.mul(da, ap);
+		let db = self.sub(psi_b, psi_ab);
+		let db = self.mul(db, bp);
+		let inner = self.add(da, db);
+		self.mul(id, inner)
+	    },
+	};
+	self.derivatives.insert((id, sym), d);
+	Ok(d)
+    }
+
+    /// Evaluate the expression at a node against an environment,
+    /// like `evaluate_env`. Unbound variables are reported and any
+    /// numeric failure gives NaN.
+    pub fn evaluate(&self, id: NodeId, env: &Env) -> Result<Number, MathError> {
+	match self.try_evaluate(id, env) {
+	    Err(MathError::UnboundVariable(syms)) => Err(MathError::UnboundVariable(syms)),
+	    Err(_) => Ok(nan()),
+	    ok => ok,
+	}
+    }
+
+    /// Strictly evaluate the expression at a node against an
+    /// environment, reporting errors like `try_evaluate`. Each
+    /// shared node is only worked out once.
+    pub fn try_evaluate(&self, id: NodeId, env: &Env) -> Result<Number, MathError> {
+	let order = self.reachable(id);
+	let mut missing: Vec<Symbol> = order.iter()
+	    .filter_map(|i| match self.nodes[i.0] {
+		Node::Var(s) if !env.is_bound(s) => Some(s),
+		_ => None,
+	    })
+	    .collect();
+	if !missing.is_empty() {
+	    missing.sort();
+	    return Err(MathError::UnboundVariable(missing));
+	}
+
+	let mut values: Vec<Option<Number>> = vec![None; id.0 + 1];
+	for i in order {
+	    let v = self.apply(self.nodes[i.0], env, |k| values[k.0].clone().unwrap())?;
+	    values[i.0] = Some(v);
+	}
+	Ok(values[id.0].take().unwrap())
+    }
+
+    // Work out a single node given the values of its operands
+    fn apply<F: Fn(NodeId) -> Number>(&self, node: Node, env: &Env, v: F) -> Result<Number, MathError> {
+	match node {
+	    Node::Const(k) => Ok(self.numbers[k].clone()),
+	    Node::Named(c) => Ok(c.value()),
+	    Node::Var(s) => env.get(s).ok_or(MathError::UnboundVariable(vec![s])),
+	    Node::Neg(i) => Ok(-v(i)),
+	    Node::Abs(i) => Ok(v(i).abs()),
+	    Node::Add(l, r) => v(l).try_add(v(r)),
+	    Node::Sub(l, r) => v(l).try_sub(v(r)),
+	    Node::Mul(l, r) => v(l).try_mul(v(r)),
+	    Node::Div(l, r) => v(l).try_div(v(r)),
+	    Node::Pow(l, r) => v(l).try_pow(v(r)),
+	    Node::Sin(i) => v(i).try_sin(),
+	    Node::Cos(i) => v(i).try_cos(),
+	    Node::Sinh(i) => v(i).try_sinh(),
+	    Node::Cosh(i) => v(i).try_cosh(),
+	    Node::Asin(i) => v(i).try_asin(),
+	    Node::Acos(i) => v(i).try_acos(),
+	    Node::Asinh(i) => v(i).try_asinh(),
+	    Node::Acosh(i) => v(i).try_acosh(),
+	    Node::Exp(i) => v(i).try_exp(),
+	    Node::Ln(i) => v(i).try_ln(),
+	    Node::Factorial(i) => v(i).try_factorial(),
+	    Node::Gamma(i) => v(i).try_gamma(),
+	    Node::LnGamma(i) => v(i).try_ln_gamma(),
+	    Node::Digamma(i) => v(i).try_digamma(),
+	    Node::Beta(a, b) => v(a).try_beta(v(b)),
+	    Node::Polygamma(n, i) => {
+		let order = v(n);
+		if !order.is_whole() || order.imag() != 0.0 || order.real() < 0.0 {
+		    return Err(MathError::DomainError(format!("polygamma of order {}", order)));
+		}
+		v(i).try_polygamma(order.real() as u32)
+	    },
+	}
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::calc::deriver::*;
+
+    #[test]
+    fn test_sharing_and_round_trip() {
+	let mut dag = Dag::new();
+	let f = Expr::parse("sin(x^2) * sin(x^2) + 2.5 / (x^2 + pi)").unwrap();
+	let id = dag.insert(&f);
+	assert_eq!(dag.to_expr(id), f);
+	// x, 2, x^2, sin, *, 2.5, pi, x^2 + pi, /, +
+	assert_eq!(dag.len(), 10);
+	assert_eq!(dag.count(id), 10);
+
+	// inserting again stores nothing new
+	assert_eq!(dag.insert(&f), id);
+	let g = dag.insert(&Expr::parse("sin(x^2)").unwrap());
+	assert_eq!(dag.len(), 10);
+	assert_eq!(dag.count(g), 4);
+    }
+
+    #[test]
+    fn test_matches_deriver() {
+	let fs = [
+	    "x^3 * sin(x)", "ln(x^2 + 1) / cosh(x)", "x^x", "2^x + asin(x / 4)",
+	    "gamma(x) * beta(x, 2)", "acosh(2x) - |x - 3| + (x^2)!", "digamma(x)",
+	];
+	for s in fs.iter() {
+	    let f = Expr::parse(s).unwrap();
+	    let mut dag = Dag::new();
+	    let id = dag.insert(&f);
+	    let d = dag.derive(id, 'x');
+	    let tree = derive(f.clone(), 'x');
+	    for x0 in [0.7, 1.3, 2.2].iter() {
+		let env = Env::new().bind('x', real(*x0));
+		let a = dag.evaluate(d, &env).unwrap().real();
+		let b = evaluate_env(&tree, &env).unwrap().real();
+		assert!((a - b).abs() <= 1e-9 * (1.0 + b.abs()), "d/dx {} at {}: {} vs {}", s, x0, a, b);
+		let e = evaluate_env(&dag.to_expr(d), &env).unwrap().real();
+		assert!((a - e).abs() <= 1e-9 * (1.0 + e.abs()));
+	    }
+	}
+
+	let mut dag = Dag::new();
+	let p = dag.insert(&polygamma(var('x'), var('x')));
+	assert!(matches!(dag.try_derive(p, 'x'), Err(MathError::Unsupported(_))));
+	let d = dag.derive(p, 'x');
+	assert!(dag.evaluate(d, &Env::new()).unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_repeated_derivatives() {
+	// e^sin(x) = 1 + x + x^2/2 - x^4/8 - x^5/15 - x^6/240 + x^7/90
+	//            + 31x^8/5760 + x^9/5670 - 2951x^10/3628800 + ...
+	let expected = [1.0, 1.0, 1.0, 0.0, -3.0, -8.0, -3.0, 56.0, 217.0, 64.0, -2951.0];
+	let mut dag = Dag::new();
+	let mut f = dag.insert(&Expr::parse("e^sin(x)").unwrap());
+	let env = Env::new().bind('x', real(0.0));
+	for (k, value) in expected.iter().enumerate() {
+	    if k > 0 {
+		f = dag.derive(f, 'x');
+	    }
+	    let v = dag.evaluate(f, &env).unwrap().real();
+	    assert!((v - value).abs() < 1e-9, "derivative {} is {}", k, v);
+	}
+	// as a tree the tenth derivative has hundreds of thousands of
+	// nodes, but in the arena it takes a few hundred
+	assert!(dag.len() < 500, "{} nodes", dag.len());
+
+	// derivatives are remembered
+	let n = dag.len();
+	let id = dag.insert(&Expr::parse("e^sin(x)").unwrap());
+	dag.derive(id, 'x');
+	assert_eq!(dag.len(), n);
+
+	let g = dag.insert(&Expr::parse("x^2 * y").unwrap());
+	let missing = vec![Symbol::from('y')];
+	assert_eq!(dag.try_evaluate(g, &env), Err(MathError::UnboundVariable(missing)));
+    }
+}
+
+// end src/expr/dag.rs
@@ -0,0 +1,287 @@
+// src/expr/display.rs
+
+use std::fmt;
+use crate::expr::expr::*;
+use crate::expr::expr::Expr::*;
+use crate::expr::constant::*;
+use crate::number::number::*;
+
+
+/// The characters used when printing an `Expr`. `Ascii` output
+/// reads back with `Expr::parse` as the same tree, except that a
+/// float comes back as the exact decimal it prints as and an
+/// infinite float as the named constant `inf`. `Unicode` uses
+/// superscripts, `·` and `√` to be easier on the eyes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintStyle {
+    Ascii,
+    Unicode,
+}
+
+/// An `Expr` paired with the `PrintStyle` to display it in,
+/// made with `Expr::styled`.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("2x^2 + sqrt(x) / (x + 1)").unwrap();
+/// assert_eq!(f.to_string(), "2x^2 + x^0.5/(x + 1)");
+/// assert_eq!(f.styled(PrintStyle::Unicode).to_string(), "2x² + √x/(x + 1)");
+/// ```
+pub struct Styled<'a> {
+    expr: &'a Expr,
+    style: PrintStyle,
+}
+
+impl Expr {
+    /// Display an expression in the given `PrintStyle`. The
+    /// plain `Display` impl is the same as `PrintStyle::Ascii`.
+    pub fn styled(&self, style: PrintStyle) -> Styled<'_> {
+	Styled { expr: self, style }
+    }
+}
+
+impl<'a> fmt::Display for Styled<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f, "{}", render(self.expr, self.style))
+    }
+}
+
+/// Print an expression with as few brackets as the precedence and
+/// associativity of each operation allows, in `PrintStyle::Ascii`.
+///
+/// ```
+/// use mathy::calc::prelude::*;
+/// let e1 = 2.0 * var('x');
+/// assert_eq!(e1.to_string(), "2x");
+/// // use this for raw debugging
+/// println!("My Expr: {:?}", e1);
+/// ```
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f, "{}", render(self, PrintStyle::Ascii))
+    }
+}
+
+fn render(e: &Expr, style: PrintStyle) -> String {
+    let unicode = style == PrintStyle::Unicode;
+    match e {
+	Const(n) => n.to_string(),
+	Named(c) => {
+	    match (c, unicode) {
+		(Constant::Pi, true) => "π".to_string(),
+		(Constant::Infinity, true) => "∞".to_string(),
+		(c, _) => c.to_string(),
+	    }
+	},
+	Var(s) => s.to_string(),
+	Neg(ref i) => {
+	    // -(2) would read back as the constant -2
+	    if i.precedence() <= 3 || matches!(i.as_ref(), Const(_)) {
+		format!("-{}", bracket(i, style))
+	    } else {
+		format!("-{}", render(i, style))
+	    }
+	},
+	Add(ref l, ref r) => format!("{} + {}", render(l, style), right_operand(r, 1, style)),
+	Sub(ref l, ref r) => format!("{} - {}", render(l, style), right_operand(r, 1, style)),
+	Mul(ref l, ref r) => {
+	    let left = operand(l, 2, style);
+	    let right = right_operand(r, 2, style);
+	    if juxtapose(l, &right) {
+		format!("{}{}", left, right)
+	    } else if unicode {
+		format!("{}·{}", left, right)
+	    } else {
+		format!("{}*{}", left, right)
+	    }
+	},
+	Div(ref l, ref r) => format!("{}/{}", operand(l, 2, style), right_operand(r, 2, style)),
+	Pow(ref b, ref p) => {
+	    if unicode {
+		if let Const(n) = p.as_ref() {
+		    if n.real_eq(0.5) {
+			return format!("√{}", operand(b, 6, style));
+		    }
+		    if let Some(sup) = superscript(n) {
+			return format!("{}{}", base(b, style), sup);
+		    }
+		}
+	    }
+	    format!("{}^{}", base(b, style), operand(p, 3, style))
+	},
+	Exp(ref i) => {
+	    match (unicode, i.as_ref()) {
+		(true, Const(n)) if superscript(n).is_some() => {
+		    format!("e{}", superscript(n).unwrap())
+		},
+		(_, i) => format!("e^{}", operand(i, 3, style)),
+	    }
+	},
+	Factorial(ref i) => format!("{}!", operand(i, 5, style)),
+	Abs(ref i) => format!("|{}|", render(i, style)),
+	_ => {
+	    let args: Vec<String> = e.children().iter().map(|c| render(c, style)).collect();
+	    format!("{}({})", function_name(e, style), args.join(", "))
+	},
+    }
+}
+
+// The name of a function in the given style
+pub(crate) fn function_name(e: &Expr, style: PrintStyle) -> &'static str {
+    let unicode = style == PrintStyle::Unicode;
+    match e {
+	Sin(_) => "sin",
+	Cos(_) => "cos",
+	Sinh(_) => "sinh",
+	Cosh(_) => "cosh",
+	Asin(_) => "asin",
+	Acos(_) => "acos",
+	Asinh(_) => "asinh",
+	Acosh(_) => "acosh",
+	Ln(_) => "ln",
+	Gamma(_) if unicode => "Γ",
+	Gamma(_) => "gamma",
+	LnGamma(_) if unicode => "lnΓ",
+	LnGamma(_) => "lngamma",
+	Digamma(_) | Polygamma(_, _) if unicode => "ψ",
+	Digamma(_) => "digamma",
+	Polygamma(_, _) => "polygamma",
+	_ => "beta",
+    }
+}
+
+fn bracket(e: &Expr, style: PrintStyle) -> String {
+    format!("({})", render(e, style))
+}
+
+// Fractions are bracketed inside a product or power, since
+// 1/3 would otherwise be read back as a division
+fn operand(e: &Expr, precedence: u8, style: PrintStyle) -> String {
+    let fraction = matches!(e, Const(Number::Rational(_))) && precedence > 1;
+    if e.precedence() < precedence || fraction {
+	bracket(e, style)
+    } else {
+	render(e, style)
+    }
+}
+
+// The right side of a left associative operator also needs brackets
+// at the same precedence, and negatives are bracketed to avoid
+// printing things like x - -y
+fn right_operand(e: &Expr, precedence: u8, style: PrintStyle) -> String {
+    if e.precedence() == precedence || e.precedence() == 3 {
+	bracket(e, style)
+    } else {
+	operand(e, precedence, style)
+    }
+}
+
+// The base of a power, which needs brackets for anything but an atom
+// or a factorial. A bare e would be read back as the exponential
+fn base(b: &Expr, style: PrintStyle) -> String {
+    match b {
+	Named(Constant::E) => bracket(b, style),
+	b => operand(b, 5, style),
+    }
+}
+
+// Write a number times something as 2x, as long as the number
+// can't run into what follows it, like 2 * 3 or 2 * e5
+pub(crate) fn juxtapose(l: &Expr, right: &str) -> bool {
+    let number = match l {
+	Const(Number::Integer(_)) | Const(Number::Rational(_)) => true,
+	Const(Number::Real(x)) => x.is_finite(),
+	_ => false,
+    };
+    let mut chars = right.chars();
+    let first = chars.next();
+    let letter = match first {
+	Some('e') | Some('E') => !chars.next().is_some_and(|c| c.is_ascii_digit() || c == '+' || c == '-'),
+	Some('j') => chars.next().is_some_and(|c| c.is_alphanumeric()),
+	Some(c) => c.is_alphabetic() || c == '(' || c == '√',
+	None => false,
+    };
+    number && letter
+}
+
+// A whole number power as superscript digits, ie ² or ⁻¹
+fn superscript(n: &Number) -> Option<String> {
+    const DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+    if !n.is_whole() || n.imag() != 0.0 || n.real().abs() > 1e15 {
+	return None;
+    }
+    let k = n.real() as i64;
+    let digits: String = k.unsigned_abs().to_string().chars()
+	.map(|d| DIGITS[d.to_digit(10).unwrap() as usize])
+	.collect();
+    Some(if k < 0 { format!("⁻{}", digits) } else { digits })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::expr::evaluator::*;
+
+    fn ascii(s: &str) -> String {
+	Expr::parse(s).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_minimal_brackets() {
+	assert_eq!(ascii("(x + 1) + (y + 2)"), "x + 1 + (y + 2)");
+	assert_eq!(ascii("x - (y - z)"), "x - (y - z)");
+	assert_eq!(ascii("x - y - z"), "x - y - z");
+	assert_eq!(ascii("x / (y * z)"), "x/(y*z)");
+	assert_eq!(ascii("(x^2)^3"), "(x^2)^3");
+	assert_eq!(ascii("x^y^z"), "x^y^z");
+	assert_eq!(ascii("-(x + 1)"), "-(x + 1)");
+	assert_eq!(ascii("-x^2"), "-x^2");
+	assert_eq!(ascii("x * -y"), "x*(-y)");
+	assert_eq!(ascii("2 * 3"), "2*3");
+	assert_eq!(ascii("2 sin(x)"), "2sin(x)");
+	assert_eq!(ascii("(x - 2.5)!"), "(x - 5/2)!");
+	assert_eq!(ascii("gamma(x) + e^(2x)"), "gamma(x) + e^(2x)");
+    }
+
+    #[test]
+    fn test_old_bugs() {
+	// the constant used to be printed before the variable
+	assert_eq!(mul(var('x'), con(2.0)).to_string(), "x*2");
+	// compound denominators lost their brackets
+	assert_eq!(div(con(1.0), add(var('x'), con(1.0))).to_string(), "1/(x + 1)");
+	assert_eq!(neg(var('x')).to_string(), "-x");
+    }
+
+    #[test]
+    fn test_constants_read_back() {
+	let constants = [
+	    integer(-7), rational(5, 2), rational(-1, 3), complex(1.0, 2.0),
+	    complex(1.5, -2.0), complex(-1.0, 0.5), imag(-2.0), imag(3.0),
+	];
+	for c in constants.iter() {
+	    for e in [Const(c.clone()), sub(var('x'), Const(c.clone())), pow(var('x'), Const(c.clone()))].iter() {
+		let parsed = Expr::parse(&e.to_string()).unwrap();
+		assert_eq!(format!("{:?}", parsed), format!("{:?}", e), "reading back {}", e);
+	    }
+	}
+
+	// an infinite float comes back as the named constant
+	let inf = Expr::parse(&Const(real(f64::INFINITY)).to_string()).unwrap();
+	assert_eq!(inf, infinity());
+	assert_eq!(try_evaluate(&inf, &Env::new()), Ok(real(f64::INFINITY)));
+    }
+
+    #[test]
+    fn test_unicode() {
+	let u = |s: &str| Expr::parse(s).unwrap().styled(PrintStyle::Unicode).to_string();
+	assert_eq!(u("x^2 * y^-1"), "x²·y⁻¹");
+	assert_eq!(u("sqrt(x + 1)"), "√(x + 1)");
+	assert_eq!(u("2 pi x"), "2π·x");
+	assert_eq!(u("gamma(x)^10"), "Γ(x)¹⁰");
+	assert_eq!(u("e^2"), "e²");
+	assert_eq!(u("x^y"), "x^y");
+    }
+}
+
+// end src/expr/display.rs
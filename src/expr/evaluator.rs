@@ -1,127 +1,153 @@
 // src/calc/evaluator.rs
 
+use std::collections::HashMap;
+
+use crate::error::*;
 use crate::expr::expr::*;
 use crate::expr::expr::Expr::*;
+use crate::expr::symbol::*;
 use crate::number::number::*;
-//use crate::number::number::Number::*;
 
-/// Execute an evaluation against a given `Expr` tree.
-/// Takes three arguments; an `Expr` tree, a symbol char,
-/// and a `Number` type. 
+/// A set of variable bindings used to evaluate an `Expr` with
+/// any number of variables. Built up with `bind()`:
 ///
 /// ```
 /// use mathy::expr::prelude::*;
-/// let f = powf(var('x'), 2.0);
-/// let y = evaluate(f, 'x', real(10.0));
-/// assert_eq!(y, real(100.0));
+/// let f = var('x') * var('y') + var('z');
+/// let env = Env::new()
+///     .bind('x', real(2.0))
+///     .bind('y', real(3.0))
+///     .bind('z', real(4.0));
+/// assert_eq!(evaluate_env(&f, &env), Ok(real(10.0)));
 /// ```
-pub fn evaluate(e: Expr, sym: char, v: Number) -> Number {
-    match e {
-        Const(c) => c,
-        Var(x) if x == sym => v, 
-		
-	Neg(ref i) => {
-	    let inner = evaluate(unpack(i), sym, v);
-	    -inner
-	},
-
-        Add(ref l, ref r) => {
-            let left = evaluate(unpack(l), sym, v);
-            let right = evaluate(unpack(r), sym, v);
-	    left + right
-        },
-
-        Sub(ref l, ref r) => {
-            let left = evaluate(unpack(l), sym, v);
-            let right = evaluate(unpack(r), sym, v);
-	    left - right
-        },
-
-        Mul(ref l, ref r) => {
-            let left = evaluate(unpack(l), sym, v);
-            let right = evaluate(unpack(r), sym, v);
-	    left * right
-        },
-
-        Div(ref l, ref r) => {
-            let left = evaluate(unpack(l), sym, v);
-            let right = evaluate(unpack(r), sym, v);
-	    left / right
-        },
-
-        Sin(ref i) => {
-            let inner = evaluate(unpack(i), sym, v);
-	    inner.sin()
-        },
-
-        Cos(ref i) => {
-            let inner = evaluate(unpack(i), sym, v);
-	    inner.cos()
-        },
-
-	Sinh(ref i) => {
-	    let inner = evaluate(unpack(i), sym, v);
-	    inner.sinh()
-	},
-
-	Cosh(ref i) => {
-	    let inner = evaluate(unpack(i), sym, v);
-	    inner.cosh()
-	},
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Env {
+    bindings: HashMap<Symbol, Number>,
+}
 
+impl Env {
+    /// Create an empty environment.
+    pub fn new() -> Env {
+	Env { bindings: HashMap::new() }
+    }
 
-	Asin(ref i) => {
-	    let inner = evaluate(unpack(i), sym, v);
-	    inner.asin()
-	},
+    /// Bind a symbol to a value, returning the environment
+    /// so calls can be chained.
+    pub fn bind<S: Into<Symbol>>(mut self, sym: S, v: Number) -> Env {
+	self.set(sym, v);
+	self
+    }
 
+    /// Bind a symbol to a value in place, replacing any old value.
+    pub fn set<S: Into<Symbol>>(&mut self, sym: S, v: Number) {
+	self.bindings.insert(sym.into(), v);
+    }
 
-	Acos(ref i) => {
-	    let inner = evaluate(unpack(i), sym, v);
-	    inner.acos()
-	},
+    /// Look up the value bound to a symbol.
+    pub fn get<S: Into<Symbol>>(&self, sym: S) -> Option<Number> {
+	self.bindings.get(&sym.into()).cloned()
+    }
 
+    /// Check if a symbol has a value bound to it.
+    pub fn is_bound<S: Into<Symbol>>(&self, sym: S) -> bool {
+	self.bindings.contains_key(&sym.into())
+    }
 
-	Asinh(ref i) => {
-	    let inner = evaluate(unpack(i), sym, v);
-	    inner.asinh()
-	},
+    /// List every variable in an `Expr` which has no value in this
+    /// environment, sorted by name and without duplicates.
+    pub fn unbound(&self, e: &Expr) -> Vec<Symbol> {
+	let mut syms = e.free_symbols();
+	syms.retain(|s| !self.bindings.contains_key(s));
+	syms
+    }
+}
 
+/// Evaluate an `Expr` tree against an environment of variable
+/// bindings. Every variable in the tree must have a value in
+/// the `Env`, otherwise the unbound ones are reported back as a
+/// `MathError::UnboundVariable`. Numeric failures such as a
+/// division by zero evaluate to NaN; use `try_evaluate` to
+/// have those reported as errors too.
+pub fn evaluate_env(e: &Expr, env: &Env) -> Result<Number, MathError> {
+    match try_evaluate(e, env) {
+	Ok(n) => Ok(n),
+	Err(MathError::UnboundVariable(syms)) => Err(MathError::UnboundVariable(syms)),
+	Err(_) => Ok(nan()),
+    }
+}
 
-	Acosh(ref i) => {
-	    let inner = evaluate(unpack(i), sym, v);
-	    inner.acosh()
-	},
+/// Strictly evaluate an `Expr` tree against an environment,
+/// reporting unbound variables, domain errors, division by zero,
+/// overflow and unsupported operations instead of producing NaN.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = con(1.0) / var('x');
+/// let env = Env::new().bind('x', real(0.0));
+/// assert_eq!(try_evaluate(&f, &env), Err(MathError::DivisionByZero));
+/// ```
+pub fn try_evaluate(e: &Expr, env: &Env) -> Result<Number, MathError> {
+    let missing = env.unbound(e);
+    if !missing.is_empty() {
+	return Err(MathError::UnboundVariable(missing));
+    }
+    eval(e, env)
+}
 
-	Pow(ref l, ref r) => {
-	    let left = evaluate(unpack(l), sym, v);
-	    let right = evaluate(unpack(r), sym, v);		
-	    left.pow(right)
-	}
-	
-	Exp(ref i) => {
-	    let inner = evaluate(unpack(i), sym, v);
-	    inner.exp()
-	},
-	
-	Ln(ref i) => {
-	    let inner = evaluate(unpack(i), sym, v);
-	    inner.ln()
-	},
+/// Execute an evaluation against a given `Expr` tree.
+/// Takes three arguments; an `Expr` tree, a symbol (anything
+/// that converts into a `Symbol`, like a char or a &str),
+/// and a `Number` type. Any other variable evaluates to NaN,
+/// use `evaluate_env` for expressions of several variables.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = powf(var('x'), 2.0);
+/// let y = evaluate(f, 'x', real(10.0));
+/// assert_eq!(y, real(100.0));
+/// ```
+pub fn evaluate<S: Into<Symbol>>(e: Expr, sym: S, v: Number) -> Number {
+    let env = Env::new().bind(sym, v);
+    evaluate_env(&e, &env).unwrap_or(nan())
+}
 
-	Factorial(ref i) => {
-	    let inner = evaluate(unpack(i), sym, v);
-	    inner.factorial()
-	},
+// Recursively evaluate a tree where all variables are known to be bound
+fn eval(e: &Expr, env: &Env) -> Result<Number, MathError> {
+    match e {
+        Const(c) => Ok(c.clone()),
+        Named(c) => Ok(c.value()),
+        Var(x) => env.get(*x).ok_or(MathError::UnboundVariable(vec![*x])),
 
-	/*
-	Gamma(ref i) => {
+	Neg(ref i) => Ok(-eval(i, env)?),
+	Abs(ref i) => Ok(eval(i, env)?.abs()),
+        Add(ref l, ref r) => eval(l, env)?.try_add(eval(r, env)?),
+        Sub(ref l, ref r) => eval(l, env)?.try_sub(eval(r, env)?),
+        Mul(ref l, ref r) => eval(l, env)?.try_mul(eval(r, env)?),
+        Div(ref l, ref r) => eval(l, env)?.try_div(eval(r, env)?),
+	Pow(ref l, ref r) => eval(l, env)?.try_pow(eval(r, env)?),
 
+        Sin(ref i) => eval(i, env)?.try_sin(),
+        Cos(ref i) => eval(i, env)?.try_cos(),
+	Sinh(ref i) => eval(i, env)?.try_sinh(),
+	Cosh(ref i) => eval(i, env)?.try_cosh(),
+	Asin(ref i) => eval(i, env)?.try_asin(),
+	Acos(ref i) => eval(i, env)?.try_acos(),
+	Asinh(ref i) => eval(i, env)?.try_asinh(),
+	Acosh(ref i) => eval(i, env)?.try_acosh(),
+	Exp(ref i) => eval(i, env)?.try_exp(),
+	Ln(ref i) => eval(i, env)?.try_ln(),
+	Factorial(ref i) => eval(i, env)?.try_factorial(),
+	Gamma(ref i) => eval(i, env)?.try_gamma(),
+	LnGamma(ref i) => eval(i, env)?.try_ln_gamma(),
+	Digamma(ref i) => eval(i, env)?.try_digamma(),
+	Beta(ref a, ref b) => eval(a, env)?.try_beta(eval(b, env)?),
+	Polygamma(ref n, ref i) => {
+	    let order = eval(n, env)?;
+	    if !order.is_whole() || order.imag() != 0.0 || order.real() < 0.0 {
+		return Err(MathError::DomainError(format!("polygamma of order {}", order)));
+	    }
+	    eval(i, env)?.try_polygamma(order.real() as u32)
 	},
-	*/
-
-	// Leftover, can't evaluate?
-	_ => nan(), 
     }
 }
 
@@ -158,6 +184,73 @@ mod test {
 	let value3 = evaluate(expr3, 'x', real(-4.0));
 	assert_eq!(value3, complex(0.0, 2.0));
     }
+
+    #[test]
+    fn test_named_constants() {
+	let env = Env::new();
+	assert_eq!(evaluate_env(&pi(), &env), Ok(real(std::f64::consts::PI)));
+	assert_eq!(evaluate_env(&mul(two(), e()), &env), Ok(real(2.0 * std::f64::consts::E)));
+
+	// without simplifying, e^(i*pi) is only -1 to within rounding
+	let euler = evaluate_env(&exp(mul(imag_unit(), pi())), &env).unwrap();
+	assert!((euler + real(1.0)).abs().real() < 1e-15);
+    }
+
+    #[test]
+    fn test_env_multivariate() {
+	let f = Expr::parse("x*y + z").unwrap();
+	let env = Env::new()
+	    .bind('x', real(2.0))
+	    .bind('y', real(5.0))
+	    .bind('z', real(-1.0));
+	assert_eq!(evaluate_env(&f, &env), Ok(real(9.0)));
+    }
+
+    #[test]
+    fn test_env_unbound() {
+	let f = Expr::parse("x*y + z + sinh(w)").unwrap();
+	let env = Env::new().bind('y', real(1.0));
+	let names: Vec<&str> = match evaluate_env(&f, &env) {
+	    Err(MathError::UnboundVariable(syms)) => syms.iter().map(|s| s.name()).collect(),
+	    other => panic!("expected unbound variables, got {:?}", other),
+	};
+	assert_eq!(names, vec!["w", "x", "z"]);
+
+	// the single variable shim still gives NaN
+	assert!(evaluate(f, 'y', real(1.0)).is_nan());
+    }
+
+    #[test]
+    fn test_gamma_nodes() {
+	let env = Env::new().bind('x', real(3.0)).bind('y', real(2.0));
+	let f = gamma(var('x')) + ln_gamma(var('x')) + beta(var('x'), var('y'));
+	let expected = real(2.0) + real(2.0_f64.ln()) + real(1.0 / 12.0);
+	let got = evaluate_env(&f, &env).unwrap();
+	assert!((got - expected).abs().real() < 1e-12);
+
+	let d = polygamma(con(1.0), var('x')) - digamma(var('x'));
+	assert!(evaluate_env(&d, &env).unwrap().is_finite());
+	assert_eq!(evaluate(factorial(var('x')), 'x', real(4.0)), real(24.0));
+    }
+
+    #[test]
+    fn test_try_evaluate() {
+	let env = Env::new().bind('x', real(0.0));
+
+	let f = ln(var('x'));
+	assert!(matches!(try_evaluate(&f, &env), Err(MathError::DomainError(_))));
+	assert!(evaluate_env(&f, &env).unwrap().is_nan());
+
+	let g = gamma(var('x'));
+	assert!(matches!(try_evaluate(&g, &env), Err(MathError::DomainError(_))));
+
+	let p = polygamma(con(0.5), var('x'));
+	assert!(matches!(try_evaluate(&p, &env), Err(MathError::DomainError(_))));
+
+	// a NaN constant is a legitimate result, not an error
+	let h = add(Const(nan()), var('x'));
+	assert!(try_evaluate(&h, &env).unwrap().is_nan());
+    }
 }
 
 // end evaluator.rs
@@ -2,6 +2,8 @@
 
 use std::ops::{Add,Sub,Mul,Div,Neg};
 use crate::number::number::*;
+use crate::expr::symbol::*;
+use crate::expr::constant::*;
 
 
 pub type E = Box<Expr>;
@@ -16,8 +18,10 @@ pub type E = Box<Expr>;
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Const(Number),
-    Var(char),
+    Named(Constant),
+    Var(Symbol),
     Neg(E),
+    Abs(E),
     Add(E, E),
     Sub(E, E),
     Mul(E, E),
@@ -35,6 +39,10 @@ pub enum Expr {
     Exp(E),
     Factorial(E),
     Gamma(E),
+    LnGamma(E),
+    Digamma(E),
+    Polygamma(E, E),
+    Beta(E, E),
 }
 
 use self::Expr::*;
@@ -48,211 +56,116 @@ impl Expr {
     /// Check if the top level of the Expr tree
     /// is a constant value and return true.
     pub fn is_const(&self) -> bool {
-        match self {
-            Const(_) => true,
-            _ => false,
-        }
+        matches!(self, Const(_) | Named(_))
     }
 
     /// Check if the top level of the Expr tree
     /// is a variable and return true.
     pub fn is_var(&self) -> bool {
-        match self {
-            Var(_) => true,
-            _ => false,
-        }
+        matches!(self, Var(_))
     }
 
     /// Check if the top level of the Expr tree is
     /// an an operation and return true.
     pub fn is_op(&self) -> bool {
-        match self {
-            Const(_) => false,
-            Var(_) => false,
-            _ => true,
-        }
+        !matches!(self, Const(_) | Named(_) | Var(_))
+    }
+
+    /// How tightly the top level of the Expr tree binds when it
+    /// is written out, where higher binds tighter. Sums are 1,
+    /// products and quotients 2, negation 3, powers 4, factorials
+    /// 5 and anything which never needs brackets, like a variable
+    /// or a function call, is 6. Numbers rank by how they print,
+    /// so a negative constant counts as a negation.
+    pub fn precedence(&self) -> u8 {
+	match self {
+	    Add(_, _) | Sub(_, _) => 1,
+	    Mul(_, _) | Div(_, _) => 2,
+	    Neg(_) => 3,
+	    Pow(_, _) | Exp(_) => 4,
+	    Factorial(_) => 5,
+	    Const(n) => {
+		match n {
+		    Number::Complex(x, _) if *x != 0.0 => 1,
+		    Number::Complex(_, z) if *z < 0.0 => 3,
+		    Number::Complex(_, _) => 6,
+		    Number::Rational(_) => 2,
+		    n if n.real() < 0.0 => 3,
+		    _ => 6,
+		}
+	    },
+	    _ => 6,
+	}
     }
 
     /// Recursively dive into the Expr tree and see
     /// if it contains a Var(c) type where c == s.
-    /// TODO: finish the rest of the expression variants
-    pub fn has_var(&self, s: char) -> bool {
-        match self {
-            Var(x)     => { s == *x },
-            Neg(ref e) => { e.has_var(s) },
-            Exp(ref e) => { e.has_var(s) },
-            Ln(ref e)  => { e.has_var(s) },
-            Sin(ref e) => { e.has_var(s) },
-            Cos(ref e) => { e.has_var(s) },
-            Add(ref l, ref r) => { l.has_var(s) || r.has_var(s) },
-            Sub(ref l, ref r) => { l.has_var(s) || r.has_var(s) },
-            Mul(ref l, ref r) => { l.has_var(s) || r.has_var(s) },
-            Div(ref l, ref r) => { l.has_var(s) || r.has_var(s) },
-            Pow(ref l, ref r) => { l.has_var(s) || r.has_var(s) },
-	    _ => false,
-        }
+    pub fn has_var<S: Into<Symbol>>(&self, s: S) -> bool {
+	let s = s.into();
+	self.nodes().any(|n| matches!(n, Var(x) if *x == s))
     }
 
     /// Use this to substitute any variable with another recurisvely.
-    pub fn substitute(&self, sym1: char, sym2: char) -> Expr {
+    pub fn substitute<S1: Into<Symbol>, S2: Into<Symbol>>(&self, sym1: S1, sym2: S2) -> Expr {
+	let (sym1, sym2) = (sym1.into(), sym2.into());
         match self {
-            Var(x) => {
-                if *x == sym1 {
-                    Var(sym2)
-                } else {
-                    Var(*x)
-                }
-            },
-            Neg(ref i) => neg(i.substitute(sym1, sym2)),
-            Exp(ref i) => exp(i.substitute(sym1, sym2)),
-            Sin(ref i) => sin(i.substitute(sym1, sym2)),
-            Cos(ref i) => cos(i.substitute(sym1, sym2)),
-            Sinh(ref i) => sinh(i.substitute(sym1, sym2)),
-            Cosh(ref i) => cosh(i.substitute(sym1, sym2)),
-            Asin(ref i) => asin(i.substitute(sym1, sym2)),
-            Acos(ref i) => acos(i.substitute(sym1, sym2)),
-            Asinh(ref i) => asinh(i.substitute(sym1, sym2)),
-            Acosh(ref i) => acosh(i.substitute(sym1, sym2)),
-            Add(ref l, ref r) => add(l.substitute(sym1, sym2), r.substitute(sym1, sym2)),
-            Sub(ref l, ref r) => sub(l.substitute(sym1, sym2), r.substitute(sym1, sym2)),
-            Mul(ref l, ref r) => mul(l.substitute(sym1, sym2), r.substitute(sym1, sym2)),
-            Div(ref l, ref r) => div(l.substitute(sym1, sym2), r.substitute(sym1, sym2)),
-            Pow(ref l, ref r) => pow(l.substitute(sym1, sym2), r.substitute(sym1, sym2)),
-
-	    // fallback in case not everything is mapped
-            e => e.clone(),
+            Var(x) if *x == sym1 => Var(sym2),
+            e => e.map_children(|c| c.substitute(sym1, sym2)),
         }
     }
 
-    /// Map an Expr to a String type with recursive formatting.
-    /// Use this if you want better formula visualization.
+    /// The direct children of a node, left to right. Constants
+    /// and variables have none.
+    pub fn children(&self) -> Vec<&Expr> {
+	match self {
+	    Const(_) | Named(_) | Var(_) => vec![],
+	    Neg(ref i) | Abs(ref i) | Sin(ref i) | Cos(ref i) | Sinh(ref i) | Cosh(ref i) |
+	    Asin(ref i) | Acos(ref i) | Asinh(ref i) | Acosh(ref i) |
+	    Ln(ref i) | Exp(ref i) | Factorial(ref i) | Gamma(ref i) |
+	    LnGamma(ref i) | Digamma(ref i) => vec![i],
+	    Add(ref l, ref r) | Sub(ref l, ref r) | Mul(ref l, ref r) |
+	    Div(ref l, ref r) | Pow(ref l, ref r) |
+	    Polygamma(ref l, ref r) | Beta(ref l, ref r) => vec![l, r],
+	}
+    }
+
+    /// Rebuild a node with the same operation, applying `f` to each
+    /// child from left to right. Constants and variables come back
+    /// as they are.
     ///
     /// ```
-    /// use mathy::calc::prelude::*;
-    /// let e1 = var('x') * con(2.0);
-    /// println!("My equation: {}", e1.to_string());
-    /// // use this for raw debugging
-    /// println!("My Expr: {:?}", e1);
+    /// use mathy::expr::prelude::*;
+    /// let f = Expr::parse("sin(x) + gamma(x)").unwrap();
+    /// let g = f.map_children(|c| c.map_children(|_| var('y')));
+    /// assert_eq!(g.to_string(), "sin(y) + gamma(y)");
     /// ```
-    pub fn to_string(&self) -> String {
-        match self {
-            Const(c) => String::from(format!("{}", c.to_string())),
-            Var(s)   => String::from(format!("{}", s)),
-            Neg(ref i) => String::from(format!("-({})",   i.to_string())),
-            Ln(ref i)  => String::from(format!("ln({})",  i.to_string())),
-            Exp(ref i) => String::from(format!("e^({})",  i.to_string())),
-            Sin(ref i) => String::from(format!("sin({})", i.to_string())),
-            Cos(ref i) => String::from(format!("cos({})", i.to_string())),
-	    Sinh(ref i) => String::from(format!("sinh({})", i.to_string())),
-	    Cosh(ref i) => String::from(format!("cosh({})", i.to_string())),
-	    Asin(ref i) => String::from(format!("asin({})", i.to_string())),
-	    Acos(ref i) => String::from(format!("acos({})", i.to_string())),
-	    Asinh(ref i) => String::from(format!("asinh({})", i.to_string())),
-	    Acosh(ref i) => String::from(format!("acosh({})", i.to_string())),
-            Add(ref l, ref r) => {
-		let left = unpack(l);
-		let right = unpack(r);
-		match (left, right) {
-		    (Const(a), Const(b)) => {
-			String::from(format!("{} + {}", a.to_string(), b.to_string()))
-		    },
-		    (Const(a), b) => {
-			String::from(format!("{} + ({})", a.to_string(), b.to_string()))
-		    },
-		    (a, Const(b)) => {
-			String::from(format!("({}) + {}", a.to_string(), b.to_string()))
-		    },
-		    (Var(c), b) => {
-			String::from(format!("{} + ({})", c, b.to_string()))
-		    },
-		    (a, b) => {
-			String::from(format!("({}) + ({})", a.to_string(), b.to_string()))
-		    }
-		}
-            },
-            Sub(ref l, ref r) => {
-		let left = unpack(l);
-		let right = unpack(r);
-		match (left, right) {
-		    (Const(a), Const(b)) => {
-			String::from(format!("{} - {}", a.to_string(), b.to_string()))
-		    },
-		    (Const(a), b) => {
-			String::from(format!("{} - ({})", a.to_string(), b.to_string()))
-		    },
-		    (a, Const(b)) => {
-			String::from(format!("({}) - {}", a.to_string(), b.to_string()))
-		    },
-		    (Var(c), b) => {
-			String::from(format!("{} - ({})", c, b.to_string()))
-		    },
-		    (a, b) => {
-			String::from(format!("({}) - ({})", a.to_string(), b.to_string()))
-		    }
-		}
-            },
-            Mul(ref l, ref r) => {
-		let left = unpack(l);
-		let right = unpack(r);
-		match (left, right) {
-		    (Const(a), Const(b)) => {
-			String::from(format!("{} * {}", a.to_string(), b.to_string()))
-		    },
-		    (Const(a), Var(c)) => {
-			String::from(format!("{}{}", a.to_string(), c))
-		    },
-		    (Var(c), Const(b)) => {
-			String::from(format!("{}{}", b.to_string(), c))
-		    },
-		    (Const(a), b) => {
-			String::from(format!("{} * ({})", a.to_string(), b.to_string()))
-		    },
-		    (a, Const(b)) => {
-			String::from(format!("({}) * {}", a.to_string(), b.to_string()))
-		    },
-		    (a, b) => {
-			String::from(format!("({}) * ({})", a.to_string(), b.to_string()))
-		    }
-		}
-            },
-            Div(ref l, ref r) => {
-		let left = unpack(l);
-		let right = unpack(r);
-		match (left, right) {
-		    (Const(a), Const(b)) => {
-			String::from(format!("{} / {}", a.to_string(), b.to_string()))
-		    },
-		    (Const(a), b) => {
-			String::from(format!("{} / {}", a.to_string(), b.to_string()))
-		    },
-		    (a, Const(b)) => {
-			String::from(format!("({}) / {}", a.to_string(), b.to_string()))
-		    },
-		    (a, b) => {
-			String::from(format!("({}) / ({})", a.to_string(), b.to_string()))
-		    }
-		}
-            },
-            Pow(ref l, ref r) => {
-		let left = unpack(l);
-		let right = unpack(r);
-		match (left, right) {
-		    (Const(a), Const(b)) => {
-			String::from(format!("{}^{}", a.to_string(), b.to_string()))
-		    },
-		    (Const(a), b) => {
-			String::from(format!("{}^({})", a.to_string(), b.to_string()))
-		    },
-		    (a, Const(b)) => {
-			String::from(format!("{}^{}", a.to_string(), b.to_string()))
-		    },
-		    (a, b) => {
-			String::from(format!("({})^({})", a.to_string(), b.to_string()))
-		    }
-		}
-            },
-	    Factorial(ref i) => String::from(format!("({})!", i.to_string())), 
-	    Gamma(ref i) => String::from(format!("Γ({})", i.to_string())),
+    pub fn map_children<F: FnMut(&Expr) -> Expr>(&self, mut f: F) -> Expr {
+	let mut g = |i: &E| pack(f(i));
+	match self {
+	    Const(_) | Named(_) | Var(_) => self.clone(),
+	    Neg(ref i)       => Neg(g(i)),
+	    Abs(ref i)       => Abs(g(i)),
+	    Sin(ref i)       => Sin(g(i)),
+	    Cos(ref i)       => Cos(g(i)),
+	    Sinh(ref i)      => Sinh(g(i)),
+	    Cosh(ref i)      => Cosh(g(i)),
+	    Asin(ref i)      => Asin(g(i)),
+	    Acos(ref i)      => Acos(g(i)),
+	    Asinh(ref i)     => Asinh(g(i)),
+	    Acosh(ref i)     => Acosh(g(i)),
+	    Ln(ref i)        => Ln(g(i)),
+	    Exp(ref i)       => Exp(g(i)),
+	    Factorial(ref i) => Factorial(g(i)),
+	    Gamma(ref i)     => Gamma(g(i)),
+	    LnGamma(ref i)   => LnGamma(g(i)),
+	    Digamma(ref i)   => Digamma(g(i)),
+	    Add(ref l, ref r)       => { let l = g(l); Add(l, g(r)) },
+	    Sub(ref l, ref r)       => { let l = g(l); Sub(l, g(r)) },
+	    Mul(ref l, ref r)       => { let l = g(l); Mul(l, g(r)) },
+	    Div(ref l, ref r)       => { let l = g(l); Div(l, g(r)) },
+	    Pow(ref l, ref r)       => { let l = g(l); Pow(l, g(r)) },
+	    Polygamma(ref l, ref r) => { let l = g(l); Polygamma(l, g(r)) },
+	    Beta(ref l, ref r)      => { let l = g(l); Beta(l, g(r)) },
 	}
     }
 
@@ -394,20 +307,25 @@ impl Neg for Expr {
 
 // con = constant value
 // var = variable
+// vari = indexed variable, ie x_1
 // varf = variable that is multiplied by a number
 // pow = power function to raise an Expr by an Expr
 // powf = power function except raise Expr by a float
 
-pub fn zero()       -> Expr { Const(real(0.0)) }
-pub fn one()        -> Expr { Const(real(1.0)) }
-pub fn two()        -> Expr { Const(real(2.0)) }
-pub fn pi()         -> Expr { Const(real(3.14159265359)) }
-pub fn e()          -> Expr { Const(real(2.71828182845)) }
+pub fn zero()       -> Expr { Const(integer(0)) }
+pub fn one()        -> Expr { Const(integer(1)) }
+pub fn two()        -> Expr { Const(integer(2)) }
+pub fn pi()         -> Expr { Named(Constant::Pi) }
+pub fn e()          -> Expr { Named(Constant::E) }
+pub fn imag_unit()  -> Expr { Named(Constant::ImaginaryUnit) }
+pub fn infinity()   -> Expr { Named(Constant::Infinity) }
 pub fn con(v: f64)  -> Expr { Const(real(v)) }
-pub fn var(c: char) -> Expr { Var(c) }
+pub fn var<S: Into<Symbol>>(s: S) -> Expr { Var(s.into()) }
+pub fn vari(base: &str, index: usize) -> Expr { Var(Symbol::indexed(base, index)) }
 pub fn neg(e: Expr) -> Expr { Neg(pack(e)) }
+pub fn abs(e: Expr) -> Expr { Abs(pack(e)) }
 pub fn exp(e: Expr) -> Expr { Exp(pack(e)) }
-pub fn varf(c: char, v: f64) -> Expr { mul(con(v),  var(c)) }
+pub fn varf<S: Into<Symbol>>(s: S, v: f64) -> Expr { mul(con(v), var(s)) }
 pub fn add(l: Expr, r: Expr) -> Expr { Add(pack(l), pack(r)) }
 pub fn sub(l: Expr, r: Expr) -> Expr { Sub(pack(l), pack(r)) }
 pub fn mul(l: Expr, r: Expr) -> Expr { Mul(pack(l), pack(r)) }
@@ -429,15 +347,14 @@ pub fn tan(e: Expr)    -> Expr {
     div(sin(e.clone()), cos(e.clone()))
 }
 pub fn atan(e: Expr) -> Expr {
-    div(asin(e.clone()), acos(e.clone()))
+    asin(div(e.clone(), sqrt(add(con(1.0), square(e)))))
 }
 pub fn tanh(e: Expr) -> Expr {
     div(sinh(e.clone()), cosh(e.clone()))
 }
 pub fn atanh(e: Expr) -> Expr {
-    // alternate form is 0.5*log(x+1) - 0.5*log(1-x)
-    sub(mul(con(0.5), log2(add(e.clone(), con(1.0)))),
-	mul(con(0.5), log2(sub(con(1.0), e.clone()))))
+    // alternate form is 0.5*ln(1+x) - 0.5*ln(1-x)
+    mul(con(0.5), ln(div(add(con(1.0), e.clone()), sub(con(1.0), e))))
 }
 pub fn ln(e: Expr)     -> Expr { Ln(pack(e)) }
 pub fn log(base: f64, e: Expr) -> Expr {
@@ -460,6 +377,23 @@ pub fn gamma(e: Expr) -> Expr {
     Gamma(pack(e))
 }
 
+pub fn ln_gamma(e: Expr) -> Expr {
+    LnGamma(pack(e))
+}
+
+pub fn digamma(e: Expr) -> Expr {
+    Digamma(pack(e))
+}
+
+// polygamma of order n, where n should be a whole constant
+pub fn polygamma(n: Expr, e: Expr) -> Expr {
+    Polygamma(pack(n), pack(e))
+}
+
+pub fn beta(a: Expr, b: Expr) -> Expr {
+    Beta(pack(a), pack(b))
+}
+
 // unit tests and other such things
 #[cfg(test)]
 mod test {
@@ -470,9 +404,9 @@ mod test {
         let expr  = varf('x', 3.0);
         let expr2 = varf('y', 3.0);
         let expr3 = con(3.0);
-        assert_eq!(true,  expr.has_var('x')); 
-        assert_eq!(false, expr2.has_var('x'));
-        assert_eq!(false, expr3.has_var('x'));
+        assert!(expr.has_var('x'));
+        assert!(!expr2.has_var('x'));
+        assert!(!expr3.has_var('x'));
     }
 
     #[test]
@@ -484,6 +418,22 @@ mod test {
 	let s1 = sin(var('x'));
 	let s2 = sin(var('x'));
 	assert_eq!(s1, s2);
+	// the small constants are exact, not floats
+	for c in [zero(), one(), two()].iter() {
+	    assert!(matches!(c, Const(n) if n.is_exact()));
+	}
+    }
+
+    #[test]
+    fn symbol_test() {
+	let f = sinh(var("theta")) * vari("x", 1);
+	assert!(f.has_var("theta"));
+	assert!(f.has_var(Symbol::indexed("x", 1)));
+	assert!(!f.has_var('x'));
+
+	let g = f.substitute("theta", "omega");
+	assert!(g.has_var("omega"));
+	assert!(!g.has_var("theta"));
     }
 }
 
@@ -0,0 +1,299 @@
+// src/expr/latex.rs
+
+use crate::expr::expr::*;
+use crate::expr::expr::Expr::*;
+use crate::expr::constant::*;
+use crate::number::number::*;
+
+
+// Greek letters which get their own LaTeX command when used
+// as a variable name
+const GREEK: [&str; 24] = [
+    "alpha", "beta", "gamma", "delta", "epsilon", "zeta",
+    "eta", "theta", "iota", "kappa", "lambda", "mu",
+    "nu", "xi", "omicron", "rho", "sigma", "tau",
+    "upsilon", "phi", "chi", "psi", "omega", "Omega",
+];
+
+impl Expr {
+    /// Render an expression as LaTeX math, using `\frac` for
+    /// quotients, `\sqrt` for roots and only the brackets the
+    /// precedence of each operation needs.
+    ///
+    /// ```
+    /// use mathy::expr::prelude::*;
+    /// let f = Expr::parse("sin(x)^2 / (x + 1)").unwrap();
+    /// assert_eq!(f.to_latex(), "\\frac{\\sin^{2}\\left(x\\right)}{x + 1}");
+    /// ```
+    pub fn to_latex(&self) -> String {
+	match self {
+	    Const(n) => n.to_latex(),
+	    Named(c) => c.to_latex().to_string(),
+	    Var(s) => {
+		let base = latex_name(s.base());
+		match s.index() {
+		    Some(i) => format!("{}_{{{}}}", base, i),
+		    None => base,
+		}
+	    },
+	    Neg(ref i) => {
+		// -(-x) and -(x + y) need brackets, -2x doesn't
+		if i.precedence() < 2 || i.precedence() == 3 {
+		    format!("-{}", bracket(i))
+		} else {
+		    format!("-{}", i.to_latex())
+		}
+	    },
+	    Add(ref l, ref r) => {
+		match r.as_ref() {
+		    Neg(ref i) => format!("{} - {}", l.to_latex(), right_operand(i, 2)),
+		    r => format!("{} + {}", l.to_latex(), right_operand(r, 2)),
+		}
+	    },
+	    Sub(ref l, ref r) => format!("{} - {}", l.to_latex(), right_operand(r, 2)),
+	    Mul(ref l, ref r) => {
+		let left = wrap(l, 2);
+		let right = right_operand(r, 2);
+		// numbers next to each other need a dot to stay apart,
+		// and 2 \frac{1}{3} would read as a mixed number
+		let number = matches!(l.as_ref(), Const(_));
+		if right.starts_with(|c: char| c.is_ascii_digit()) || (number && right.starts_with("\\frac")) {
+		    format!("{} \\cdot {}", left, right)
+		} else {
+		    format!("{} {}", left, right)
+		}
+	    },
+	    Div(ref l, ref r) => format!("\\frac{{{}}}{{{}}}", l.to_latex(), r.to_latex()),
+	    Pow(ref b, ref p) => {
+		match p.as_ref() {
+		    Const(n) if n.real_eq(0.5) => format!("\\sqrt{{{}}}", b.to_latex()),
+		    p if root_index(p).is_some() => {
+			format!("\\sqrt[{}]{{{}}}", root_index(p).unwrap(), b.to_latex())
+		    },
+		    // sin(x)^2 is written as sin^2(x), but the inverse
+		    // functions already have a superscript so they're
+		    // bracketed instead
+		    p if inverse_function(b) => format!("{}^{{{}}}", bracket(b), p.to_latex()),
+		    Const(n) if n.is_whole() && n.real() > 0.0 && plain_function(b).is_some() => {
+			let (name, arg) = plain_function(b).unwrap();
+			format!("{}^{{{}}}{}", name, n.to_latex(), arg)
+		    },
+		    p => format!("{}^{{{}}}", wrap(b, 6), p.to_latex()),
+		}
+	    },
+	    Exp(ref i) => format!("e^{{{}}}", i.to_latex()),
+	    Abs(ref i) => format!("\\left|{}\\right|", i.to_latex()),
+	    Factorial(ref i) => format!("{}!", wrap(i, 6)),
+	    Polygamma(ref n, ref i) => format!("\\psi^{{({})}}{}", n.to_latex(), bracket(i)),
+	    Beta(ref a, ref b) => {
+		format!("\\mathrm{{B}}\\left({}, {}\\right)", a.to_latex(), b.to_latex())
+	    },
+	    _ => {
+		let (name, arg) = plain_function(self).unwrap();
+		format!("{}{}", name, arg)
+	    },
+	}
+    }
+}
+
+impl Number {
+    /// Render a number as LaTeX, with fractions as `\frac` and
+    /// complex numbers as `a + bi`.
+    ///
+    /// ```
+    /// use mathy::number::number::*;
+    /// assert_eq!(rational(-1, 3).to_latex(), "-\\frac{1}{3}");
+    /// assert_eq!(complex(2.0, -1.5).to_latex(), "2 - 1.5i");
+    /// ```
+    pub fn to_latex(&self) -> String {
+	match self {
+	    Number::NaN => "\\mathrm{NaN}".to_string(),
+	    Number::Integer(n) => n.to_string(),
+	    Number::Rational(q) => {
+		let sign = if q.is_negative() { "-" } else { "" };
+		format!("{}\\frac{{{}}}{{{}}}", sign, q.numer().abs(), q.denom())
+	    },
+	    Number::Real(x) => real_latex(*x),
+	    Number::Complex(x, z) => {
+		let imag = if z.abs() == 1.0 {
+		    "i".to_string()
+		} else {
+		    format!("{}i", real_latex(z.abs()))
+		};
+		let sign = if *z < 0.0 { "-" } else { "+" };
+		if *x == 0.0 {
+		    format!("{}{}", if *z < 0.0 { "-" } else { "" }, imag)
+		} else {
+		    format!("{} {} {}", real_latex(*x), sign, imag)
+		}
+	    },
+	}
+    }
+}
+
+impl Constant {
+    /// The LaTeX for a named constant, ie `\pi`.
+    pub fn to_latex(&self) -> &'static str {
+	match self {
+	    Constant::Pi => "\\pi",
+	    Constant::E => "e",
+	    Constant::ImaginaryUnit => "i",
+	    Constant::Infinity => "\\infty",
+	}
+    }
+}
+
+fn real_latex(x: f64) -> String {
+    match x {
+	x if x == f64::INFINITY => "\\infty".to_string(),
+	x if x == f64::NEG_INFINITY => "-\\infty".to_string(),
+	x => x.to_string(),
+    }
+}
+
+// Variable names, with greek letters as commands and any other
+// name longer than a letter kept upright
+fn latex_name(name: &str) -> String {
+    if GREEK.contains(&name) {
+	format!("\\{}", name)
+    } else if name.chars().count() > 1 {
+	format!("\\mathrm{{{}}}", name)
+    } else {
+	name.to_string()
+    }
+}
+
+fn bracket(e: &Expr) -> String {
+    format!("\\left({}\\right)", e.to_latex())
+}
+
+// Bracket an expression which binds looser than the given precedence
+fn wrap(e: &Expr, precedence: u8) -> String {
+    if e.precedence() < precedence {
+	bracket(e)
+    } else {
+	e.to_latex()
+    }
+}
+
+// Bracket the right side of a sum or product like wrap, and also
+// anything starting with a minus sign, so x + -2 reads x + (-2)
+fn right_operand(e: &Expr, precedence: u8) -> String {
+    let s = wrap(e, precedence);
+    if e.precedence() == 3 || s.starts_with('-') {
+	bracket(e)
+    } else {
+	s
+    }
+}
+
+// The index n of a power 1/n which is written as a root
+fn root_index(p: &Expr) -> Option<Number> {
+    let n = match p {
+	Const(k) if k.is_exact() => k.reciprocal(),
+	Div(ref a, ref b) => {
+	    match (a.as_ref(), b.as_ref()) {
+		(Const(a), Const(b)) if a.real_eq(1.0) => b.clone(),
+		_ => return None,
+	    }
+	},
+	_ => return None,
+    };
+    if n.is_whole() && n.real() > 1.0 { Some(n) } else { None }
+}
+
+// The command and bracketed argument of a single argument function
+// which can take a superscript between the two
+fn plain_function(e: &Expr) -> Option<(&'static str, String)> {
+    let name = match e {
+	Sin(_) => "\\sin",
+	Cos(_) => "\\cos",
+	Sinh(_) => "\\sinh",
+	Cosh(_) => "\\cosh",
+	Asin(_) => "\\sin^{-1}",
+	Acos(_) => "\\cos^{-1}",
+	Asinh(_) => "\\sinh^{-1}",
+	Acosh(_) => "\\cosh^{-1}",
+	Ln(_) => "\\ln",
+	Gamma(_) => "\\Gamma",
+	LnGamma(_) => "\\ln\\Gamma",
+	Digamma(_) => "\\psi",
+	_ => return None,
+    };
+    Some((name, bracket(e.children()[0])))
+}
+
+
+// Functions whose LaTeX name already carries a superscript
+fn inverse_function(e: &Expr) -> bool {
+    matches!(e, Asin(_) | Acos(_) | Asinh(_) | Acosh(_))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_latex_corpus() {
+	let corpus = [
+	    ("x + 1", "x + 1"),
+	    ("x - (y - 1)", "x - \\left(y - 1\\right)"),
+	    ("2x", "2 x"),
+	    ("x * 2", "x \\cdot 2"),
+	    ("(x + 1) * (x - 1)", "\\left(x + 1\\right) \\left(x - 1\\right)"),
+	    ("x / (y + 1)", "\\frac{x}{y + 1}"),
+	    ("x^2", "x^{2}"),
+	    ("(x + 1)^(n - 1)", "\\left(x + 1\\right)^{n - 1}"),
+	    ("sqrt(x + 1)", "\\sqrt{x + 1}"),
+	    ("x^(1/3)", "\\sqrt[3]{x}"),
+	    ("sin(x)^2 + cos(x)^2", "\\sin^{2}\\left(x\\right) + \\cos^{2}\\left(x\\right)"),
+	    ("acosh(x)", "\\cosh^{-1}\\left(x\\right)"),
+	    ("gamma(x) * lngamma(x)", "\\Gamma\\left(x\\right) \\ln\\Gamma\\left(x\\right)"),
+	    ("polygamma(1, x)", "\\psi^{(1)}\\left(x\\right)"),
+	    ("e^(i * pi)", "e^{i \\pi}"),
+	    ("(n + 1)!", "\\left(n + 1\\right)!"),
+	    ("|theta - x_1|", "\\left|\\theta - x_{1}\\right|"),
+	    ("-(x + y)", "-\\left(x + y\\right)"),
+	    ("0.25 * x", "\\frac{1}{4} x"),
+	    ("ln(inf)", "\\ln\\left(\\infty\\right)"),
+	    ("x + -2", "x + \\left(-2\\right)"),
+	    ("x - -2", "x - \\left(-2\\right)"),
+	    ("x - -0.5", "x - \\left(-\\frac{1}{2}\\right)"),
+	    ("x - -2i", "x - \\left(-2 i\\right)"),
+	    ("2 * -x", "2 \\left(-x\\right)"),
+	    ("asin(x)^2", "\\left(\\sin^{-1}\\left(x\\right)\\right)^{2}"),
+	    ("acos(x)^2", "\\left(\\cos^{-1}\\left(x\\right)\\right)^{2}"),
+	    ("asinh(x)^3", "\\left(\\sinh^{-1}\\left(x\\right)\\right)^{3}"),
+	    ("acosh(x)^n", "\\left(\\cosh^{-1}\\left(x\\right)\\right)^{n}"),
+	];
+	for (input, expected) in corpus.iter() {
+	    let e = Expr::parse(input).unwrap();
+	    assert_eq!(e.to_latex(), *expected, "rendering {}", input);
+	}
+    }
+
+    #[test]
+    fn test_number_latex() {
+	assert_eq!(integer(-12).to_latex(), "-12");
+	assert_eq!(rational(3, 4).to_latex(), "\\frac{3}{4}");
+	assert_eq!(real(-2.5).to_latex(), "-2.5");
+	assert_eq!(real(f64::NEG_INFINITY).to_latex(), "-\\infty");
+	assert_eq!(i().to_latex(), "i");
+	assert_eq!(imag(-2.0).to_latex(), "-2i");
+	assert_eq!(complex(1.0, 1.0).to_latex(), "1 + i");
+	assert_eq!(nan().to_latex(), "\\mathrm{NaN}");
+
+	// complex constants are bracketed inside a product
+	assert_eq!(mul(Const(complex(1.0, 2.0)), var('z')).to_latex(), "\\left(1 + 2i\\right) z");
+
+	// built directly, since the parser folds these
+	assert_eq!(add(var('x'), neg(neg(var('y')))).to_latex(), "x - \\left(-y\\right)");
+	assert_eq!(sub(var('x'), Const(real(-0.5))).to_latex(), "x - \\left(-0.5\\right)");
+	assert_eq!(sub(var('x'), Const(imag(-2.0))).to_latex(), "x - \\left(-2i\\right)");
+	assert_eq!(mul(con(2.0), Const(rational(1, 3))).to_latex(), "2 \\cdot \\frac{1}{3}");
+	assert_eq!(mul(con(2.0), Const(rational(-1, 3))).to_latex(), "2 \\left(-\\frac{1}{3}\\right)");
+	assert_eq!(mul(var('x'), Const(rational(1, 3))).to_latex(), "x \\frac{1}{3}");
+    }
+}
+
+// end src/expr/latex.rs
@@ -1,4 +1,19 @@
+#[allow(clippy::module_inception)]
 pub mod expr;
+pub mod visit;
+pub mod symbol;
+pub mod constant;
 pub mod simplify;
+pub mod canonical;
+pub mod rewrite;
+pub mod algebra;
 pub mod evaluator;
+pub mod parser;
+pub mod latex;
+pub mod display;
+pub mod pretty;
+pub mod compile;
+pub mod batch;
+pub mod dag;
+pub mod cse;
 pub mod prelude;
@@ -0,0 +1,615 @@
+// src/expr/parser.rs
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::expr::expr::*;
+use crate::number::number::*;
+use crate::number::rational::*;
+
+/// Error returned when a string can't be turned into an `Expr`.
+/// The `position` is the character offset (not byte offset) into
+/// the input where the parser gave up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(position: usize, message: String) -> ParseError {
+        ParseError { position, message }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(Number),
+    Imag(f64),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    Bang,
+    Pipe,
+    LParen,
+    RParen,
+    Comma,
+    End,
+}
+
+use self::Token::*;
+
+impl Token {
+    fn describe(&self) -> String {
+	match self {
+	    Num(n) => format!("number '{}'", n),
+	    Imag(n) => format!("number '{}j'", n),
+	    Ident(s) => format!("'{}'", s),
+	    Plus => "'+'".into(),
+	    Minus => "'-'".into(),
+	    Star => "'*'".into(),
+	    Slash => "'/'".into(),
+	    Caret => "'^'".into(),
+	    Bang => "'!'".into(),
+	    Pipe => "'|'".into(),
+	    LParen => "'('".into(),
+	    RParen => "')'".into(),
+	    Comma => "','".into(),
+	    End => "end of input".into(),
+	}
+    }
+}
+
+/// Names which map onto a function call. Any other identifier
+/// is read as a variable name.
+const FUNCTIONS: [&str; 30] = [
+    "sin", "cos", "tan", "sinh", "cosh", "tanh",
+    "asin", "acos", "atan", "asinh", "acosh", "atanh",
+    "ln", "log", "log2", "log10", "exp", "sqrt",
+    "gamma", "Γ", "factorial", "square", "cube",
+    "lngamma", "lnΓ", "digamma", "ψ", "polygamma",
+    "beta", "abs",
+];
+
+
+// Break the input into (position, token) pairs
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+	let c = chars[i];
+	let start = i;
+
+	if c.is_whitespace() {
+	    i += 1;
+	    continue;
+	}
+
+	if c.is_ascii_digit() || (c == '.' && i + 1 < chars.len() && chars[i+1].is_ascii_digit()) {
+	    while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+		i += 1;
+	    }
+
+	    // scientific notation, only when an exponent actually follows
+	    // so that `2e` is still read as 2 * e
+	    if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+		let mut j = i + 1;
+		if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+		    j += 1;
+		}
+		if j < chars.len() && chars[j].is_ascii_digit() {
+		    i = j;
+		    while i < chars.len() && chars[i].is_ascii_digit() {
+			i += 1;
+		    }
+		}
+	    }
+
+	    let text: String = chars[start..i].iter().collect();
+	    let value = text.parse::<f64>().map_err(|_| {
+		ParseError::new(start, format!("invalid number '{}'", text))
+	    })?;
+
+	    // a trailing `j` marks an imaginary literal, ie 2j
+	    if i < chars.len() && chars[i] == 'j'
+		&& !(i + 1 < chars.len() && chars[i+1].is_alphanumeric()) {
+		i += 1;
+		tokens.push((start, Imag(value)));
+	    } else {
+		// literals are exact, so 0.1 is the fraction 1/10, unless
+		// the exponent is too large to hold that way
+		let exact = BigRational::from_decimal(&text).map(Number::from);
+		tokens.push((start, Num(exact.unwrap_or_else(|| real(value)))));
+	    }
+	    continue;
+	}
+
+	// identifiers start with a letter and may carry digits and
+	// underscores afterwards, ie theta, v0 or x_1. A leading `?`
+	// marks a wildcard for rewrite rule patterns, ie ?a
+	let wildcard = c == '?' && i + 1 < chars.len() && chars[i+1].is_alphabetic();
+	if c.is_alphabetic() || wildcard {
+	    i += 1;
+	    while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+		i += 1;
+	    }
+	    let word: String = chars[start..i].iter().collect();
+	    tokens.push((start, Ident(word)));
+	    continue;
+	}
+
+	let tok = match c {
+	    '+' => Plus,
+	    '-' => Minus,
+	    '*' => Star,
+	    '/' => Slash,
+	    '^' => Caret,
+	    // the only name which isn't made of letters
+	    '∞' => Ident("∞".to_string()),
+	    '!' => Bang,
+	    '|' => Pipe,
+	    '(' => LParen,
+	    ')' => RParen,
+	    ',' => Comma,
+	    _ => return Err(ParseError::new(i, format!("unexpected character '{}'", c))),
+	};
+	tokens.push((start, tok));
+	i += 1;
+    }
+
+    tokens.push((chars.len(), End));
+    Ok(tokens)
+}
+
+
+// A real number plus or minus an imaginary one, ie 1+2j, is
+// a single complex constant, which is how complex numbers print
+fn complex_literal(left: &Expr, right: &Expr, sign: f64) -> Option<Expr> {
+    match (left, right) {
+	(Expr::Const(a), Expr::Const(Number::Complex(x, z))) if *x == 0.0 && !matches!(a, Number::Complex(_, _) | Number::NaN) => {
+	    Some(Expr::Const(complex(a.real(), sign * z)))
+	},
+	_ => None,
+    }
+}
+
+/// A recursive descent parser over the token stream.
+/// Precedence from loosest to tightest binding is:
+/// `+ -`, `* /` (and implicit multiplication), unary `-`,
+/// `^` (right associative), and postfix `!`.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+	&self.tokens[self.pos].1
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+	let idx = (self.pos + offset).min(self.tokens.len() - 1);
+	&self.tokens[idx].1
+    }
+
+    fn position(&self) -> usize {
+	self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+	let tok = self.tokens[self.pos].1.clone();
+	if self.pos < self.tokens.len() - 1 {
+	    self.pos += 1;
+	}
+	tok
+    }
+
+    fn unexpected(&self) -> ParseError {
+	ParseError::new(self.position(), format!("unexpected {}", self.peek().describe()))
+    }
+
+    fn expect(&mut self, tok: Token) -> Result<(), ParseError> {
+	if *self.peek() == tok {
+	    self.next();
+	    Ok(())
+	} else {
+	    Err(ParseError::new(
+		self.position(),
+		format!("expected {} but found {}", tok.describe(), self.peek().describe()),
+	    ))
+	}
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+	let mut left = self.parse_term()?;
+	loop {
+	    match self.peek() {
+		Plus => {
+		    self.next();
+		    let right = self.parse_term()?;
+		    left = complex_literal(&left, &right, 1.0).unwrap_or_else(|| add(left, right));
+		},
+		Minus => {
+		    self.next();
+		    let right = self.parse_term()?;
+		    left = complex_literal(&left, &right, -1.0).unwrap_or_else(|| sub(left, right));
+		},
+		_ => return Ok(left),
+	    }
+	}
+    }
+
+    fn parse_term(&mut self) -> Result<Expr, ParseError> {
+	let mut left = self.parse_unary()?;
+	loop {
+	    match self.peek() {
+		Star => {
+		    self.next();
+		    left = mul(left, self.parse_unary()?);
+		},
+		Slash => {
+		    self.next();
+		    let right = self.parse_unary()?;
+		    // a whole number over a whole number is an exact
+		    // fraction, so printed rationals like 5/2 come back
+		    // as the same constant
+		    left = match (&left, &right) {
+			(Expr::Const(a @ Number::Integer(_)), Expr::Const(b @ Number::Integer(_))) if !b.is_zero() => {
+			    Expr::Const(a / b)
+			},
+			_ => div(left, right),
+		    };
+		},
+		// implicit multiplication, ie 2x or (x+1)(x-1)
+		Num(_) | Imag(_) | Ident(_) | LParen => {
+		    left = mul(left, self.parse_power()?);
+		},
+		_ => return Ok(left),
+	    }
+	}
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+	match self.peek() {
+	    Minus => {
+		self.next();
+		// fold a bare negative literal into the constant itself
+		// so that printed constants like -2 come back unchanged
+		if !matches!(self.peek_at(1), Caret | Bang) {
+		    match self.peek().clone() {
+			Num(n) => {
+			    self.next();
+			    return Ok(Expr::Const(-n));
+			},
+			Imag(v) => {
+			    self.next();
+			    return Ok(Expr::Const(imag(-v)));
+			},
+			_ => {},
+		    }
+		}
+		Ok(neg(self.parse_unary()?))
+	    },
+	    Plus => {
+		self.next();
+		self.parse_unary()
+	    },
+	    _ => self.parse_power(),
+	}
+    }
+
+    fn parse_power(&mut self) -> Result<Expr, ParseError> {
+	// e^x is read as the exponential function rather than a power
+	if *self.peek() == Ident("e".into()) && *self.peek_at(1) == Caret {
+	    self.next();
+	    self.next();
+	    return Ok(exp(self.parse_unary()?));
+	}
+
+	let base = self.parse_postfix()?;
+	if *self.peek() == Caret {
+	    self.next();
+	    let power = self.parse_unary()?;
+	    Ok(pow(base, power))
+	} else {
+	    Ok(base)
+	}
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, ParseError> {
+	let mut e = self.parse_primary()?;
+	while *self.peek() == Bang {
+	    self.next();
+	    e = factorial(e);
+	}
+	Ok(e)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+	let start = self.position();
+	if matches!(self.peek(), End | RParen | Comma | Plus | Star | Slash | Caret | Bang) {
+	    return Err(self.unexpected());
+	}
+
+	match self.next() {
+	    Num(n) => Ok(Expr::Const(n)),
+	    Imag(n) => Ok(Expr::Const(imag(n))),
+	    LParen => {
+		let inner = self.parse_expr()?;
+		self.expect(RParen)?;
+		Ok(inner)
+	    },
+	    // absolute value bars, ie |x|
+	    Pipe => {
+		let inner = self.parse_expr()?;
+		self.expect(Pipe)?;
+		Ok(abs(inner))
+	    },
+	    Ident(name) => {
+		if FUNCTIONS.contains(&name.as_str()) {
+		    self.parse_call(&name, start)
+		} else {
+		    match name.as_str() {
+			"pi" | "π" => Ok(pi()),
+			"e" => Ok(e()),
+			"i" => Ok(imag_unit()),
+			"inf" | "∞" => Ok(infinity()),
+			"NaN" => Ok(Expr::Const(nan())),
+			_ => Ok(var(name)),
+		    }
+		}
+	    },
+	    _ => unreachable!(),
+	}
+    }
+
+    fn parse_call(&mut self, name: &str, start: usize) -> Result<Expr, ParseError> {
+	let mut args = if *self.peek() == Pipe {
+	    // allow ln|x| without the extra parentheses
+	    vec![self.parse_primary()?]
+	} else {
+	    self.expect(LParen)?;
+	    let mut args = vec![self.parse_expr()?];
+	    while *self.peek() == Comma {
+		self.next();
+		args.push(self.parse_expr()?);
+	    }
+	    self.expect(RParen)?;
+	    args
+	};
+
+	// functions taking two arguments
+	if args.len() == 2 {
+	    let y = args.pop().unwrap();
+	    let x = args.pop().unwrap();
+	    match name {
+		"log" => return Ok(div(ln(y), ln(x))),
+		"beta" => return Ok(beta(x, y)),
+		"polygamma" | "ψ" => return Ok(polygamma(x, y)),
+		_ => {},
+	    }
+	    args = vec![x, y];
+	}
+
+	if args.len() != 1 {
+	    return Err(ParseError::new(
+		start,
+		format!("'{}' takes 1 argument but {} were given", name, args.len()),
+	    ));
+	}
+
+	let arg = args.pop().unwrap();
+	let e = match name {
+	    "sin" => sin(arg),
+	    "cos" => cos(arg),
+	    "tan" => tan(arg),
+	    "sinh" => sinh(arg),
+	    "cosh" => cosh(arg),
+	    "tanh" => tanh(arg),
+	    "asin" => asin(arg),
+	    "acos" => acos(arg),
+	    "atan" => atan(arg),
+	    "asinh" => asinh(arg),
+	    "acosh" => acosh(arg),
+	    "atanh" => atanh(arg),
+	    "ln" => ln(arg),
+	    "log" | "log10" => log10(arg),
+	    "log2" => log2(arg),
+	    "exp" => exp(arg),
+	    "sqrt" => sqrt(arg),
+	    "square" => square(arg),
+	    "cube" => cube(arg),
+	    "gamma" | "Γ" => gamma(arg),
+	    "factorial" => factorial(arg),
+	    "lngamma" | "lnΓ" => ln_gamma(arg),
+	    "digamma" | "ψ" => digamma(arg),
+	    "abs" => abs(arg),
+	    _ => return Err(ParseError::new(start, format!("unknown function '{}'", name))),
+	};
+	Ok(e)
+    }
+}
+
+
+impl Expr {
+    /// Parse a string into an `Expr` tree. Supports the usual
+    /// arithmetic operators with precedence, unary minus, `^` for
+    /// powers, postfix `!`, implicit multiplication like `2x` and
+    /// every function that has a shortcut in `expr::expr`.
+    ///
+    /// ```
+    /// use mathy::expr::prelude::*;
+    /// let f = Expr::parse("3x^2 + sin(x)").unwrap();
+    /// assert_eq!(evaluate(f, 'x', real(0.0)), real(0.0));
+    /// ```
+    pub fn parse(input: &str) -> Result<Expr, ParseError> {
+	let tokens = tokenize(input)?;
+	let mut parser = Parser { tokens, pos: 0 };
+	let e = parser.parse_expr()?;
+	if *parser.peek() != End {
+	    return Err(parser.unexpected());
+	}
+	Ok(e)
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Expr, ParseError> {
+	Expr::parse(s)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::calc::deriver::*;
+    use crate::expr::evaluator::*;
+
+    #[test]
+    fn test_precedence() {
+	let e = Expr::parse("1 + 2 * x ^ 2").unwrap();
+	let expected = add(con(1.0), mul(con(2.0), powf(var('x'), 2.0)));
+	assert_eq!(e, expected);
+
+	// powers associate to the right
+	let p = Expr::parse("2^3^2").unwrap();
+	assert_eq!(p, pow(con(2.0), pow(con(3.0), con(2.0))));
+
+	let s = Expr::parse("x - 1 - y").unwrap();
+	assert_eq!(s, sub(sub(var('x'), con(1.0)), var('y')));
+    }
+
+    #[test]
+    fn test_unary_and_implicit() {
+	assert_eq!(Expr::parse("-x^2").unwrap(), neg(powf(var('x'), 2.0)));
+	assert_eq!(Expr::parse("-2x").unwrap(), mul(con(-2.0), var('x')));
+	assert_eq!(Expr::parse("2x").unwrap(), varf('x', 2.0));
+	assert_eq!(Expr::parse("x y").unwrap(), mul(var('x'), var('y')));
+	assert_eq!(Expr::parse("xy").unwrap(), var("xy"));
+	assert_eq!(
+	    Expr::parse("(x+1)(x-1)").unwrap(),
+	    mul(add(var('x'), con(1.0)), sub(var('x'), con(1.0)))
+	);
+    }
+
+    #[test]
+    fn test_functions() {
+	let e: Expr = "3*x^2 + sin(x)/ln(x)".parse().unwrap();
+	let expected = add(
+	    mul(con(3.0), powf(var('x'), 2.0)),
+	    div(sin(var('x')), ln(var('x'))),
+	);
+	assert_eq!(e, expected);
+
+	assert_eq!(Expr::parse("e^(2x)").unwrap(), exp(varf('x', 2.0)));
+	assert_eq!(Expr::parse("Γ(x)").unwrap(), gamma(var('x')));
+	assert_eq!(Expr::parse("x!").unwrap(), factorial(var('x')));
+	assert_eq!(Expr::parse("acosh(x)").unwrap(), acosh(var('x')));
+	assert_eq!(Expr::parse("beta(x, 2)").unwrap(), beta(var('x'), con(2.0)));
+	assert_eq!(Expr::parse("ψ(1, x)").unwrap(), polygamma(con(1.0), var('x')));
+	assert_eq!(Expr::parse("lnΓ(x)").unwrap(), ln_gamma(var('x')));
+	assert_eq!(Expr::parse("ln|x - 1|").unwrap(), ln(abs(sub(var('x'), con(1.0)))));
+	assert_eq!(Expr::parse("abs(x)").unwrap(), abs(var('x')));
+    }
+
+    #[test]
+    fn test_symbols() {
+	let e = Expr::parse("omega * theta + v0 - x_1").unwrap();
+	let expected = sub(
+	    add(mul(var("omega"), var("theta")), var("v0")),
+	    vari("x", 1),
+	);
+	assert_eq!(e, expected);
+	assert_eq!(Expr::parse("2theta").unwrap(), varf("theta", 2.0));
+	assert_eq!(Expr::parse("sin(?a)^2").unwrap(), powf(sin(var("?a")), 2.0));
+	assert!(Expr::parse("x ? y").is_err());
+	assert_eq!(Expr::parse("∞").unwrap(), infinity());
+	assert_eq!(Expr::parse("-2∞").unwrap(), mul(Expr::Const(integer(-2)), infinity()));
+	assert_eq!(Expr::parse(&infinity().styled(crate::expr::display::PrintStyle::Unicode).to_string()).unwrap(), infinity());
+    }
+
+    #[test]
+    fn test_errors() {
+	let err = Expr::parse("2 + * x").unwrap_err();
+	assert_eq!(err.position, 4);
+
+	let err = Expr::parse("sin(x").unwrap_err();
+	assert_eq!(err.position, 5);
+
+	let err = Expr::parse("x $ 2").unwrap_err();
+	assert_eq!(err.position, 2);
+
+	assert!(Expr::parse("sin(x, y)").is_err());
+	assert!(Expr::parse("").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+	let exprs = [
+	    derive(exp(powf(var('x'), 2.0)), 'x'),
+	    derive(tan(var('x')), 'x'),
+	    add(con(1.0), div(con(1.0), add(var('x'), con(1.0)))),
+	    powf(add(var('x'), con(1.0)), 2.0),
+	    factorial(sub(var('x'), con(2.5))),
+	    sin(mul(var("omega"), var("t"))),
+	    add(beta(var('a'), var('b')), digamma(var('x'))),
+	    neg(mul(var('x'), var('y'))),
+	    pow(pow(var('x'), var('y')), var('z')),
+	    div(var('x'), mul(var('y'), var('z'))),
+	    sub(var('x'), neg(var('y'))),
+	    mul(var('x'), con(2.0)),
+	    pow(e(), var('x')),
+	];
+
+	for e in exprs.iter() {
+	    let printed = e.to_string();
+	    let parsed = Expr::parse(&printed).unwrap();
+	    assert_eq!(&parsed, e, "round trip failed for {}", printed);
+	}
+	// decimals parse to fractions, which have to print back as
+	// the same tree rather than only an equal value
+	let sources = ["x - 2.5", "0.1x + 1e-3", "x^0.75", "-0.5 + x", "2 * 0.75", "x / 0.5", "sin(1.25x)", "1/2/3"];
+	for s in sources.iter() {
+	    let e = Expr::parse(s).unwrap();
+	    let parsed = Expr::parse(&e.to_string()).unwrap();
+	    assert_eq!(format!("{:?}", parsed), format!("{:?}", e), "round trip failed for {}", e);
+	}
+	assert_eq!(Expr::parse("5/2").unwrap(), Expr::Const(rational(5, 2)));
+	assert_eq!(Expr::parse("1/0").unwrap(), div(one(), zero()));
+    }
+
+    #[test]
+    fn test_evaluate_parsed() {
+	let f = Expr::parse("sqrt(x) + 2^x").unwrap();
+	assert_eq!(evaluate(f, 'x', real(4.0)), real(18.0));
+
+	// atan and atanh are written in terms of the other functions
+	let close = |s: &str, v: f64| {
+	    let e = Expr::parse(s).unwrap();
+	    assert!((try_evaluate(&e, &Env::new()).unwrap().real() - v).abs() < 1e-12, "{}", s);
+	};
+	close("atan(1)", std::f64::consts::FRAC_PI_4);
+	close("atan(0.5)", 0.5f64.atan());
+	close("atan(-3)", (-3f64).atan());
+	close("atanh(0.5)", 0.5f64.atanh());
+	close("atanh(-0.25)", (-0.25f64).atanh());
+    }
+}
+
+// end src/expr/parser.rs
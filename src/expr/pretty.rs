@@ -0,0 +1,377 @@
+// src/expr/pretty.rs
+
+use crate::expr::expr::*;
+use crate::expr::expr::Expr::*;
+use crate::expr::constant::*;
+use crate::expr::symbol::*;
+use crate::expr::display::*;
+use crate::number::number::*;
+
+
+// A rectangle of text with every line padded to the same width.
+// The baseline is the row that lines up with its neighbours, like
+// the bar of a fraction or the only row of plain text.
+#[derive(Clone)]
+struct Block {
+    lines: Vec<String>,
+    baseline: usize,
+}
+
+fn width_of(s: &str) -> usize {
+    s.chars().count()
+}
+
+fn pad(s: &str, width: usize) -> String {
+    format!("{}{}", s, " ".repeat(width.saturating_sub(width_of(s))))
+}
+
+fn center(s: &str, width: usize) -> String {
+    let left = width.saturating_sub(width_of(s)) / 2;
+    pad(&format!("{}{}", " ".repeat(left), s), width)
+}
+
+impl Block {
+    fn text(s: &str) -> Block {
+	Block { lines: vec![s.to_string()], baseline: 0 }
+    }
+
+    fn width(&self) -> usize {
+	self.lines.first().map_or(0, |l| width_of(l))
+    }
+
+    fn height(&self) -> usize {
+	self.lines.len()
+    }
+
+    // Put another block to the right, lining up the baselines
+    fn beside(self, other: Block) -> Block {
+	let above = self.baseline.max(other.baseline);
+	let below = (self.height() - self.baseline).max(other.height() - other.baseline);
+	let rows = |b: &Block| {
+	    let top = above - b.baseline;
+	    (0..above + below).map(move |i| {
+		match i.checked_sub(top).and_then(|j| b.lines.get(j)) {
+		    Some(l) => l.clone(),
+		    None => " ".repeat(b.width()),
+		}
+	    }).collect::<Vec<String>>()
+	};
+	let lines = rows(&self).iter().zip(rows(&other).iter())
+	    .map(|(l, r)| format!("{}{}", l, r))
+	    .collect();
+	Block { lines, baseline: above }
+    }
+
+    // Stack blocks on top of each other, centred, keeping the
+    // baseline of the block at index `base`
+    fn stack(blocks: Vec<Block>, base: usize) -> Block {
+	let width = blocks.iter().map(|b| b.width()).max().unwrap_or(0);
+	let baseline = blocks[..base].iter().map(|b| b.height()).sum::<usize>() + blocks[base].baseline;
+	let lines = blocks.iter().flat_map(|b| b.lines.iter().map(|l| center(l, width))).collect();
+	Block { lines, baseline }
+    }
+
+    fn row(blocks: Vec<Block>) -> Block {
+	blocks.into_iter().reduce(|a, b| a.beside(b)).unwrap_or_else(|| Block::text(""))
+    }
+
+    // A column of characters the height of this block, ie a bracket
+    fn column(&self, top: &str, middle: &str, bottom: &str, single: &str) -> Block {
+	let h = self.height();
+	let lines = (0..h).map(|i| {
+	    match i {
+		_ if h == 1 => single,
+		0 => top,
+		i if i == h - 1 => bottom,
+		_ => middle,
+	    }.to_string()
+	}).collect();
+	Block { lines, baseline: self.baseline }
+    }
+}
+
+fn text(s: &str) -> Block {
+    Block::text(s)
+}
+
+fn parens(b: Block, style: PrintStyle) -> Block {
+    let (left, right) = match style {
+	PrintStyle::Unicode => (b.column("⎛", "⎜", "⎝", "("), b.column("⎞", "⎟", "⎠", ")")),
+	PrintStyle::Ascii => (b.column("/", "|", "\\", "("), b.column("\\", "|", "/", ")")),
+    };
+    Block::row(vec![left, b, right])
+}
+
+fn fraction(num: Block, den: Block, style: PrintStyle) -> Block {
+    let bar = if style == PrintStyle::Unicode { "─" } else { "-" };
+    let width = num.width().max(den.width());
+    Block::stack(vec![num, text(&bar.repeat(width)), den], 1)
+}
+
+// Raise an exponent to sit above and to the right of its base
+fn power(base: Block, exponent: Block) -> Block {
+    let (bw, ew) = (base.width(), exponent.width());
+    let mut lines: Vec<String> = exponent.lines.iter()
+	.map(|l| format!("{}{}", " ".repeat(bw), l))
+	.collect();
+    lines.extend(base.lines.iter().map(|l| format!("{}{}", l, " ".repeat(ew))));
+    Block { lines, baseline: exponent.height() + base.baseline }
+}
+
+fn radical(b: Block, style: PrintStyle) -> Block {
+    let (mid, last) = match style {
+	PrintStyle::Unicode => (" │", "╲╱"),
+	PrintStyle::Ascii => (" |", "\\/"),
+    };
+    let bar = format!("  {}", "_".repeat(b.width()));
+    let sign = b.column(mid, mid, last, last);
+    let body = Block::row(vec![sign, b]);
+    Block::stack(vec![text(&bar), body], 1)
+}
+
+// Lay out an expression, bracketing it when it binds looser than
+// the given precedence. Stacked fractions never need brackets.
+fn operand(e: &Expr, precedence: u8, style: PrintStyle) -> Block {
+    let stacked = matches!(e, Div(_, _) | Const(Number::Rational(_)));
+    if e.precedence() < precedence && !stacked {
+	parens(layout(e, style), style)
+    } else {
+	layout(e, style)
+    }
+}
+
+fn right_operand(e: &Expr, precedence: u8, style: PrintStyle) -> Block {
+    if e.precedence() == 3 || (e.precedence() == precedence && !matches!(e, Div(_, _))) {
+	parens(layout(e, style), style)
+    } else {
+	operand(e, precedence, style)
+    }
+}
+
+fn layout(e: &Expr, style: PrintStyle) -> Block {
+    match e {
+	Const(Number::Rational(q)) => {
+	    fraction(text(&q.numer().to_string()), text(&q.denom().to_string()), style)
+	},
+	Const(_) | Named(_) | Var(_) => text(&e.styled(style).to_string()),
+	Neg(ref i) => {
+	    match i.as_ref() {
+		// a space keeps the sign apart from the fraction bar
+		Div(_, _) => text("- ").beside(layout(i, style)),
+		Const(_) => text("-").beside(parens(layout(i, style), style)),
+		i if i.precedence() <= 3 => text("-").beside(parens(layout(i, style), style)),
+		i => text("-").beside(layout(i, style)),
+	    }
+	},
+	Add(ref l, ref r) => {
+	    Block::row(vec![layout(l, style), text(" + "), right_operand(r, 1, style)])
+	},
+	Sub(ref l, ref r) => {
+	    Block::row(vec![layout(l, style), text(" - "), right_operand(r, 1, style)])
+	},
+	Mul(ref l, ref r) => {
+	    let flat = r.styled(style).to_string();
+	    let stacked = [l, r].iter().any(|o| matches!(o.as_ref(), Div(_, _) | Const(Number::Rational(_))));
+	    let sep = match style {
+		_ if juxtapose(l, &flat) && !stacked => "",
+		PrintStyle::Unicode => "·",
+		PrintStyle::Ascii => "*",
+	    };
+	    Block::row(vec![operand(l, 2, style), text(sep), right_operand(r, 2, style)])
+	},
+	Div(ref l, ref r) => fraction(layout(l, style), layout(r, style), style),
+	Pow(ref b, ref p) => {
+	    match p.as_ref() {
+		Const(n) if n.real_eq(0.5) => radical(layout(b, style), style),
+		p => {
+		    let base = match b.as_ref() {
+			Div(_, _) | Const(Number::Rational(_)) | Named(Constant::E) => {
+			    parens(layout(b, style), style)
+			},
+			b => operand(b, 5, style),
+		    };
+		    power(base, layout(p, style))
+		},
+	    }
+	},
+	Exp(ref i) => power(text("e"), layout(i, style)),
+	Factorial(ref i) => {
+	    let inner = match i.as_ref() {
+		Div(_, _) | Const(Number::Rational(_)) => parens(layout(i, style), style),
+		i => operand(i, 5, style),
+	    };
+	    inner.beside(text("!"))
+	},
+	Abs(ref i) => {
+	    let inner = layout(i, style);
+	    let bar = if style == PrintStyle::Unicode { "│" } else { "|" };
+	    let side = inner.column(bar, bar, bar, "|");
+	    Block::row(vec![side.clone(), inner, side])
+	},
+	_ => {
+	    let mut args = Vec::new();
+	    for (n, c) in e.children().into_iter().enumerate() {
+		if n > 0 {
+		    args.push(text(", "));
+		}
+		args.push(layout(c, style));
+	    }
+	    text(function_name(e, style)).beside(parens(Block::row(args), style))
+	},
+    }
+}
+
+fn render(b: Block) -> String {
+    let lines: Vec<&str> = b.lines.iter().map(|l| l.trim_end()).collect();
+    lines.join("\n")
+}
+
+impl Expr {
+    /// Render an expression over several lines of text, with
+    /// fractions stacked, exponents raised and square roots drawn
+    /// with a radical sign.
+    ///
+    /// ```
+    /// use mathy::expr::prelude::*;
+    /// let f = Expr::parse("sqrt(x) / (x + 1)^2").unwrap();
+    /// let expected = [
+    ///     "    _",
+    ///     "  \\/x",
+    ///     "--------",
+    ///     "       2",
+    ///     "(x + 1)",
+    /// ].join("\n");
+    /// assert_eq!(f.pretty(PrintStyle::Ascii), expected);
+    /// ```
+    pub fn pretty(&self, style: PrintStyle) -> String {
+	render(layout(self, style))
+    }
+}
+
+// Bounds drawn above and below a big operator symbol
+fn with_limits(sign: Block, upper: Option<Block>, lower: Option<Block>) -> Block {
+    let mut blocks = Vec::new();
+    let mut base = 0;
+    if let Some(u) = upper {
+	blocks.push(u);
+	base = 1;
+    }
+    blocks.push(sign);
+    if let Some(l) = lower {
+	blocks.push(l);
+    }
+    Block::stack(blocks, base)
+}
+
+/// Render the integral of `f` with respect to `x`, with an integral
+/// sign as tall as the integrand and optional lower and upper bounds.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("x^2").unwrap();
+/// let expected = "1\n⌠\n⎮  2\n⎮ x  dx\n⌡\n0";
+/// assert_eq!(pretty_integral(&f, 'x', Some((&zero(), &one())), PrintStyle::Unicode), expected);
+/// ```
+pub fn pretty_integral<S: Into<Symbol>>(f: &Expr, x: S, bounds: Option<(&Expr, &Expr)>, style: PrintStyle) -> String {
+    let body = layout(f, style);
+    let height = body.height().max(1) + 2;
+    let (top, middle, bottom) = match style {
+	PrintStyle::Unicode => ("⌠", "⎮", "⌡"),
+	PrintStyle::Ascii => (" /", "| ", "/ "),
+    };
+    let lines = (0..height).map(|i| {
+	match i {
+	    0 => top,
+	    i if i == height - 1 => bottom,
+	    _ => middle,
+	}.to_string()
+    }).collect();
+    let sign = Block { lines, baseline: height / 2 };
+    let upper = bounds.map(|b| layout(b.1, style));
+    let lower = bounds.map(|b| layout(b.0, style));
+    let sign = with_limits(sign, upper, lower);
+    let dx = text(&format!(" d{}", x.into()));
+    render(Block::row(vec![sign, text(" "), body, dx]))
+}
+
+/// Render the sum of `f` as `index` runs from `from` to `to`, with
+/// a large sigma sized to the summand.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("1 / k^2").unwrap();
+/// let s = pretty_sum(&f, 'k', &one(), &infinity(), PrintStyle::Ascii);
+/// let expected = [
+///     " inf",
+///     " ___",
+///     " \\     1",
+///     "  \\    --",
+///     "  /     2",
+///     " /__   k",
+///     "k = 1",
+/// ].join("\n");
+/// assert_eq!(s, expected);
+/// ```
+pub fn pretty_sum<S: Into<Symbol>>(f: &Expr, index: S, from: &Expr, to: &Expr, style: PrintStyle) -> String {
+    let body = layout(f, style);
+    let half = body.height().div_ceil(2);
+    let (down, up, bottom) = match style {
+	PrintStyle::Unicode => ("╲", "╱", "‾"),
+	PrintStyle::Ascii => ("\\", "/", "_"),
+    };
+    let width = half + 1;
+    let mut lines = vec!["_".repeat(width)];
+    for i in 0..half {
+	lines.push(pad(&format!("{}{}", " ".repeat(i), down), width));
+    }
+    for i in (0..half).rev() {
+	let rest = if i == 0 && style == PrintStyle::Ascii { bottom.repeat(half) } else { String::new() };
+	lines.push(pad(&format!("{}{}{}", " ".repeat(i), up, rest), width));
+    }
+    if style == PrintStyle::Unicode {
+	lines.push(bottom.repeat(width));
+    }
+    let sign = Block { lines, baseline: half };
+    let lower = text(&format!("{} = ", index.into())).beside(layout(from, style));
+    let sign = with_limits(sign, Some(layout(to, style)), Some(lower));
+    render(Block::row(vec![sign, text("  "), body]))
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ascii(s: &str) -> String {
+	Expr::parse(s).unwrap().pretty(PrintStyle::Ascii)
+    }
+
+    #[test]
+    fn test_flat_expressions() {
+	// anything without fractions or powers matches the flat printer
+	for s in ["x + 2y", "sin(x)*cos(y)", "-(x + 1)", "gamma(x + 1)", "(n - 1)!"].iter() {
+	    let e = Expr::parse(s).unwrap();
+	    assert_eq!(e.pretty(PrintStyle::Ascii), e.to_string());
+	}
+    }
+
+    #[test]
+    fn test_fractions_and_powers() {
+	assert_eq!(ascii("1 / (x + 1) + y"), "  1\n----- + y\nx + 1");
+	assert_eq!(ascii("x^(n + 1)"), " n + 1\nx");
+	assert_eq!(ascii("(x / 2)^3"), "   3\n/x\\\n|-|\n\\2/");
+	assert_eq!(ascii("(a / b)!"), "/a\\\n|-|!\n\\b/");
+	assert_eq!(ascii("-(1 / x)"), "  1\n- -\n  x");
+	assert_eq!(Const(rational(-1, 3)).pretty(PrintStyle::Ascii), "-1\n--\n3");
+	assert_eq!(Expr::parse("2/3 x").unwrap().pretty(PrintStyle::Unicode), "2\n─·x\n3");
+    }
+
+    #[test]
+    fn test_unicode_radical() {
+	let f = Expr::parse("sqrt(x + 1) * Γ(x)").unwrap();
+	assert_eq!(f.pretty(PrintStyle::Unicode), "  _____\n╲╱x + 1·Γ(x)");
+    }
+}
+
+// end src/expr/pretty.rs
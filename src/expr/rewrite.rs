@@ -0,0 +1,443 @@
+// src/expr/rewrite.rs
+// pattern based rewrite rules
+
+use std::collections::HashMap;
+use std::mem::discriminant;
+
+use crate::expr::expr::*;
+use crate::expr::expr::Expr::*;
+use crate::expr::evaluator::*;
+use crate::expr::parser::*;
+use crate::expr::symbol::*;
+
+/// What each wildcard of a pattern matched against.
+pub type Bindings = HashMap<Symbol, Expr>;
+
+/// Check if a symbol is a pattern wildcard, which are the
+/// symbols whose name starts with a `?` like `?a`.
+pub fn is_wildcard(s: Symbol) -> bool {
+    s.name().starts_with('?')
+}
+
+/// A condition on what a wildcard may match for a rule to apply.
+#[derive(Clone, Copy)]
+pub enum Guard {
+    /// A constant whole number, ie `3` but not `x` or `0.5`
+    Integer,
+    /// Any expression without variables, ie `2` or `ln(pi)`
+    Constant,
+    /// Anything but the constant zero
+    NonZero,
+    /// A constant real number greater than zero
+    Positive,
+    /// A single variable
+    Variable,
+    /// Any expression which doesn't contain the symbol
+    FreeOf(Symbol),
+    /// Any user-defined check
+    Custom(fn(&Expr) -> bool),
+}
+
+impl Guard {
+    /// Check the guard against what a wildcard matched.
+    pub fn check(&self, e: &Expr) -> bool {
+	match self {
+	    Guard::Integer => matches!(e, Const(n) if n.is_whole()),
+	    Guard::Constant => Env::new().unbound(e).is_empty(),
+	    Guard::NonZero => !matches!(e, Const(n) if n.is_zero()),
+	    Guard::Positive => matches!(e, Const(n) if n.imag() == 0.0 && n.real() > 0.0),
+	    Guard::Variable => e.is_var(),
+	    Guard::FreeOf(s) => !e.has_var(*s),
+	    Guard::Custom(f) => f(e),
+	}
+    }
+}
+
+/// A rewrite rule, turning anything matching the left hand side
+/// pattern into the right hand side with the wildcards filled in.
+/// Rules can be guarded so that they only apply when the
+/// wildcards match certain kinds of expressions.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let r = Rule::parse("cos_n_pi", "cos(?n * pi) -> (-1)^?n").unwrap()
+///     .when("?n", Guard::Integer);
+/// assert_eq!(r.apply(&Expr::parse("cos(3 pi)").unwrap()), Some(powf(con(-1.0), 3.0)));
+/// assert_eq!(r.apply(&Expr::parse("cos(0.5 pi)").unwrap()), None);
+/// ```
+#[derive(Clone)]
+pub struct Rule {
+    pub name: String,
+    lhs: Expr,
+    rhs: Expr,
+    guards: Vec<(Symbol, Guard)>,
+}
+
+impl Rule {
+    /// Make a rule from two patterns. Wildcards used on the right
+    /// but not on the left are left in the result as they are.
+    pub fn new(name: &str, lhs: Expr, rhs: Expr) -> Rule {
+	Rule { name: name.to_string(), lhs, rhs, guards: Vec::new() }
+    }
+
+    /// Parse a rule written as `lhs -> rhs`, ie
+    /// `exp(ln(?a)) -> ?a`. Every wildcard on the right has to
+    /// appear on the left.
+    pub fn parse(name: &str, rule: &str) -> Result<Rule, ParseError> {
+	let split = rule.find("->").ok_or_else(|| {
+	    ParseError::new(0, "expected '->' between the two sides of a rule".to_string())
+	})?;
+	// positions are in characters, so offset the right side by those
+	let offset = rule[..split].chars().count() + 2;
+	let lhs = Expr::parse(&rule[..split])?;
+	let rhs = Expr::parse(&rule[split + 2..]).map_err(|e| {
+	    ParseError::new(e.position + offset, e.message)
+	})?;
+
+	let mut wild = Vec::new();
+	wildcards(&lhs, &mut wild);
+	let mut rwild = Vec::new();
+	wildcards(&rhs, &mut rwild);
+	if let Some(s) = rwild.iter().find(|s| !wild.contains(s)) {
+	    return Err(ParseError::new(
+		offset, format!("wildcard '{}' is not on the left hand side", s)));
+	}
+	Ok(Rule::new(name, lhs, rhs))
+    }
+
+    /// Only apply the rule when a wildcard passes a guard.
+    pub fn when<S: Into<Symbol>>(mut self, wildcard: S, guard: Guard) -> Rule {
+	self.guards.push((wildcard.into(), guard));
+	self
+    }
+
+    /// Try the rule against the top of an expression, without
+    /// looking at any children.
+    pub fn apply(&self, e: &Expr) -> Option<Expr> {
+	let b = match_pattern(&self.lhs, e)?;
+	let passes = self.guards.iter().all(|(s, g)| {
+	    b.get(s).map(|m| g.check(m)).unwrap_or(false)
+	});
+	if passes {
+	    Some(instantiate(&self.rhs, &b))
+	} else {
+	    None
+	}
+    }
+}
+
+fn wildcards(e: &Expr, out: &mut Vec<Symbol>) {
+    match e {
+	Var(s) if is_wildcard(*s) => out.push(*s),
+	e => e.children().iter().for_each(|c| wildcards(c, out)),
+    }
+}
+
+/// Match a pattern against the top of an expression, giving back
+/// what each wildcard matched. A wildcard used more than once has
+/// to match the same expression each time. Both orders of the
+/// operands of `Add` and `Mul` are tried.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let p = Expr::parse("sin(?a)^2 + cos(?a)^2").unwrap();
+/// let e = Expr::parse("cos(2x)^2 + sin(2x)^2").unwrap();
+/// let b = match_pattern(&p, &e).unwrap();
+/// assert_eq!(b[&Symbol::new("?a")], varf('x', 2.0));
+/// ```
+pub fn match_pattern(pattern: &Expr, e: &Expr) -> Option<Bindings> {
+    let mut b = Bindings::new();
+    if match_into(pattern, e, &mut b) {
+	Some(b)
+    } else {
+	None
+    }
+}
+
+fn match_into(p: &Expr, e: &Expr, b: &mut Bindings) -> bool {
+    match (p, e) {
+	(Var(s), e) if is_wildcard(*s) => {
+	    match b.get(s) {
+		Some(bound) => bound == e,
+		None => {
+		    b.insert(*s, e.clone());
+		    true
+		},
+	    }
+	},
+	(Add(ref pl, ref pr), Add(ref l, ref r)) | (Mul(ref pl, ref pr), Mul(ref l, ref r)) => {
+	    for (x, y) in [(l, r), (r, l)].iter() {
+		let mut attempt = b.clone();
+		if match_into(pl, x, &mut attempt) && match_into(pr, y, &mut attempt) {
+		    *b = attempt;
+		    return true;
+		}
+	    }
+	    false
+	},
+	(Const(x), Const(y)) => x == y,
+	(Named(x), Named(y)) => x == y,
+	(Var(x), Var(y)) => x == y,
+	(p, e) if discriminant(p) == discriminant(e) => {
+	    p.children().iter().zip(e.children().iter()).all(|(pc, ec)| match_into(pc, ec, b))
+	},
+	_ => false,
+    }
+}
+
+// Replace every bound wildcard with what it matched
+fn instantiate(e: &Expr, b: &Bindings) -> Expr {
+    match e {
+	Var(s) => b.get(s).cloned().unwrap_or_else(|| e.clone()),
+	e => e.map_children(|c| instantiate(c, b)),
+    }
+}
+
+/// A named group of rules which can be switched on and off
+/// together in a `Rewriter`.
+#[derive(Clone)]
+pub struct RuleSet {
+    pub name: String,
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn new(name: &str) -> RuleSet {
+	RuleSet { name: name.to_string(), rules: Vec::new() }
+    }
+
+    /// Add a rule to the end of the set.
+    pub fn rule(mut self, r: Rule) -> RuleSet {
+	self.rules.push(r);
+	self
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+	&self.rules
+    }
+
+    // Only for the built-in sets, whose rules are known to parse
+    fn builtin(name: &str, rules: &[(&str, &str)]) -> RuleSet {
+	rules.iter().fold(RuleSet::new(name), |set, (n, r)| {
+	    set.rule(Rule::parse(n, r).unwrap())
+	})
+    }
+
+    /// Trigonometric identities, named "trig".
+    pub fn trig() -> RuleSet {
+	RuleSet::builtin("trig", &[
+	    ("pythagorean", "sin(?a)^2 + cos(?a)^2 -> 1"),
+	    ("hyperbolic", "cosh(?a)^2 - sinh(?a)^2 -> 1"),
+	    ("sin_odd", "sin(-?a) -> -sin(?a)"),
+	    ("cos_even", "cos(-?a) -> cos(?a)"),
+	])
+    }
+
+    /// Inverses of exponentials and logarithms, named "exp_log".
+    pub fn exp_log() -> RuleSet {
+	RuleSet::builtin("exp_log", &[
+	    ("exp_ln", "exp(ln(?a)) -> ?a"),
+	    ("ln_exp", "ln(exp(?a)) -> ?a"),
+	    ("ln_one", "ln(1) -> 0"),
+	    ("exp_zero", "exp(0) -> 1"),
+	])
+    }
+
+    /// Identities of arithmetic, named "algebra".
+    pub fn algebra() -> RuleSet {
+	let set = RuleSet::builtin("algebra", &[
+	    ("double_neg", "-(-?a) -> ?a"),
+	    ("add_zero", "?a + 0 -> ?a"),
+	    ("mul_one", "?a * 1 -> ?a"),
+	    ("mul_zero", "?a * 0 -> 0"),
+	    ("pow_one", "?a^1 -> ?a"),
+	    ("sub_self", "?a - ?a -> 0"),
+	]);
+	set.rule(Rule::parse("pow_zero", "?a^0 -> 1").unwrap().when("?a", Guard::NonZero))
+	    .rule(Rule::parse("div_self", "?a / ?a -> 1").unwrap().when("?a", Guard::NonZero))
+    }
+}
+
+/// Applies rule sets anywhere in an expression tree, rewriting
+/// bottom up and repeating until nothing changes or the iteration
+/// limit is hit. Rules are tried in the order their sets were
+/// added; the first one to match a node wins.
+///
+/// Rewriting never does arithmetic on constants, so run `simplify`
+/// on the result to fold those.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let physics = RuleSet::new("physics")
+///     .rule(Rule::parse("momentum", "?m * ?v -> p").unwrap().when("?m", Guard::Variable));
+/// let r = Rewriter::standard().with(physics);
+/// let e = Expr::parse("(sin(t)^2 + cos(t)^2) * m * v").unwrap();
+/// assert_eq!(r.rewrite(e.clone()), var('p'));
+/// assert_eq!(r.disable("physics").rewrite(e), mul(var('m'), var('v')));
+/// ```
+#[derive(Clone)]
+pub struct Rewriter {
+    sets: Vec<(RuleSet, bool)>,
+    max_iterations: usize,
+}
+
+impl Rewriter {
+    /// A rewriter without any rules.
+    pub fn new() -> Rewriter {
+	Rewriter { sets: Vec::new(), max_iterations: 32 }
+    }
+
+    /// A rewriter with the built-in "algebra", "exp_log" and
+    /// "trig" rule sets enabled.
+    pub fn standard() -> Rewriter {
+	Rewriter::new()
+	    .with(RuleSet::algebra())
+	    .with(RuleSet::exp_log())
+	    .with(RuleSet::trig())
+    }
+
+    /// Add a rule set, enabled.
+    pub fn with(mut self, set: RuleSet) -> Rewriter {
+	self.sets.push((set, true));
+	self
+    }
+
+    pub fn enable(self, name: &str) -> Rewriter {
+	self.toggle(name, true)
+    }
+
+    pub fn disable(self, name: &str) -> Rewriter {
+	self.toggle(name, false)
+    }
+
+    fn toggle(mut self, name: &str, on: bool) -> Rewriter {
+	for (set, enabled) in self.sets.iter_mut() {
+	    if set.name == name {
+		*enabled = on;
+	    }
+	}
+	self
+    }
+
+    pub fn max_iterations(mut self, n: usize) -> Rewriter {
+	self.max_iterations = n;
+	self
+    }
+
+    /// Rewrite with every enabled rule set.
+    pub fn rewrite(&self, e: Expr) -> Expr {
+	let rules: Vec<&Rule> = self.sets.iter()
+	    .filter(|(_, on)| *on)
+	    .flat_map(|(set, _)| set.rules.iter())
+	    .collect();
+	self.run(e, &rules)
+    }
+
+    /// Rewrite with only the named rule sets, whether they are
+    /// enabled or not.
+    pub fn rewrite_with(&self, e: Expr, names: &[&str]) -> Expr {
+	let rules: Vec<&Rule> = self.sets.iter()
+	    .filter(|(set, _)| names.contains(&set.name.as_str()))
+	    .flat_map(|(set, _)| set.rules.iter())
+	    .collect();
+	self.run(e, &rules)
+    }
+
+    fn run(&self, e: Expr, rules: &[&Rule]) -> Expr {
+	let mut current = e;
+	for _ in 0..self.max_iterations {
+	    let next = rewrite_pass(&current, rules);
+	    if next == current {
+		break;
+	    }
+	    current = next;
+	}
+	current
+    }
+}
+
+impl Default for Rewriter {
+    fn default() -> Rewriter {
+	Rewriter::new()
+    }
+}
+
+// Rewrite the children first, then the node itself
+fn rewrite_pass(e: &Expr, rules: &[&Rule]) -> Expr {
+    let e = e.map_children(|c| rewrite_pass(c, rules));
+    rules.iter().find_map(|r| r.apply(&e)).unwrap_or(e)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::number::number::*;
+
+    fn parse(s: &str) -> Expr {
+	Expr::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_match_pattern() {
+	let p = parse("?a * ?a + ?b");
+	let b = match_pattern(&p, &parse("3 + sin(x) * sin(x)")).unwrap();
+	assert_eq!(b[&Symbol::new("?a")], sin(var('x')));
+	assert_eq!(b[&Symbol::new("?b")], con(3.0));
+
+	// the same wildcard has to match the same thing
+	assert!(match_pattern(&p, &parse("x * y + 3")).is_none());
+	// only Add and Mul commute
+	assert!(match_pattern(&parse("?a - 1"), &parse("1 - x")).is_none());
+	assert!(match_pattern(&parse("x + ?a"), &parse("y + 2")).is_none());
+    }
+
+    #[test]
+    fn test_guards() {
+	let r = Rule::parse("split", "?a^?n -> ?a * ?a^(?n - 1)").unwrap()
+	    .when("?n", Guard::Integer)
+	    .when("?a", Guard::FreeOf(Symbol::from('y')));
+	assert!(r.apply(&parse("x^3")).is_some());
+	assert!(r.apply(&parse("x^n")).is_none());
+	assert!(r.apply(&parse("y^3")).is_none());
+
+	fn small(e: &Expr) -> bool {
+	    matches!(e, Const(n) if n.real().abs() < 1.0)
+	}
+	let g = Rule::parse("small", "sin(?a) -> ?a").unwrap().when("?a", Guard::Custom(small));
+	assert_eq!(g.apply(&parse("sin(0.01)")), Some(Const(rational(1, 100))));
+	assert_eq!(g.apply(&parse("sin(2)")), None);
+    }
+
+    #[test]
+    fn test_rule_parse_errors() {
+	assert!(Rule::parse("bad", "sin(?a)").is_err());
+	let err = Rule::parse("bad", "sin(?a) -> ?b").err().unwrap();
+	assert!(err.message.contains("?b"));
+	// the position counts from the start of the whole rule
+	let err = Rule::parse("bad", "x -> (y").err().unwrap();
+	assert_eq!(err.position, 7);
+    }
+
+    #[test]
+    fn test_rewriter() {
+	let e = parse("2 * (cos(x + 1)^2 + sin(x + 1)^2) + ln(exp(y))");
+	let r = Rewriter::standard();
+	assert_eq!(r.rewrite(e.clone()), add(con(2.0), var('y')));
+
+	// nested matches need more than one pass
+	assert_eq!(r.rewrite(parse("-(-(x * 1)) + 0")), var('x'));
+
+	let no_trig = r.clone().disable("trig").rewrite(e.clone());
+	assert!(match_pattern(&parse("?a * (?b + ?c) + y"), &no_trig).is_some());
+	assert_eq!(r.clone().disable("trig").enable("trig").rewrite(e.clone()), r.rewrite(e.clone()));
+
+	let only_logs = r.rewrite_with(e, &["exp_log"]);
+	assert_eq!(only_logs, parse("2 * (cos(x + 1)^2 + sin(x + 1)^2) + y"));
+
+	assert_eq!(Rewriter::new().rewrite(parse("x * 1")), parse("x * 1"));
+	assert_eq!(r.rewrite(parse("0^0")), parse("0^0"));
+    }
+}
+
+// end src/expr/rewrite.rs
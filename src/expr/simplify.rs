@@ -2,39 +2,137 @@
 
 use crate::expr::expr::*;
 use crate::expr::expr::Expr::*;
+use crate::expr::constant::*;
 use crate::number::number::*;
 
 
+/// The weight of a single node of an `Expr` tree, not counting
+/// its children. Used by a `Simplifier` to pick the cheapest of
+/// the forms it comes across.
+pub type Weight = fn(&Expr) -> usize;
+
+/// The default `Weight`, where every node costs the same so the
+/// cost of an expression is its node count.
+pub fn node_weight(_: &Expr) -> usize {
+    1
+}
+
+/// A simplifier which repeatedly applies the rewrite rules until
+/// the expression stops changing or the iteration limit is hit,
+/// giving back the cheapest form seen along the way.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// let f = Expr::parse("0 * y + 1 * (x + 0)").unwrap();
+/// let s = Simplifier::new().max_iterations(8);
+/// assert_eq!(s.simplify(f), var('x'));
+/// ```
+#[derive(Clone, Copy)]
+pub struct Simplifier {
+    max_iterations: usize,
+    weight: Weight,
+}
+
+impl Simplifier {
+    /// A simplifier measuring cost by node count, with
+    /// a limit of 32 iterations.
+    pub fn new() -> Simplifier {
+	Simplifier { max_iterations: 32, weight: node_weight }
+    }
+
+    /// Set the most passes of the rules to make. A limit of one
+    /// is a single pass; zero gives back the expression as is.
+    pub fn max_iterations(mut self, n: usize) -> Simplifier {
+	self.max_iterations = n;
+	self
+    }
+
+    /// Use a different weight for each node when measuring cost,
+    /// ie to make transcendental functions more expensive than
+    /// arithmetic.
+    pub fn weight(mut self, w: Weight) -> Simplifier {
+	self.weight = w;
+	self
+    }
+
+    /// The total weight of every node in an expression.
+    pub fn cost(&self, e: &Expr) -> usize {
+	let children: usize = e.children().iter().map(|c| self.cost(c)).sum();
+	(self.weight)(e) + children
+    }
+
+    /// Simplify until a fixpoint, returning the cheapest form.
+    /// Ties go to the form found last, so rewrites which only
+    /// normalise, like `x*2` to `2x`, are kept.
+    pub fn simplify(&self, e: Expr) -> Expr {
+	let mut best_cost = self.cost(&e);
+	let mut best = e.clone();
+	let mut current = e;
+	for _ in 0..self.max_iterations {
+	    let next = simplify_once(current.clone());
+	    if next == current {
+		break;
+	    }
+	    let c = self.cost(&next);
+	    if c <= best_cost {
+		best_cost = c;
+		best = next.clone();
+	    }
+	    current = next;
+	}
+	best
+    }
+}
+
+impl Default for Simplifier {
+    fn default() -> Simplifier {
+	Simplifier::new()
+    }
+}
+
+/// Simplify an expression with the default `Simplifier`.
 pub fn simplify(e: Expr) -> Expr {
+    Simplifier::new().simplify(e)
+}
+
+// Zero times infinity isn't zero, so products with an infinity
+// anywhere inside are left alone
+fn infinite(e: &Expr) -> bool {
+    e.nodes().any(|n| matches!(n, Named(Constant::Infinity)))
+}
+
+// A single pass of the simplification rules
+fn simplify_once(e: Expr) -> Expr {
     match e {
         Neg(ref i) => {
-	    let inner = simplify(unpack(i));
+	    let inner = simplify_once(unpack(i));
 	    match inner {
 		// two negates would undo each-other
 		// Neg(Neg(x)) => x
-		Neg(ref a) => simplify(unpack(a)), 
+		Neg(ref a) => unpack(a),
+		Const(c) => Const(-c),
 		a => neg(a),
 	    }
 
         },
         Add(ref l, ref r) => {
-            let left = simplify(unpack(l));
-            let right = simplify(unpack(r));
+            let left = simplify_once(unpack(l));
+            let right = simplify_once(unpack(r));
 
             match (left, right) {
                 (Const(l), Const(r)) => Const(l + r),
                 (Const(x), b) => {
                     if x.is_zero() {
-                        simplify(b)
+                        b
                     } else {
-                        add(Const(x), simplify(b))
+                        add(Const(x), b)
                     }
                 },
                 (a, Const(x)) => {
                     if x.is_zero() {
-                        simplify(a)
+                        a
                     } else {
-                        add(Const(x), simplify(a))
+                        add(Const(x), a)
                     }
                 },
 
@@ -76,58 +174,58 @@ pub fn simplify(e: Expr) -> Expr {
         }, // end addition addition logic 
 	
         Sub(ref l, ref r) => {
-            let left = simplify(unpack(l));
-            let right = simplify(unpack(r));
+            let left = simplify_once(unpack(l));
+            let right = simplify_once(unpack(r));
             match (left, right) {
                 (Const(a), Const(b)) => Const(a - b),
                 (Const(x), b) => {
                     if x.is_zero() {
-                        neg(simplify(b))
+                        neg(b)
                     } else {
-                        sub(Const(x), simplify(b))
+                        sub(Const(x), b)
                     }
                 },
                 (a, Const(x)) => {
                     if x.is_zero() {
-                        simplify(a)
+                        a
                     } else {
-                        sub(simplify(a), Const(x))
+                        sub(a, Const(x))
                     }
                 },
                 (a, Neg(i)) => {
                     let inner = unpack(&i);
-                    simplify(add(simplify(a), simplify(inner))) 
+                    add(a, inner)
                 },
-                (a, b) => sub(simplify(a), simplify(b)),
+                (a, b) => sub(a, b),
             }
         }, // end subtraction logic 
 	
         Mul(ref l, ref r) => {
-            let left = unpack(l);
-            let right = unpack(r);
+            let left = simplify_once(unpack(l));
+            let right = simplify_once(unpack(r));
 	    
             match (left, right) {
                 (Const(x), Const(y)) => Const(x * y),
                 (Const(x), b) => {
-                    if x.is_zero() {
+                    if x.is_zero() && !infinite(&b) {
                         zero()
                     } else if x.real_eq(1.0) {
-                        simplify(b)
+                        b
                     } else if x.real_eq(-1.0) {
-                        neg(simplify(b))
+                        neg(b)
                     } else {
-                        mul(Const(x), simplify(b))
+                        mul(Const(x), b)
                     }
                 },
                 (a, Const(y)) => {
-                    if y.is_zero() {
+                    if y.is_zero() && !infinite(&a) {
                         zero()
                     } else if y.real_eq(1.0) {
-                        simplify(a)
+                        a
                     } else if y.real_eq(-1.0) {
-                        neg(simplify(a))
+                        neg(a)
                     } else {
-			mul(Const(y), simplify(a)) 
+			mul(Const(y), a) 
                     }
                 },
 		(left, Neg(r)) => {
@@ -175,71 +273,154 @@ pub fn simplify(e: Expr) -> Expr {
 		    if a == b {
 			powf(a, 2.0)
 		    } else {
-			mul(simplify(a), simplify(b))
+			mul(a, b)
 		    }
 		},
             }
         }, // end multiplication logic
 
         Div(ref l, ref r) => {
-            let left = unpack(l);
-            let right = unpack(r);
+            let left = simplify_once(unpack(l));
+            let right = simplify_once(unpack(r));
 
             match (left, right) {
+		// exact quotients fold to a fraction instead of a float
+		(Const(x), Const(y)) if x.is_exact() && y.is_exact() && !y.is_zero() => {
+		    Const(x / y)
+		},
                 (numerator, Const(x)) => {
                     if x.real_eq(1.0) {
-                        simplify(numerator)   
+                        numerator   
+                    } else if x.real_eq(-1.0) {
+                        neg(numerator)
                     } else if x.real_eq(0.0) {
                         Const(nan())
                     } else {
-                        div(simplify(numerator), Const(x))
+                        div(numerator, Const(x))
                     }
                 },
 
-                (a, b) => div(simplify(a), simplify(b)),
+                (a, b) => div(a, b),
             }
         }, // end division logic
 
 	Pow(ref l, ref r) => {
-	    let left = unpack(l);
-	    let right = unpack(r);
+	    let left = simplify_once(unpack(l));
+	    let right = simplify_once(unpack(r));
 
 	    match (left, right) {
 		(Const(b), Const(p)) => Const(b.pow(p)),
+		// a power of e is the exponential function
+		(Named(Constant::E), p) => exp(p),
 		(a, Const(b)) => {
 		    if b.real_eq(1.0) {
-			simplify(a)
+			a
 		    } else {
-			pow(simplify(a), simplify(Const(b)))
+			pow(a, Const(b))
 		    }
 		}
-		(a, b) => pow(simplify(a), simplify(b)),
+		(a, b) => pow(a, b),
 	    }
 	},
 
 	Exp(ref i) => {
-	    let inner = unpack(i);
+	    let inner = simplify_once(unpack(i));
 	    match inner {
 		// e^x and ln(x) are inverse functions
 		Ln(ref a) => {
 		    unpack(a)
 		} 
-		a => exp(a),
+		Const(ref k) if k.real_eq(1.0) => Named(Constant::E),
+		// Euler's formula at multiples of pi/2, ie e^(i*pi) = -1
+		a => match imaginary_quarter_turns(&a) {
+		    Some(0) => one(),
+		    Some(1) => imag_unit(),
+		    Some(2) => Const(integer(-1)),
+		    Some(_) => neg(imag_unit()),
+		    None => exp(a),
+		},
 	    }
 	},
 	// Same as Exp inverse rule, just switched
 	Ln(ref i) => {
-	    let inner = unpack(i);
+	    let inner = simplify_once(unpack(i));
 	    match inner {
 		Exp(ref a) => {
 		    unpack(a)
 		} 
+		Named(Constant::E) => one(),
 		a => ln(a),
 	    }
 	},
 
+	// sin and cos are known exactly at multiples of pi/2
+	Sin(ref i) => {
+	    let inner = simplify_once(unpack(i));
+	    match quarter_turns(&inner) {
+		Some(0) | Some(2) => zero(),
+		Some(1) => one(),
+		Some(_) => Const(integer(-1)),
+		None => sin(inner),
+	    }
+	},
+	Cos(ref i) => {
+	    let inner = simplify_once(unpack(i));
+	    match quarter_turns(&inner) {
+		Some(0) => one(),
+		Some(2) => Const(integer(-1)),
+		Some(_) => zero(),
+		None => cos(inner),
+	    }
+	},
+
+
+        // no rules for the node itself, only its children
+        e => e.map_children(|c| simplify_once(c.clone())),
+    }
+}
+
+// The multiple k of pi in an expression like k*pi, pi/k or -pi
+fn pi_multiple(e: &Expr) -> Option<Number> {
+    match e {
+	Named(Constant::Pi) => Some(integer(1)),
+	Const(k) if k.is_zero() => Some(integer(0)),
+	Neg(ref i) => pi_multiple(i).map(|k| -k),
+	Mul(ref l, ref r) => {
+	    match (l.as_ref(), r.as_ref()) {
+		(Const(k), Named(Constant::Pi)) | (Named(Constant::Pi), Const(k)) => Some(k.clone()),
+		_ => None,
+	    }
+	},
+	Div(ref l, ref r) => {
+	    match (pi_multiple(l), r.as_ref()) {
+		(Some(k), Const(d)) => Some(&k / d),
+		_ => None,
+	    }
+	},
+	_ => None,
+    }
+}
+
+// How many quarter turns of pi/2 an angle makes, modulo 4, when it
+// is a whole number of them
+fn quarter_turns(e: &Expr) -> Option<i64> {
+    let n = &pi_multiple(e)? * &integer(2);
+    if !n.is_whole() || n.imag() != 0.0 || n.real().abs() > 1e15 {
+	return None;
+    }
+    Some((n.real() as i64).rem_euclid(4))
+}
 
-        _ => e,
+// Quarter turns for a product of i with a multiple of pi, in either order
+fn imaginary_quarter_turns(e: &Expr) -> Option<i64> {
+    match e {
+	Mul(ref l, ref r) => {
+	    match (l.as_ref(), r.as_ref()) {
+		(Named(Constant::ImaginaryUnit), a) | (a, Named(Constant::ImaginaryUnit)) => quarter_turns(a),
+		_ => None,
+	    }
+	},
+	_ => None,
     }
 }
 
@@ -253,14 +434,14 @@ mod test {
 
     #[test]
     fn test_add_simplify() {
-        let tests = vec![
+        let tests = [
             zero() + con(1.0),
             con(1.0) + zero(),
             zero() + zero(),
             con(1.0) + con(1.0),
         ];
 
-        let answers = vec![
+        let answers = [
             ONE, ONE, ZERO, con(2.0), 
         ];
 
@@ -288,6 +469,7 @@ mod test {
 	let output = simplify(input);
 	let expected = var('x');
 	assert_eq!(output, expected);
+	assert_eq!(simplify(div(var('x'), Const(integer(-1)))), neg(var('x')));
     }
 
     #[test]
@@ -297,6 +479,80 @@ mod test {
 	let expected = con(1.0);
 	assert_eq!(output, expected);
     }
+
+    #[test]
+    fn test_mul_children(){
+	// the children of a product used to be matched unsimplified
+	let input = mul(add(zero(), con(2.0)), mul(con(1.0), var('x')));
+	assert_eq!(simplify(input), varf('x', 2.0));
+
+	let nested = sin(add(mul(zero(), var('y')), var('x')));
+	assert_eq!(simplify(nested), sin(var('x')));
+    }
+
+    #[test]
+    fn test_fixpoint(){
+	// x * x^2 becomes x^(1 + 2), which only adds up on the next pass
+	let input = mul(var('x'), powf(var('x'), 2.0));
+	assert_eq!(simplify(input.clone()), powf(var('x'), 3.0));
+
+	// one pass gives x^(1 + 2), which costs the same so it's kept
+	let once = Simplifier::new().max_iterations(1).simplify(input.clone());
+	assert_eq!(once, pow(var('x'), add(con(1.0), con(2.0))));
+	assert_eq!(Simplifier::new().max_iterations(0).simplify(input.clone()), input);
+    }
+
+    #[test]
+    fn test_cost(){
+	let s = Simplifier::new();
+	assert_eq!(s.cost(&add(var('x'), sin(var('y')))), 4);
+
+	// make every function a lot more expensive than arithmetic
+	fn heavy(e: &Expr) -> usize {
+	    match e {
+		Const(_) | Var(_) | Neg(_) | Add(_, _) | Sub(_, _) | Mul(_, _) | Div(_, _) => 1,
+		_ => 10,
+	    }
+	}
+	let h = Simplifier::new().weight(heavy);
+	assert_eq!(h.cost(&exp(ln(var('x')))), 21);
+	assert_eq!(h.simplify(exp(ln(var('x')))), var('x'));
+	// normalising rewrites which don't change the cost still apply
+	assert_eq!(simplify(mul(var('x'), con(2.0))), mul(con(2.0), var('x')));
+	assert_eq!(simplify(mul(var('x'), con(2.0))).to_string(), "2x");
+	assert_eq!(simplify(add(var('x'), con(2.0))), add(con(2.0), var('x')));
+    }
+
+    #[test]
+    fn test_exact_constants(){
+	// parsed literals are exact, so there's no rounding to see
+	let e = Expr::parse("0.1 + 0.2").unwrap();
+	assert_eq!(simplify(e).to_string(), "3/10");
+	assert_eq!(simplify(Expr::parse("1 / 3").unwrap()).to_string(), "1/3");
+
+	let third = Expr::parse("x / 3 + x / 6").unwrap();
+	assert_eq!(crate::expr::canonical::canonical(third).to_string(), "(1/2)x");
+    }
+
+    #[test]
+    fn test_named_constants(){
+	let parse = |s: &str| simplify(Expr::parse(s).unwrap());
+	assert_eq!(parse("sin(pi)"), zero());
+	assert_eq!(parse("sin(3 * pi)"), zero());
+	assert_eq!(parse("cos(-pi)"), Const(integer(-1)));
+	assert_eq!(parse("sin(pi / 2)"), one());
+	assert_eq!(parse("ln(e)"), one());
+	assert_eq!(parse("e^(i * pi)"), Const(integer(-1)));
+	assert_eq!(simplify(pow(e(), mul(pi(), imag_unit()))), Const(integer(-1)));
+	assert_eq!(simplify(exp(con(1.0))), e());
+	assert_eq!(parse("sin(pi / 3)"), sin(div(pi(), Const(integer(3)))));
+
+	// zero times infinity stays as it is
+	assert_eq!(parse("0 * inf"), mul(zero(), infinity()));
+	assert_eq!(parse("(x inf) * 0"), mul(zero(), mul(var('x'), infinity())));
+	assert_eq!(parse("1 / inf"), div(one(), infinity()));
+	assert_eq!(parse("0 * x"), zero());
+    }
 }
 
 
@@ -0,0 +1,178 @@
+// src/expr/symbol.rs
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+/// An interned variable name. Symbols are cheap to copy and compare,
+/// since only a small id is stored; the name itself lives in a
+/// global table for the lifetime of the program.
+///
+/// Indexed symbols like `x_1` are regular symbols whose name ends in
+/// an underscore followed by a number, and can be made with
+/// `Symbol::indexed`.
+///
+/// ```
+/// use mathy::expr::symbol::*;
+/// let theta = Symbol::new("theta");
+/// let x1 = Symbol::indexed("x", 1);
+/// assert_eq!(theta.name(), "theta");
+/// assert_eq!(x1.name(), "x_1");
+/// assert_eq!(x1.index(), Some(1));
+/// assert_eq!(Symbol::from('x'), Symbol::new("x"));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+struct Interner {
+    names: Vec<&'static str>,
+    ids: HashMap<&'static str, u32>,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| {
+	Mutex::new(Interner { names: Vec::new(), ids: HashMap::new() })
+    })
+}
+
+impl Symbol {
+    /// Intern a name and return its Symbol. Interning the same
+    /// name twice gives back the same Symbol.
+    pub fn new(name: &str) -> Symbol {
+	let mut table = interner().lock().unwrap();
+	if let Some(id) = table.ids.get(name) {
+	    return Symbol(*id);
+	}
+	// names are never removed, so leaking them is what we want
+	let stored: &'static str = Box::leak(name.to_string().into_boxed_str());
+	let id = table.names.len() as u32;
+	table.names.push(stored);
+	table.ids.insert(stored, id);
+	Symbol(id)
+    }
+
+    /// Create a subscripted symbol like `x_1` for vector components.
+    pub fn indexed(base: &str, index: usize) -> Symbol {
+	Symbol::new(&format!("{}_{}", base, index))
+    }
+
+    /// The full name of the symbol, including any subscript.
+    pub fn name(&self) -> &'static str {
+	interner().lock().unwrap().names[self.0 as usize]
+    }
+
+    /// The name of the symbol without its subscript.
+    pub fn base(&self) -> &'static str {
+	let name = self.name();
+	match self.index() {
+	    Some(_) => &name[..name.rfind('_').unwrap()],
+	    None => name,
+	}
+    }
+
+    /// The numeric subscript of the symbol, if there is one.
+    pub fn index(&self) -> Option<usize> {
+	let name = self.name();
+	let pos = name.rfind('_')?;
+	if pos == 0 {
+	    return None;
+	}
+	name[pos+1..].parse::<usize>().ok()
+    }
+}
+
+// Symbols sort by name rather than by the order they were interned,
+// so anything that orders expressions stays deterministic.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Symbol) -> Ordering {
+	if self.0 == other.0 {
+	    return Ordering::Equal;
+	}
+	self.name().cmp(other.name())
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Symbol) -> Option<Ordering> {
+	Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f, "{}", self.name())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f, "{}", self.name())
+    }
+}
+
+impl From<char> for Symbol {
+    fn from(c: char) -> Symbol {
+	Symbol::new(c.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Symbol {
+	Symbol::new(s)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(s: String) -> Symbol {
+	Symbol::new(&s)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(s: &String) -> Symbol {
+	Symbol::new(s)
+    }
+}
+
+/// Shortcut to make a list of indexed symbols, ie
+/// `symbols("x", 1..=3)` gives `x_1, x_2, x_3`.
+pub fn symbols<I: IntoIterator<Item = usize>>(base: &str, indices: I) -> Vec<Symbol> {
+    indices.into_iter().map(|i| Symbol::indexed(base, i)).collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_interning() {
+	let a = Symbol::new("omega");
+	let b = Symbol::from("omega".to_string());
+	assert_eq!(a, b);
+	assert_ne!(a, Symbol::new("theta"));
+	assert_eq!(Symbol::from('x').name(), "x");
+    }
+
+    #[test]
+    fn test_indexed() {
+	let xs = symbols("x", 1..=3);
+	assert_eq!(xs[2].name(), "x_3");
+	assert_eq!(xs[2].base(), "x");
+	assert_eq!(xs[2].index(), Some(3));
+	assert_eq!(Symbol::new("v0").index(), None);
+	assert_eq!(Symbol::new("_1").index(), None);
+    }
+
+    #[test]
+    fn test_ordering() {
+	let mut syms = [Symbol::new("z"), Symbol::new("a"), Symbol::new("m")];
+	syms.sort();
+	let names: Vec<&str> = syms.iter().map(|s| s.name()).collect();
+	assert_eq!(names, vec!["a", "m", "z"]);
+    }
+}
+
+// end src/expr/symbol.rs
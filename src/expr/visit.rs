@@ -0,0 +1,177 @@
+// src/expr/visit.rs
+
+use crate::expr::expr::*;
+use crate::expr::expr::Expr::*;
+use crate::expr::symbol::*;
+
+
+/// Something which looks at every node of an `Expr` tree, passed
+/// to `Expr::walk`. Both methods do nothing by default, so only
+/// the ones a pass cares about need writing.
+///
+/// ```
+/// use mathy::expr::prelude::*;
+/// // count the function calls, without looking inside them
+/// struct Calls(usize);
+/// impl Visitor for Calls {
+///     fn enter(&mut self, e: &Expr) -> bool {
+///         match e {
+///             Expr::Sin(_) | Expr::Cos(_) | Expr::Ln(_) => { self.0 += 1; false },
+///             _ => true,
+///         }
+///     }
+/// }
+/// let mut calls = Calls(0);
+/// Expr::parse("sin(cos(x)) + x ln(x)").unwrap().walk(&mut calls);
+/// assert_eq!(calls.0, 2);
+/// ```
+pub trait Visitor {
+    /// Called on a node before its children. Returning false
+    /// skips the children of the node.
+    fn enter(&mut self, _e: &Expr) -> bool {
+	true
+    }
+
+    /// Called on a node after its children, even when they
+    /// were skipped.
+    fn leave(&mut self, _e: &Expr) {}
+}
+
+/// An iterator over every node of an `Expr` tree, parents before
+/// their children and children left to right, made by `Expr::nodes`.
+pub struct Nodes<'a> {
+    stack: Vec<&'a Expr>,
+}
+
+impl<'a> Iterator for Nodes<'a> {
+    type Item = &'a Expr;
+
+    fn next(&mut self) -> Option<&'a Expr> {
+	let e = self.stack.pop()?;
+	self.stack.extend(e.children().into_iter().rev());
+	Some(e)
+    }
+}
+
+impl Expr {
+    /// Run a `Visitor` over the tree, depth first.
+    pub fn walk<V: Visitor>(&self, v: &mut V) {
+	if v.enter(self) {
+	    for c in self.children() {
+		c.walk(v);
+	    }
+	}
+	v.leave(self);
+    }
+
+    /// Fold the tree bottom up, where `f` gets each node along
+    /// with what its children folded to, left to right.
+    ///
+    /// ```
+    /// use mathy::expr::prelude::*;
+    /// // the number of leaves in the tree
+    /// let f = Expr::parse("x^2 + beta(x, y)").unwrap();
+    /// let leaves = f.fold(|_, kids: Vec<usize>| kids.iter().sum::<usize>().max(1));
+    /// assert_eq!(leaves, 4);
+    /// ```
+    pub fn fold<T, F: FnMut(&Expr, Vec<T>) -> T>(&self, mut f: F) -> T {
+	fold_with(self, &mut f)
+    }
+
+    /// Iterate over every node of the tree, starting with this one.
+    ///
+    /// ```
+    /// use mathy::expr::prelude::*;
+    /// let f = Expr::parse("sin(x) * y").unwrap();
+    /// let names: Vec<String> = f.nodes().map(|n| n.to_string()).collect();
+    /// assert_eq!(names, vec!["sin(x)*y", "sin(x)", "x", "y"]);
+    /// ```
+    pub fn nodes(&self) -> Nodes<'_> {
+	Nodes { stack: vec![self] }
+    }
+
+    /// Every variable in the tree, sorted by name and without
+    /// duplicates.
+    pub fn free_symbols(&self) -> Vec<Symbol> {
+	let mut syms: Vec<Symbol> = self.nodes()
+	    .filter_map(|n| match n {
+		Var(s) => Some(*s),
+		_ => None,
+	    })
+	    .collect();
+	syms.sort();
+	syms.dedup();
+	syms
+    }
+
+    /// The number of nodes on the longest path from the top of the
+    /// tree down to a constant or variable, which count as 1.
+    pub fn depth(&self) -> usize {
+	self.fold(|_, depths: Vec<usize>| 1 + depths.into_iter().max().unwrap_or(0))
+    }
+
+    /// The number of operations and function calls in the tree.
+    pub fn count_ops(&self) -> usize {
+	self.nodes().filter(|n| n.is_op()).count()
+    }
+}
+
+fn fold_with<T, F: FnMut(&Expr, Vec<T>) -> T>(e: &Expr, f: &mut F) -> T {
+    let kids: Vec<T> = e.children().into_iter().map(|c| fold_with(c, f)).collect();
+    f(e, kids)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_measures() {
+	let f = Expr::parse("sinh(x)! + gamma(y) / (z - pi)").unwrap();
+	assert_eq!(f.free_symbols(), vec![Symbol::from('x'), Symbol::from('y'), Symbol::from('z')]);
+	assert_eq!(f.depth(), 4);
+	assert_eq!(f.count_ops(), 6);
+	assert_eq!(f.nodes().count(), 10);
+	assert_eq!(var('x').depth(), 1);
+	assert_eq!(con(2.0).count_ops(), 0);
+	assert!(pi().free_symbols().is_empty());
+    }
+
+    #[test]
+    fn test_every_variant() {
+	// has_var and substitute used to miss some of these
+	let fs = [
+	    "sinh(x)", "cosh(x)", "asin(x)", "acos(x)", "asinh(x)", "acosh(x)",
+	    "x!", "gamma(x)", "lngamma(x)", "digamma(x)", "polygamma(1, x)",
+	    "beta(2, x)", "|x|", "e^x", "ln(x)", "-x",
+	];
+	for s in fs.iter() {
+	    let f = Expr::parse(s).unwrap();
+	    assert!(f.has_var('x'), "{}", s);
+	    let g = f.substitute('x', 'y');
+	    assert!(!g.has_var('x') && g.has_var('y'), "{}", s);
+	    assert_eq!(g.substitute('y', 'x'), f);
+	}
+    }
+
+    #[test]
+    fn test_visitor() {
+	// record the order nodes are entered and left
+	struct Trace(Vec<String>);
+	impl Visitor for Trace {
+	    fn enter(&mut self, e: &Expr) -> bool {
+		self.0.push(format!("+{}", e));
+		!matches!(e, Ln(_))
+	    }
+	    fn leave(&mut self, e: &Expr) {
+		self.0.push(format!("-{}", e));
+	    }
+	}
+	let mut t = Trace(Vec::new());
+	Expr::parse("x + ln(y)").unwrap().walk(&mut t);
+	assert_eq!(t.0, vec!["+x + ln(y)", "+x", "-x", "+ln(y)", "-ln(y)", "-x + ln(y)"]);
+    }
+}
+
+// end src/expr/visit.rs
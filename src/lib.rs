@@ -1,5 +1,7 @@
+pub mod error;
 pub mod number;
 pub mod expr;
 pub mod calc;
 pub mod analysis;
+pub mod poly;
 
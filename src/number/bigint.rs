@@ -0,0 +1,568 @@
+// src/number/bigint.rs
+// arbitrary size integers for exact arithmetic
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add,Sub,Mul,Div,Rem,Neg};
+
+/// A signed integer of any size, stored as base 2^32 digits with
+/// the least significant digit first. Zero has no digits and is
+/// never negative, so equal values always compare equal.
+///
+/// ```
+/// use mathy::number::bigint::*;
+/// let a = BigInt::from(2).pow(100);
+/// assert_eq!(a.to_string(), "1267650600228229401496703205376");
+/// assert_eq!(&a / &BigInt::from(1u64 << 40), BigInt::from(2).pow(60));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+	BigInt { negative: false, digits: Vec::new() }
+    }
+
+    pub fn one() -> BigInt {
+	BigInt::from(1)
+    }
+
+    fn from_parts(negative: bool, mut digits: Vec<u32>) -> BigInt {
+	trim(&mut digits);
+	BigInt { negative: negative && !digits.is_empty(), digits }
+    }
+
+    /// Read a decimal integer with an optional leading sign.
+    pub fn parse(s: &str) -> Option<BigInt> {
+	let (negative, body) = match s.strip_prefix('-') {
+	    Some(rest) => (true, rest),
+	    None => (false, s.strip_prefix('+').unwrap_or(s)),
+	};
+	if body.is_empty() || !body.chars().all(|c| c.is_ascii_digit()) {
+	    return None;
+	}
+	let mut digits = Vec::new();
+	for c in body.bytes() {
+	    digits = mul_small(&digits, 10, (c - b'0') as u32);
+	}
+	Some(BigInt::from_parts(negative, digits))
+    }
+
+    /// The exact value of a whole, finite f64.
+    pub fn from_f64(x: f64) -> Option<BigInt> {
+	if !x.is_finite() || x != x.trunc() {
+	    return None;
+	}
+	let (mantissa, exponent) = decompose(x.abs());
+	let m = BigInt::from(mantissa);
+	let n = if exponent >= 0 {
+	    m.shl(exponent as u32)
+	} else {
+	    m.shr((-exponent) as u32)
+	};
+	Some(if x < 0.0 { -n } else { n })
+    }
+
+    /// The nearest f64, which is infinite when out of range.
+    pub fn to_f64(&self) -> f64 {
+	let bits = self.bits();
+	// only the top 64 bits matter for the rounding
+	let (top, shift) = if bits > 64 {
+	    (self.abs().shr(bits - 64), bits - 64)
+	} else {
+	    (self.abs(), 0)
+	};
+	let m = top.digits.iter().rev().fold(0.0, |acc, d| acc * 4294967296.0 + *d as f64);
+	let v = m * 2f64.powi(shift.min(2000) as i32);
+	if self.negative { -v } else { v }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+	if self.digits.len() > 2 {
+	    return None;
+	}
+	let m = self.digits.iter().rev().fold(0u64, |acc, d| (acc << 32) | *d as u64);
+	if self.negative {
+	    if m <= 1 << 63 { Some((m as i64).wrapping_neg()) } else { None }
+	} else {
+	    if m <= i64::MAX as u64 { Some(m as i64) } else { None }
+	}
+    }
+
+    pub fn is_zero(&self) -> bool {
+	self.digits.is_empty()
+    }
+
+    pub fn is_one(&self) -> bool {
+	!self.negative && self.digits == [1]
+    }
+
+    pub fn is_negative(&self) -> bool {
+	self.negative
+    }
+
+    pub fn is_even(&self) -> bool {
+	self.digits.first().map(|d| d % 2 == 0).unwrap_or(true)
+    }
+
+    pub fn abs(&self) -> BigInt {
+	BigInt { negative: false, digits: self.digits.clone() }
+    }
+
+    /// The number of bits needed for the magnitude.
+    pub fn bits(&self) -> u32 {
+	match self.digits.last() {
+	    None => 0,
+	    Some(top) => 32 * self.digits.len() as u32 - top.leading_zeros(),
+	}
+    }
+
+    /// Multiply by 2^n.
+    pub fn shl(&self, n: u32) -> BigInt {
+	if self.is_zero() {
+	    return self.clone();
+	}
+	let mut digits = vec![0; (n / 32) as usize];
+	digits.extend(shl_bits(&self.digits, n % 32));
+	BigInt::from_parts(self.negative, digits)
+    }
+
+    /// Divide the magnitude by 2^n, rounding towards zero.
+    pub fn shr(&self, n: u32) -> BigInt {
+	let skip = (n / 32) as usize;
+	if skip >= self.digits.len() {
+	    return BigInt::zero();
+	}
+	BigInt::from_parts(self.negative, shr_bits(&self.digits[skip..], n % 32))
+    }
+
+    pub fn pow(&self, mut n: u32) -> BigInt {
+	let mut base = self.clone();
+	let mut acc = BigInt::one();
+	while n > 0 {
+	    if n & 1 == 1 {
+		acc = &acc * &base;
+	    }
+	    n >>= 1;
+	    if n > 0 {
+		base = &base * &base;
+	    }
+	}
+	acc
+    }
+
+    /// Division truncating towards zero, with a remainder taking
+    /// the sign of `self` like the primitive integers. Panics on
+    /// division by zero.
+    pub fn div_rem(&self, other: &BigInt) -> (BigInt, BigInt) {
+	assert!(!other.is_zero(), "BigInt division by zero");
+	let (q, r) = divrem_mag(&self.digits, &other.digits);
+	(BigInt::from_parts(self.negative != other.negative, q),
+	 BigInt::from_parts(self.negative, r))
+    }
+
+    /// The non-negative greatest common divisor.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+	let (mut a, mut b) = (self.abs(), other.abs());
+	while !b.is_zero() {
+	    let r = a.div_rem(&b).1;
+	    a = b;
+	    b = r;
+	}
+	a
+    }
+
+    /// The exact `k`th root, if there is one. Negative numbers only
+    /// have odd roots.
+    pub fn root(&self, k: u32) -> Option<BigInt> {
+	if k == 0 {
+	    return None;
+	}
+	if self.negative {
+	    return if k % 2 == 1 { self.abs().root(k).map(|r| -r) } else { None };
+	}
+	if self.is_zero() || self.is_one() || k == 1 {
+	    return Some(self.clone());
+	}
+
+	// Newton's method from above converges on floor(root)
+	let big_k = BigInt::from(k as u64);
+	let mut x = BigInt::one().shl(self.bits().div_ceil(k));
+	loop {
+	    let y = &(&(&BigInt::from(k as u64 - 1) * &x) + &(self / &x.pow(k - 1))) / &big_k;
+	    if y >= x {
+		break;
+	    }
+	    x = y;
+	}
+	if &x.pow(k) == self { Some(x) } else { None }
+    }
+}
+
+// Split a positive finite f64 into mantissa * 2^exponent
+fn decompose(x: f64) -> (u64, i32) {
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    if exponent == 0 {
+	(fraction, -1074)
+    } else {
+	(fraction | (1 << 52), exponent - 1075)
+    }
+}
+
+fn trim(digits: &mut Vec<u32>) {
+    while digits.last() == Some(&0) {
+	digits.pop();
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for (i, x) in a.iter().enumerate() {
+	let s = *x as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+	out.push(s as u32);
+	carry = s >> 32;
+    }
+    if carry > 0 {
+	out.push(carry as u32);
+    }
+    out
+}
+
+// a - b where |a| >= |b|
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, x) in a.iter().enumerate() {
+	let t = *x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+	out.push(t as u32);
+	borrow = if t < 0 { 1 } else { 0 };
+    }
+    trim(&mut out);
+    out
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+	return Vec::new();
+    }
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+	let mut carry = 0u64;
+	for (j, y) in b.iter().enumerate() {
+	    let t = *x as u64 * *y as u64 + out[i + j] as u64 + carry;
+	    out[i + j] = t as u32;
+	    carry = t >> 32;
+	}
+	out[i + b.len()] = carry as u32;
+    }
+    trim(&mut out);
+    out
+}
+
+// a * m + c for small m and c
+fn mul_small(a: &[u32], m: u32, c: u32) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = c as u64;
+    for x in a.iter() {
+	let t = *x as u64 * m as u64 + carry;
+	out.push(t as u32);
+	carry = t >> 32;
+    }
+    if carry > 0 {
+	out.push(carry as u32);
+    }
+    trim(&mut out);
+    out
+}
+
+fn divrem_small(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut q = vec![0u32; a.len()];
+    let mut r = 0u64;
+    for i in (0..a.len()).rev() {
+	let cur = (r << 32) | a[i] as u64;
+	q[i] = (cur / d as u64) as u32;
+	r = cur % d as u64;
+    }
+    trim(&mut q);
+    (q, r as u32)
+}
+
+// Shift left by fewer than 32 bits, always giving one extra digit
+fn shl_bits(a: &[u32], s: u32) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u32;
+    for x in a.iter() {
+	out.push((x << s) | carry);
+	carry = if s == 0 { 0 } else { x >> (32 - s) };
+    }
+    out.push(carry);
+    out
+}
+
+fn shr_bits(a: &[u32], s: u32) -> Vec<u32> {
+    let mut out = vec![0u32; a.len()];
+    for i in 0..a.len() {
+	let high = if s == 0 { 0 } else { a.get(i + 1).map(|x| x << (32 - s)).unwrap_or(0) };
+	out[i] = (a[i] >> s) | high;
+    }
+    trim(&mut out);
+    out
+}
+
+// Long division of magnitudes, Knuth's algorithm D
+fn divrem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+	return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+	let (q, r) = divrem_small(a, b[0]);
+	let mut r = vec![r];
+	trim(&mut r);
+	return (q, r);
+    }
+
+    // normalise so the top digit of the divisor has its high bit set
+    let s = b[b.len() - 1].leading_zeros();
+    let mut b = shl_bits(b, s);
+    trim(&mut b);
+    let mut a = shl_bits(a, s);
+    let n = b.len();
+    let m = a.len() - n;
+    let mut q = vec![0u32; m];
+    let base = 1u64 << 32;
+
+    for j in (0..m).rev() {
+	let top = ((a[j + n] as u64) << 32) | a[j + n - 1] as u64;
+	let mut qhat = top / b[n - 1] as u64;
+	let mut rhat = top % b[n - 1] as u64;
+	while qhat >= base || qhat * b[n - 2] as u64 > ((rhat << 32) | a[j + n - 2] as u64) {
+	    qhat -= 1;
+	    rhat += b[n - 1] as u64;
+	    if rhat >= base {
+		break;
+	    }
+	}
+
+	// subtract qhat * b from the current window
+	let mut borrow = 0i64;
+	let mut carry = 0u64;
+	for i in 0..n {
+	    let p = qhat * b[i] as u64 + carry;
+	    carry = p >> 32;
+	    let t = a[i + j] as i64 - borrow - (p & 0xffff_ffff) as i64;
+	    a[i + j] = t as u32;
+	    borrow = if t < 0 { 1 } else { 0 };
+	}
+	let t = a[j + n] as i64 - borrow - carry as i64;
+	a[j + n] = t as u32;
+
+	// qhat was one too big, so add the divisor back
+	if t < 0 {
+	    qhat -= 1;
+	    let mut c = 0u64;
+	    for i in 0..n {
+		let sum = a[i + j] as u64 + b[i] as u64 + c;
+		a[i + j] = sum as u32;
+		c = sum >> 32;
+	    }
+	    a[j + n] = a[j + n].wrapping_add(c as u32);
+	}
+	q[j] = qhat as u32;
+    }
+
+    trim(&mut q);
+    (q, shr_bits(&a[..n], s))
+}
+
+impl From<i64> for BigInt {
+    fn from(x: i64) -> BigInt {
+	let mut n = BigInt::from(x.unsigned_abs());
+	n.negative = x < 0;
+	n
+    }
+}
+
+impl From<u64> for BigInt {
+    fn from(x: u64) -> BigInt {
+	BigInt::from_parts(false, vec![x as u32, (x >> 32) as u32])
+    }
+}
+
+impl From<i32> for BigInt {
+    fn from(x: i32) -> BigInt {
+	BigInt::from(x as i64)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+	match (self.negative, other.negative) {
+	    (false, true) => Ordering::Greater,
+	    (true, false) => Ordering::Less,
+	    (false, false) => cmp_mag(&self.digits, &other.digits),
+	    (true, true) => cmp_mag(&other.digits, &self.digits),
+	}
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+	Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	if self.is_zero() {
+	    return write!(f, "0");
+	}
+	// peel off nine decimal digits at a time
+	let mut chunks = Vec::new();
+	let mut rest = self.digits.clone();
+	while !rest.is_empty() {
+	    let (q, r) = divrem_small(&rest, 1_000_000_000);
+	    chunks.push(r);
+	    rest = q;
+	}
+	if self.negative {
+	    write!(f, "-")?;
+	}
+	write!(f, "{}", chunks[chunks.len() - 1])?;
+	for c in chunks.iter().rev().skip(1) {
+	    write!(f, "{:09}", c)?;
+	}
+	Ok(())
+    }
+}
+
+impl<'a> Add<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+    fn add(self, other: &BigInt) -> BigInt {
+	if self.negative == other.negative {
+	    return BigInt::from_parts(self.negative, add_mag(&self.digits, &other.digits));
+	}
+	match cmp_mag(&self.digits, &other.digits) {
+	    Ordering::Less => BigInt::from_parts(other.negative, sub_mag(&other.digits, &self.digits)),
+	    _ => BigInt::from_parts(self.negative, sub_mag(&self.digits, &other.digits)),
+	}
+    }
+}
+
+impl<'a> Sub<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+    fn sub(self, other: &BigInt) -> BigInt {
+	self + &(-other)
+    }
+}
+
+impl<'a> Mul<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+    fn mul(self, other: &BigInt) -> BigInt {
+	BigInt::from_parts(self.negative != other.negative, mul_mag(&self.digits, &other.digits))
+    }
+}
+
+impl<'a> Div<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+    fn div(self, other: &BigInt) -> BigInt {
+	self.div_rem(other).0
+    }
+}
+
+impl<'a> Rem<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+    fn rem(self, other: &BigInt) -> BigInt {
+	self.div_rem(other).1
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+    fn neg(self) -> BigInt {
+	BigInt::from_parts(!self.negative, self.digits.clone())
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+    fn neg(self) -> BigInt {
+	BigInt::from_parts(!self.negative, self.digits)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+	BigInt::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+	let a = big("123456789012345678901234567890");
+	let b = big("-987654321098765432109876543210");
+	assert_eq!((&a + &b).to_string(), "-864197532086419753208641975320");
+	assert_eq!((&a - &b).to_string(), "1111111110111111111011111111100");
+	assert_eq!((&a * &b).to_string(),
+		   "-121932631137021795226185032733622923332237463801111263526900");
+	assert_eq!(&a - &a, BigInt::zero());
+	assert!(b < a && BigInt::zero() > b);
+    }
+
+    #[test]
+    fn test_division() {
+	let a = big("121932631137021795226185032733622923332237463801111263526907");
+	let b = big("987654321098765432109876543210");
+	let (q, r) = a.div_rem(&b);
+	assert_eq!(q, big("123456789012345678901234567890"));
+	assert_eq!(r, big("7"));
+
+	// signs follow the primitive integers
+	let (q, r) = big("-7").div_rem(&big("2"));
+	assert_eq!((q, r), (big("-3"), big("-1")));
+
+	// a quotient digit estimate which needs correcting
+	let x = BigInt::one().shl(96);
+	let y = &BigInt::one().shl(64) + &BigInt::one();
+	let (q, r) = x.div_rem(&y);
+	assert_eq!(&(&q * &y) + &r, x);
+	assert!(r < y);
+    }
+
+    #[test]
+    fn test_conversions() {
+	assert_eq!(BigInt::from(-42).to_string(), "-42");
+	assert_eq!(big("-42").to_i64(), Some(-42));
+	assert_eq!(big(&i64::MIN.to_string()).to_i64(), Some(i64::MIN));
+	assert_eq!(big("9223372036854775808").to_i64(), None);
+	assert_eq!(BigInt::from_f64(1e20).unwrap().to_string(), "100000000000000000000");
+	assert_eq!(BigInt::from_f64(0.5), None);
+	assert_eq!(big("100000000000000000000").to_f64(), 1e20);
+	assert_eq!(BigInt::from(2).pow(2000).to_f64(), f64::INFINITY);
+	assert_eq!(BigInt::parse("12x"), None);
+    }
+
+    #[test]
+    fn test_gcd_and_roots() {
+	assert_eq!(big("462").gcd(&big("-1071")), big("21"));
+	assert_eq!(BigInt::from(3).pow(40).root(4), Some(BigInt::from(3).pow(10)));
+	assert_eq!(big("-27").root(3), Some(big("-3")));
+	assert_eq!(big("-4").root(2), None);
+	assert_eq!(big("17").root(2), None);
+    }
+}
+
+// end src/number/bigint.rs
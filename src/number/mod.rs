@@ -1,5 +1,8 @@
 // src/number/mod.rs
 
+#[allow(clippy::module_inception)]
 pub mod number;
+pub mod bigint;
+pub mod rational;
 
 // end src/number/mod.rs
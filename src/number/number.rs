@@ -1,23 +1,40 @@
 // src/number/number.rs
 
+use std::fmt;
 use std::ops::{Add,Sub,Mul,Div,Neg};
 
+use crate::error::*;
+use crate::error::MathError::*;
+use crate::number::bigint::*;
+use crate::number::rational::*;
 use self::Number::*;
 
 /// The primary Number type to use throughout the project.
-/// The Number holds three variants: NaN, Real and Complex.
+/// The Number holds five variants: NaN, the exact Integer and
+/// Rational, and the floating point Real and Complex.
 /// The `Expr` type will use `Number` as a placeholder for
 /// when equations need to be evaluated.
 ///
+/// Arithmetic between exact numbers stays exact, so `1/3` is kept
+/// as a fraction and `30!` doesn't overflow. Mixing in a Real or
+/// Complex, or applying a transcendental function like `sin` or
+/// `ln`, promotes the result to floating point.
+///
 /// ```
 /// use mathy::number::number::*;
 /// let ten = real(10.0);
-/// let hundred = ten * ten;
+/// let hundred = &ten * &ten;
 /// assert_eq!(hundred, real(100.0));
+///
+/// let third = integer(1) / integer(3);
+/// assert_eq!(third.to_string(), "1/3");
+/// assert_eq!(third * integer(3), integer(1));
 /// ```
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Number {
     NaN,
+    Integer(BigInt),
+    Rational(BigRational),
     Real(f64),
     Complex(f64, f64),
 }
@@ -26,16 +43,20 @@ pub enum Number {
 impl Number {
     /// Check if a number is NaN.
     pub fn is_nan(&self) -> bool {
-	match self {
-	    NaN => true,
-	    _ => false,
-	}
+	matches!(self, NaN)
+    }
+
+    /// Check if a number is an exact Integer or Rational.
+    pub fn is_exact(&self) -> bool {
+	matches!(self, Integer(_) | Rational(_))
     }
 
     /// Check if a number is zero (works for both Real/Complex)
     pub fn is_zero(&self) -> bool {
         match self {
 	    NaN => false,
+	    Integer(n) => n.is_zero(),
+	    Rational(q) => q.is_zero(),
             Real(x) => *x == 0.0,
             Complex(x, z) => *x == 0.0 && *z == 0.0,
         }
@@ -48,6 +69,8 @@ impl Number {
     pub fn is_whole(&self) -> bool {
 	match self {
 	    NaN => false,
+	    Integer(_) => true,
+	    Rational(q) => q.is_integer(),
 	    Real(x) => x - (x.round()) == 0.0,
 	    Complex(x, z) => x - (x.round()) == 0.0 && z - (z.round()) == 0.0,
 	}
@@ -57,6 +80,8 @@ impl Number {
     pub fn real(&self) -> f64 {
         match self {
 	    NaN => 0.0,
+	    Integer(n) => n.to_f64(),
+	    Rational(q) => q.to_f64(),
             Real(x) => *x,
             Complex(r, _) => *r,
         }
@@ -70,91 +95,230 @@ impl Number {
         }
     }
 
-    /// Format a Number into a std::String.
-    pub fn to_string(&self) -> String {
-        match self {
-	    NaN => "NaN".into(),
-            Real(x) => format!("{}", x),
-            Complex(x, z) => {
-                if *z < 0.0 {
-                    format!("{}{}j", x, z)
-                } else {
-                    format!("{}+{}j", x, z)
-                }
-            }
-        }
-    }
-
     /// Quick function to compare a Number to an f64
     pub fn real_eq(&self, v: f64) -> bool {
         match self {
             Real(x) => *x == v,
+	    Integer(_) | Rational(_) => *self == Real(v),
             _ => false,
         }
     }
 
     /// Another quick function to compare a Number to two f64 values
-    pub fn complex_eq(self, v1: f64, v2: f64) -> bool {
+    pub fn complex_eq(&self, v1: f64, v2: f64) -> bool {
         match self {
-            Complex(x, z) => x == v1 && z == v2,
+            Complex(x, z) => *x == v1 && *z == v2,
             _ => false,
         }
     }
     
-    // Big TODO: map all these functions onwards to their complex variants // 
+    /// Check if every component of a Number is finite. NaN returns false.
+    pub fn is_finite(&self) -> bool {
+	match self {
+	    NaN => false,
+	    Integer(_) | Rational(_) => true,
+	    Real(x) => x.is_finite(),
+	    Complex(x, z) => x.is_finite() && z.is_finite(),
+	}
+    }
+
+    /// The exact value as a fraction, or None for floating point.
+    pub fn to_rational(&self) -> Option<BigRational> {
+	match self {
+	    Integer(n) => Some(BigRational::from_integer(n.clone())),
+	    Rational(q) => Some(q.clone()),
+	    _ => None,
+	}
+    }
+
+    /// The same value as floating point, leaving Real, Complex and
+    /// NaN alone.
+    pub fn to_float(&self) -> Number {
+	match self {
+	    Integer(_) | Rational(_) => Real(self.real()),
+	    _ => self.clone(),
+	}
+    }
+
+    // Checked arithmetic, matching the std::ops implementations
+    // further down but reporting failures as a MathError.
+
+    pub fn try_add(self, other: Number) -> Result<Number, MathError> {
+	let result = &self + &other;
+	check_overflow("addition", &self, &other, result)
+    }
+
+    pub fn try_sub(self, other: Number) -> Result<Number, MathError> {
+	let result = &self - &other;
+	check_overflow("subtraction", &self, &other, result)
+    }
+
+    pub fn try_mul(self, other: Number) -> Result<Number, MathError> {
+	let result = &self * &other;
+	check_overflow("multiplication", &self, &other, result)
+    }
+
+    pub fn try_div(self, other: Number) -> Result<Number, MathError> {
+	if other.is_zero() {
+	    return Err(DivisionByZero);
+	}
+	let result = &self / &other;
+	check_overflow("division", &self, &other, result)
+    }
+    
+    /// The magnitude of a Number, |x| for reals and the modulus
+    /// for complex numbers. Always returns a real value (or NaN).
+    pub fn abs(&self) -> Number {
+	match self {
+	    NaN => NaN,
+	    Integer(n) => Integer(n.abs()),
+	    Rational(q) => Rational(q.abs()),
+	    Real(x) => Real(x.abs()),
+	    Complex(x, y) => Real(x.hypot(*y)),
+	}
+    }
+
+    /// The principal argument (angle) of a Number in (-pi, pi].
+    pub fn arg(&self) -> Number {
+	match self {
+	    NaN => NaN,
+	    Complex(x, y) => Real(y.atan2(*x)),
+	    _ => Real(0.0_f64.atan2(self.real())),
+	}
+    }
+
+    // All of the functions below take the principal branch for
+    // complex input. Real input outside of a function's real domain,
+    // like ln(-1) or asin(2), is promoted to a complex result the
+    // same way raising a negative number to a fractional power is.
+
+    /// The principal square root of a Number.
+    pub fn sqrt(&self) -> Number {
+	self.try_sqrt().unwrap_or(NaN)
+    }
+
+    pub fn try_sqrt(&self) -> Result<Number, MathError> {
+	// perfect squares like 9/4 keep an exact root
+	if let Some(q) = self.to_rational() {
+	    return match q.root(2) {
+		Some(r) => Ok(Number::from(r)),
+		None => self.to_float().try_sqrt(),
+	    };
+	}
+	match self {
+	    Real(x) if *x < 0.0 => Ok(Complex(0.0, (-x).sqrt())),
+	    _ => self.apply("sqrt", f64::sqrt, c_sqrt),
+	}
+    }
 
-    // substitute number functions to cover both real/imag
-    // complex:
     // sin(z) = sin(x+iy) = sin(x)cosh(y) + icos(x)sinh(y)
     pub fn sin(&self) -> Number { 
-        match self {
-            Real(x) => Real(x.sin()),
-            _ => Real(0.0),
-        }
+	self.try_sin().unwrap_or(NaN)
+    }
+
+    pub fn try_sin(&self) -> Result<Number, MathError> {
+	self.apply("sin", f64::sin, c_sin)
     }
     
-    // complex:
     // cos(z) = cos(x+iy) = cos(x)cosh(y) - isin(x)sinh(y)
     pub fn cos(&self) -> Number {
-        match self {
-            Real(x) => Real(x.cos()),
-            _ => Real(0.0),
-        }
+	self.try_cos().unwrap_or(NaN)
+    }
+
+    pub fn try_cos(&self) -> Result<Number, MathError> {
+	self.apply("cos", f64::cos, c_cos)
     }
 
     pub fn tan(&self) -> Number {
-	match self {
-	    Real(x) => Real(x.tan()),
-	    _ => Real(0.0),
-	}
+	self.try_tan().unwrap_or(NaN)
+    }
+
+    pub fn try_tan(&self) -> Result<Number, MathError> {
+	self.apply("tan", f64::tan, c_tan)
     }
 
+    // ln(z) = ln|z| + i*arg(z)
     pub fn ln(&self) -> Number {
-        match self {
-            Real(x) => Real(x.ln()),
-            _ => Real(0.0),
-        }
+	self.try_ln().unwrap_or(NaN)
+    }
+
+    pub fn try_ln(&self) -> Result<Number, MathError> {
+	if self.is_zero() {
+	    return Err(DomainError("ln(0) is undefined".into()));
+	}
+	match self {
+	    Integer(_) | Rational(_) => self.to_float().try_ln(),
+	    Real(x) if *x < 0.0 => Ok(Complex((-x).ln(), std::f64::consts::PI)),
+	    _ => self.apply("ln", f64::ln, c_ln),
+	}
     }
 
     /// Apply an exponentiation operation to a Number. This can
     /// result in imaginary numbers based on the power the Number is
     /// raised to.
     pub fn pow(&self, other: Number) -> Number {
-        match (self, other) {
+	self.try_pow(other).unwrap_or(NaN)
+    }
+
+    pub fn try_pow(&self, other: Number) -> Result<Number, MathError> {
+	if let (Some(base), Some(power)) = (self.to_rational(), other.to_rational()) {
+	    if let Some(result) = exact_pow(&base, &power)? {
+		return Ok(result);
+	    }
+	}
+
+        match (self.to_float(), other.to_float()) {
+	    (NaN, _) | (_, NaN) => Ok(NaN),
 	    (Real(base), Real(power)) => {
-		let log_test = power.log2();
-		
-		// check to see if we are raising to a negative power,
-		// which will give us a complex number if the power
-		// is of base log2, ie 1/2, 1/4, 1/8, 1/16, ... etc
-		if *base < 0.0 && log_test < 0.0 && (log_test - log_test.round()) == 0.0 {
-		    let new_base = base.abs();
-		    Complex(0.0, new_base.powf(power))
-		} else {
-		    Real(base.powf(power))
+		if base == 0.0 && power < 0.0 {
+		    return Err(DivisionByZero);
 		}
+
+		// a negative base with a fractional power has no real
+		// answer, so take the principal complex root instead
+		// (-b)^p = b^p * (cos(p*pi) + i*sin(p*pi))
+		if base < 0.0 && !Real(power).is_whole() && power.is_finite() {
+		    let magnitude = (-base).powf(power);
+		    let (c, s) = cos_sin_pi(power);
+		    return check_result("pow", self, Complex(magnitude * c, magnitude * s));
+		}
+
+		check_result("pow", self, Real(base.powf(power)))
+	    },
+	    (base, power) => {
+		// anything to the zero is one, even zero
+		if power.is_zero() {
+		    return Ok(Complex(1.0, 0.0));
+		}
+		if base.is_zero() {
+		    return if power.real() > 0.0 {
+			Ok(Complex(0.0, 0.0))
+		    } else {
+			Err(DivisionByZero)
+		    };
+		}
+
+		// small whole powers are done by repeated multiplication,
+		// which keeps results like (1+i)^2 = 2i exact
+		if let Real(p) = power {
+		    if Real(p).is_whole() && p.abs() <= 64.0 {
+			let z = to_c(&base);
+			let mut acc = (1.0, 0.0);
+			for _ in 0..(p.abs() as u32) {
+			    acc = c_mul(acc, z);
+			}
+			if p < 0.0 {
+			    acc = c_div((1.0, 0.0), acc);
+			}
+			return check_result("pow", &base, Complex(acc.0, acc.1));
+		    }
+		}
+
+		// z^w = e^(w * ln z)
+		let w = to_c(&power);
+		let (re, im) = c_exp(c_mul(w, c_ln(to_c(&base))));
+		check_result("pow", &base, Complex(re, im))
 	    },
-            _ => Real(0.0),
         }
     }
 
@@ -162,86 +326,146 @@ impl Number {
     /// Serves as a shortcut function to avoid having to manually
     /// wrap floats into Number variants.
     pub fn powf(&self, power: f64) -> Number {
-        match self {
-            Real(_) => self.pow(Real(power)), 
-            _ => Real(0.0),
-        }
+	self.pow(Real(power))
     }
 
+    pub fn try_powf(&self, power: f64) -> Result<Number, MathError> {
+	self.try_pow(Real(power))
+    }
+
+    // e^(x+iy) = e^x * (cos(y) + isin(y))
     pub fn exp(&self) -> Number {
-        match self {
-            Real(x) => Real(x.exp()),
-            _ => Real(0.0),
-        }
+	self.try_exp().unwrap_or(NaN)
+    }
+
+    pub fn try_exp(&self) -> Result<Number, MathError> {
+	self.apply("exp", f64::exp, c_exp)
     }
 
+    // sinh(x+iy) = sinh(x)cos(y) + icosh(x)sin(y)
     pub fn sinh(&self) -> Number {
-	match self {
-	    Real(x) => Real(x.sinh()),
-	    _ => Real(0.0),
-	}
+	self.try_sinh().unwrap_or(NaN)
+    }
+
+    pub fn try_sinh(&self) -> Result<Number, MathError> {
+	self.apply("sinh", f64::sinh, c_sinh)
     }
 
+    // cosh(x+iy) = cosh(x)cos(y) + isinh(x)sin(y)
     pub fn cosh(&self) -> Number {
-	match self {
-	    Real(x) => Real(x.cosh()),
-	    _ => Real(0.0),
-	}
+	self.try_cosh().unwrap_or(NaN)
+    }
+
+    pub fn try_cosh(&self) -> Result<Number, MathError> {
+	self.apply("cosh", f64::cosh, c_cosh)
     }
 
     pub fn tanh(&self) -> Number {
-	match self {
-	    Real(x) => Real(x.tanh()),
-	    _ => Real(0.0),
-	}
+	self.try_tanh().unwrap_or(NaN)
+    }
+
+    pub fn try_tanh(&self) -> Result<Number, MathError> {
+	self.apply("tanh", f64::tanh, c_tanh)
     }
 
+    // asin(z) = -i * ln(iz + sqrt(1 - z^2))
     pub fn asin(&self) -> Number {
+	self.try_asin().unwrap_or(NaN)
+    }
+
+    pub fn try_asin(&self) -> Result<Number, MathError> {
 	match self {
-	    Real(x) => Real(x.asin()),
-	    _ => Real(0.0),
+	    Integer(_) | Rational(_) => self.to_float().try_asin(),
+	    Real(x) if x.abs() > 1.0 => Complex(*x, 0.0).try_asin(),
+	    _ => self.apply("asin", f64::asin, c_asin),
 	}
     }
 
+    // acos(z) = pi/2 - asin(z)
     pub fn acos(&self) -> Number {
+	self.try_acos().unwrap_or(NaN)
+    }
+
+    pub fn try_acos(&self) -> Result<Number, MathError> {
 	match self {
-	    Real(x) => Real(x.acos()),
-	    _ => Real(0.0),
+	    Integer(_) | Rational(_) => self.to_float().try_acos(),
+	    Real(x) if x.abs() > 1.0 => Complex(*x, 0.0).try_acos(),
+	    _ => self.apply("acos", f64::acos, c_acos),
 	}
     }
 
+    // atan(z) = i/2 * (ln(1 - iz) - ln(1 + iz))
     pub fn atan(&self) -> Number {
-	match self {
-	    Real(x) => Real(x.atan()),
-	    _ => Real(0.0),
+	self.try_atan().unwrap_or(NaN)
+    }
+
+    pub fn try_atan(&self) -> Result<Number, MathError> {
+	if let Complex(x, y) = self {
+	    if *x == 0.0 && y.abs() == 1.0 {
+		return Err(DomainError(format!("atan({}) is undefined", self)));
+	    }
 	}
+	self.apply("atan", f64::atan, c_atan)
     }
 
     pub fn atan2(&self, other: Number) -> Number {
-	match (self, other) {
+	match (self.to_float(), other.to_float()) {
 	    (Real(x), Real(y)) => Real(x.atan2(y)),
-	    _ => Real(0.0),
+	    _ => NaN,
 	}
     }
 
+    // asinh(z) = ln(z + sqrt(z^2 + 1))
     pub fn asinh(&self) -> Number {
-	match self {
-	    Real(x) => Real(x.asinh()),
-	    _ => Real(0.0),
-	}
+	self.try_asinh().unwrap_or(NaN)
     }
 
+    pub fn try_asinh(&self) -> Result<Number, MathError> {
+	self.apply("asinh", f64::asinh, c_asinh)
+    }
+
+    // acosh(z) = ln(z + sqrt(z + 1) * sqrt(z - 1))
     pub fn acosh(&self) -> Number {
+	self.try_acosh().unwrap_or(NaN)
+    }
+
+    pub fn try_acosh(&self) -> Result<Number, MathError> {
 	match self {
-	    Real(x) => Real(x.acosh()),
-	    _ => Real(0.0),
+	    Integer(_) | Rational(_) => self.to_float().try_acosh(),
+	    Real(x) if *x < 1.0 => Complex(*x, 0.0).try_acosh(),
+	    _ => self.apply("acosh", f64::acosh, c_acosh),
 	}
     }
 
+    // atanh(z) = 1/2 * (ln(1 + z) - ln(1 - z))
     pub fn atanh(&self) -> Number {
+	self.try_atanh().unwrap_or(NaN)
+    }
+
+    pub fn try_atanh(&self) -> Result<Number, MathError> {
+	let (x, y) = to_c(self);
+	if x.abs() == 1.0 && y == 0.0 {
+	    return Err(DomainError(format!("atanh({}) is undefined", self)));
+	}
 	match self {
-	    Real(x) => Real(x.atanh()),
-	    _ => Real(0.0),
+	    Integer(_) | Rational(_) => self.to_float().try_atanh(),
+	    Real(x) if x.abs() > 1.0 => Complex(*x, 0.0).try_atanh(),
+	    _ => self.apply("atanh", f64::atanh, c_atanh),
+	}
+    }
+
+    // Apply a function to a Number using the f64 version for reals
+    // and the complex version otherwise, reporting domain errors
+    // and overflows instead of a raw NaN or infinity.
+    fn apply(&self, name: &str, f: fn(f64) -> f64, g: fn(C) -> C) -> Result<Number, MathError> {
+	match self {
+	    NaN => Ok(NaN),
+	    Integer(_) | Rational(_) => self.to_float().apply(name, f, g),
+	    Real(x) => check_result(name, self, Real(f(*x))),
+	    Complex(x, y) => {
+		let (re, im) = g((*x, *y));
+		check_result(name, self, Complex(re, im))
+	    },
 	}
     }
 
@@ -249,6 +473,7 @@ impl Number {
     // For real numbers this probably doesn't do anything
     pub fn conjugate(&self) -> Number {
 	match self {
+	    Integer(_) | Rational(_) => self.clone(),
 	    Real(x) => Real(*x),
 	    Complex(z, i) => Complex(*z, -i),
 	    _ => NaN,
@@ -257,56 +482,551 @@ impl Number {
 
     // Give a reciprocal for a given number
     pub fn reciprocal(&self) -> Number {
+	self.try_reciprocal().unwrap_or(NaN)
+    }
+
+    pub fn try_reciprocal(&self) -> Result<Number, MathError> {
 	match self {
-	    Real(x) => Real(1.0 / x),
+	    NaN => Ok(NaN),
+	    Integer(_) | Rational(_) => {
+		let r = self.to_rational().and_then(|q| q.recip());
+		r.map(Number::from).ok_or(DivisionByZero)
+	    },
+	    Real(x) => {
+		if *x == 0.0 {
+		    return Err(DivisionByZero);
+		}
+		Ok(Real(1.0 / x))
+	    },
 	    Complex(x, y) => {
 		let d = (x*x)+(y*y);
 		if d <= 0.0 {
-		    return NaN;
+		    return Err(DivisionByZero);
 		}
-		Complex(x/d, -(y/d))
+		Ok(Complex(x/d, -(y/d)))
 	    },
-	    _ => NaN,
 	}
     }
 
-    // TODO: add checks for floating point/complex numbers
-    // if given (2.1)! or a complex number z!, defer it
-    // to the Gamma function which uses integral estimation
+    /// Factorial of a Number. Whole numbers use the exact product,
+    /// anything else (like 2.5! or a complex z!) is Γ(x + 1). An
+    /// Integer gives back an exact Integer of any size.
     pub fn factorial(&self) -> Number {
+	self.try_factorial().unwrap_or(NaN)
+    }
+
+    pub fn try_factorial(&self) -> Result<Number, MathError> {
+	match self {
+	    NaN => Ok(NaN),
+	    Integer(n) => {
+		if n.is_negative() {
+		    return Err(DomainError(format!("factorial of negative integer {}", n)));
+		}
+		let n = match n.to_i64() {
+		    Some(n) if n <= MAX_EXACT_FACTORIAL => n,
+		    _ => return Err(Overflow(format!("{}!", n))),
+		};
+		let mut xs = BigInt::one();
+		for i in 2 ..= n {
+		    xs = &xs * &BigInt::from(i);
+		}
+		Ok(Integer(xs))
+	    },
+	    Real(x) if self.is_whole() => {
+		if *x < 0.0 {
+		    return Err(DomainError(format!("factorial of negative integer {}", x)));
+		}
+		// 170! is the largest factorial an f64 can hold
+		if *x > 170.0 {
+		    return Err(Overflow(format!("{}!", x)));
+		}
+		let mut xs : f64 = 1.0;
+		for i in 2 ..= (*x as u64) {
+		    xs *= i as f64;
+		}
+		Ok(Real(xs))
+	    },
+	    _ => (self + &Real(1.0)).try_gamma(),
+	}
+    }
+
+    /// The Gamma function, using the Lanczos approximation and the
+    /// reflection formula for Re(z) < 1/2. Γ(n) = (n-1)! for whole n.
+    pub fn gamma(&self) -> Number {
+	self.try_gamma().unwrap_or(NaN)
+    }
+
+    pub fn try_gamma(&self) -> Result<Number, MathError> {
+	self.check_pole("gamma")?;
 	match self {
+	    NaN => Ok(NaN),
+	    Integer(n) => Integer(n - &BigInt::one()).try_factorial(),
+	    Rational(_) => self.to_float().try_gamma(),
 	    Real(x) => {
-		let upper = (x+1.0) as usize;
-		if upper > 100 {
-		    panic!("Factorial too large for data type")
+		if self.is_whole() && *x <= 171.0 {
+		    return Real(x - 1.0).try_factorial();
 		}
-		let mut xs : u64 = 1;
-		for i in 1 .. upper {
-		    xs *= i as u64;
+		if *x > 171.7 {
+		    return Err(Overflow(format!("gamma({})", x)));
 		}
-		Real(xs as f64)
+		let (re, _) = c_gamma((*x, 0.0));
+		check_result("gamma", self, Real(re))
+	    },
+	    Complex(x, y) => {
+		let (re, im) = c_gamma((*x, *y));
+		check_result("gamma", self, Complex(re, im))
 	    },
-	    Complex(x, z) => complex(*x, *z).gamma(10),
-	    _ => NaN,
 	}
     }
 
-    // TODO: implement gamma function with estimation
-    pub fn gamma(&self, _iters: usize) -> Number {
+    /// The natural log of the Gamma function. For negative reals this
+    /// is ln|Γ(x)|, and for complex input Re(z) < 1/2 is found by
+    /// reflection. Stays finite where Γ itself would overflow.
+    pub fn ln_gamma(&self) -> Number {
+	self.try_ln_gamma().unwrap_or(NaN)
+    }
+
+    pub fn try_ln_gamma(&self) -> Result<Number, MathError> {
+	self.check_pole("ln_gamma")?;
 	match self {
-	    Complex(_x, _z) => {
-		real(0.0)
+	    NaN => Ok(NaN),
+	    Integer(_) | Rational(_) => self.to_float().try_ln_gamma(),
+	    Real(x) => {
+		if *x >= 0.5 {
+		    return check_result("ln_gamma", self, Real(c_ln_gamma((*x, 0.0)).0));
+		}
+		// ln|Γ(x)| = ln(pi) - ln|sin(pi x)| - lnΓ(1 - x)
+		let (lg, _) = c_ln_gamma((1.0 - x, 0.0));
+		let s = (std::f64::consts::PI * x).sin().abs();
+		check_result("ln_gamma", self, Real(std::f64::consts::PI.ln() - s.ln() - lg))
+	    },
+	    Complex(x, y) => {
+		let z = (*x, *y);
+		let (re, im) = if *x >= 0.5 {
+		    c_ln_gamma(z)
+		} else {
+		    let pi = std::f64::consts::PI;
+		    let s = c_ln(c_sin(c_scale(z, pi)));
+		    c_sub(c_sub((pi.ln(), 0.0), s), c_ln_gamma(c_sub((1.0, 0.0), z)))
+		};
+		check_result("ln_gamma", self, Complex(re, im))
 	    },
-	    _ => NaN,
 	}
     }
+
+    /// The Beta function B(a, b) = Γ(a)Γ(b) / Γ(a + b).
+    pub fn beta(&self, other: Number) -> Number {
+	self.try_beta(other).unwrap_or(NaN)
+    }
+
+    pub fn try_beta(&self, other: Number) -> Result<Number, MathError> {
+	match (self, &other) {
+	    (NaN, _) | (_, NaN) => Ok(NaN),
+	    // work in logs to avoid overflowing for large arguments
+	    (Real(a), Real(b)) if *a > 0.0 && *b > 0.0 => {
+		let l = self.try_ln_gamma()? + other.try_ln_gamma()? - Real(a + b).try_ln_gamma()?;
+		l.try_exp()
+	    },
+	    _ => {
+		let num = self.try_gamma()?.try_mul(other.try_gamma()?)?;
+		match (self + &other).try_gamma() {
+		    // Γ(a + b) has a pole, so B(a, b) goes to zero
+		    Err(DomainError(_)) => Ok(Real(0.0)),
+		    Err(e) => Err(e),
+		    Ok(den) => num.try_div(den),
+		}
+	    },
+	}
+    }
+
+    /// The digamma function ψ(x), the derivative of ln Γ(x).
+    pub fn digamma(&self) -> Number {
+	self.try_digamma().unwrap_or(NaN)
+    }
+
+    pub fn try_digamma(&self) -> Result<Number, MathError> {
+	self.try_polygamma(0)
+    }
+
+    /// The polygamma function ψ^(n)(x), the nth derivative of the
+    /// digamma function. Order 0 is digamma itself.
+    pub fn polygamma(&self, n: u32) -> Number {
+	self.try_polygamma(n).unwrap_or(NaN)
+    }
+
+    pub fn try_polygamma(&self, n: u32) -> Result<Number, MathError> {
+	let name = if n == 0 { "digamma" } else { "polygamma" };
+	self.check_pole(name)?;
+	let z = match self {
+	    NaN => return Ok(NaN),
+	    Complex(x, y) => (*x, *y),
+	    _ => (self.real(), 0.0),
+	};
+
+	let result = if n == 0 {
+	    c_digamma(z)
+	} else {
+	    if z.0 < -1e5 {
+		return Err(Unsupported(format!("polygamma of large negative {}", self)));
+	    }
+	    c_polygamma(n, z)
+	};
+
+	match self {
+	    Complex(_, _) => check_result(name, self, Complex(result.0, result.1)),
+	    _ => check_result(name, self, Real(result.0)),
+	}
+    }
+
+    // The gamma family has poles at zero and the negative integers
+    fn check_pole(&self, name: &str) -> Result<(), MathError> {
+	let (x, y) = to_c(self);
+	if y == 0.0 && x <= 0.0 && x == x.round() {
+	    return Err(DomainError(format!("{} has a pole at {}", name, x)));
+	}
+	Ok(())
+    }
+}
+
+// Exact results past these sizes would take too long to compute,
+// so factorials report an Overflow and powers fall back to floats
+const MAX_EXACT_FACTORIAL: i64 = 10_000;
+const MAX_EXACT_BITS: i64 = 1 << 16;
+
+// Exact powers of exact numbers, including roots which come out
+// exact like 8^(2/3) = 4. None means floating point is needed,
+// which includes roots of negative numbers, so that they give the
+// same principal value as a float base would.
+fn exact_pow(base: &BigRational, power: &BigRational) -> Result<Option<Number>, MathError> {
+    let n = match power.numer().to_i64() {
+	Some(n) => n,
+	None => return Ok(None),
+    };
+    let bits = base.numer().bits().max(base.denom().bits()) as i64;
+    if n.abs() > MAX_EXACT_BITS || bits * n.abs() > MAX_EXACT_BITS {
+	return Ok(None);
+    }
+    let root = match power.denom().to_i64() {
+	Some(1) => base.clone(),
+	Some(k) if k <= 64 && !base.is_negative() => match base.root(k as u32) {
+	    Some(r) => r,
+	    None => return Ok(None),
+	},
+	_ => return Ok(None),
+    };
+    match root.pow(n) {
+	Some(r) => Ok(Some(Number::from(r))),
+	None => Err(DivisionByZero),
+    }
+}
+
+// Report an Overflow when finite inputs give a non-finite result
+fn check_overflow(name: &str, a: &Number, b: &Number, result: Number) -> Result<Number, MathError> {
+    if a.is_finite() && b.is_finite() && !result.is_finite() {
+	Err(Overflow(format!("{} of {} and {}", name, a, b)))
+    } else {
+	Ok(result)
+    }
+}
+
+// Report a DomainError for NaN components and an Overflow for infinite
+// components when the input was finite
+fn check_result(name: &str, input: &Number, result: Number) -> Result<Number, MathError> {
+    let has_nan = match result {
+	Real(x) => x.is_nan(),
+	Complex(x, y) => x.is_nan() || y.is_nan(),
+	_ => false,
+    };
+    if has_nan && !input.is_nan() && input.is_finite() {
+	Err(DomainError(format!("{}({}) is undefined", name, input)))
+    } else if input.is_finite() && !result.is_finite() && !result.is_nan() {
+	Err(Overflow(format!("{}({})", name, input)))
+    } else {
+	Ok(result)
+    }
+}
+
+
+// Complex helpers working on (real, imaginary) pairs, so the
+// formulas above can be written without wrapping every step.
+type C = (f64, f64);
+
+fn to_c(n: &Number) -> C {
+    (n.real(), n.imag())
+}
+
+fn c_add(a: C, b: C) -> C { (a.0 + b.0, a.1 + b.1) }
+fn c_sub(a: C, b: C) -> C { (a.0 - b.0, a.1 - b.1) }
+fn c_mul(a: C, b: C) -> C { (a.0*b.0 - a.1*b.1, a.0*b.1 + a.1*b.0) }
+fn c_scale(a: C, k: f64) -> C { (a.0 * k, a.1 * k) }
+
+fn c_div(a: C, b: C) -> C {
+    let d = b.0*b.0 + b.1*b.1;
+    ((a.0*b.0 + a.1*b.1) / d, (a.1*b.0 - a.0*b.1) / d)
+}
+
+fn c_exp(z: C) -> C {
+    let m = z.0.exp();
+    (m * z.1.cos(), m * z.1.sin())
+}
+
+fn c_ln(z: C) -> C {
+    (z.0.hypot(z.1).ln(), z.1.atan2(z.0))
+}
+
+fn c_sqrt(z: C) -> C {
+    let r = z.0.hypot(z.1);
+    if r == 0.0 {
+	return (0.0, 0.0);
+    }
+    let t = ((r + z.0.abs()) / 2.0).sqrt();
+    if z.0 >= 0.0 {
+	(t, z.1 / (2.0 * t))
+    } else {
+	(z.1.abs() / (2.0 * t), t.copysign(z.1))
+    }
+}
+
+fn c_sin(z: C) -> C { (z.0.sin() * z.1.cosh(), z.0.cos() * z.1.sinh()) }
+fn c_cos(z: C) -> C { (z.0.cos() * z.1.cosh(), -(z.0.sin() * z.1.sinh())) }
+fn c_tan(z: C) -> C { c_div(c_sin(z), c_cos(z)) }
+fn c_sinh(z: C) -> C { (z.0.sinh() * z.1.cos(), z.0.cosh() * z.1.sin()) }
+fn c_cosh(z: C) -> C { (z.0.cosh() * z.1.cos(), z.0.sinh() * z.1.sin()) }
+fn c_tanh(z: C) -> C { c_div(c_sinh(z), c_cosh(z)) }
+
+fn c_asin(z: C) -> C {
+    let iz = (-z.1, z.0);
+    let root = c_sqrt(c_sub((1.0, 0.0), c_mul(z, z)));
+    let l = c_ln(c_add(iz, root));
+    (l.1, -l.0)
+}
+
+fn c_acos(z: C) -> C {
+    c_sub((std::f64::consts::FRAC_PI_2, 0.0), c_asin(z))
+}
+
+fn c_atan(z: C) -> C {
+    let iz = (-z.1, z.0);
+    let d = c_sub(c_ln(c_sub((1.0, 0.0), iz)), c_ln(c_add((1.0, 0.0), iz)));
+    // multiply by i/2
+    (-d.1 / 2.0, d.0 / 2.0)
+}
+
+fn c_asinh(z: C) -> C {
+    c_ln(c_add(z, c_sqrt(c_add(c_mul(z, z), (1.0, 0.0)))))
+}
+
+fn c_acosh(z: C) -> C {
+    let root = c_mul(c_sqrt(c_add(z, (1.0, 0.0))), c_sqrt(c_sub(z, (1.0, 0.0))));
+    c_ln(c_add(z, root))
+}
+
+fn c_atanh(z: C) -> C {
+    let d = c_sub(c_ln(c_add((1.0, 0.0), z)), c_ln(c_sub((1.0, 0.0), z)));
+    c_scale(d, 0.5)
+}
+
+// Lanczos approximation coefficients for g = 7, n = 9
+const LANCZOS_G: f64 = 7.0;
+const LANCZOS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+// ln Γ(z) from the Lanczos series, valid for Re(z) >= 1/2
+fn c_ln_gamma(z: C) -> C {
+    let z = c_sub(z, (1.0, 0.0));
+    let mut sum = (LANCZOS[0], 0.0);
+    for (i, p) in LANCZOS.iter().enumerate().skip(1) {
+	sum = c_add(sum, c_div((*p, 0.0), c_add(z, (i as f64, 0.0))));
+    }
+    let t = c_add(z, (LANCZOS_G + 0.5, 0.0));
+    let half_ln_2pi = 0.5 * (2.0 * std::f64::consts::PI).ln();
+    let a = c_mul(c_add(z, (0.5, 0.0)), c_ln(t));
+    c_add(c_sub(c_add((half_ln_2pi, 0.0), a), t), c_ln(sum))
+}
+
+// Γ(z) everywhere off the poles, reflecting into Re(z) >= 1/2
+fn c_gamma(z: C) -> C {
+    if z.0 < 0.5 {
+	// Γ(z) = pi / (sin(pi z) Γ(1 - z))
+	let pi = std::f64::consts::PI;
+	let s = c_sin(c_scale(z, pi));
+	c_div((pi, 0.0), c_mul(s, c_gamma(c_sub((1.0, 0.0), z))))
+    } else {
+	c_exp(c_ln_gamma(z))
+    }
+}
+
+fn c_powi(z: C, n: u32) -> C {
+    let mut acc = (1.0, 0.0);
+    for _ in 0..n {
+	acc = c_mul(acc, z);
+    }
+    acc
+}
+
+fn factorial_f64(n: u32) -> f64 {
+    (2..=n).fold(1.0, |acc, i| acc * i as f64)
+}
+
+// Even Bernoulli numbers B2, B4, ... B14 for the asymptotic series
+const BERNOULLI: [f64; 7] = [
+    1.0 / 6.0, -1.0 / 30.0, 1.0 / 42.0, -1.0 / 30.0,
+    5.0 / 66.0, -691.0 / 2730.0, 7.0 / 6.0,
+];
+
+// ψ(z) by shifting z up with ψ(z) = ψ(z + 1) - 1/z and then using
+// the asymptotic expansion, reflecting negative real parts first
+fn c_digamma(z: C) -> C {
+    if z.0 < 0.0 {
+	// ψ(z) = ψ(1 - z) - pi cot(pi z)
+	let pi = std::f64::consts::PI;
+	let cot = c_div(c_cos(c_scale(z, pi)), c_sin(c_scale(z, pi)));
+	return c_sub(c_digamma(c_sub((1.0, 0.0), z)), c_scale(cot, pi));
+    }
+
+    let mut z = z;
+    let mut acc = (0.0, 0.0);
+    while z.0 < 10.0 {
+	acc = c_sub(acc, c_div((1.0, 0.0), z));
+	z = c_add(z, (1.0, 0.0));
+    }
+
+    // ψ(z) ~ ln z - 1/2z - sum B2k / (2k z^2k)
+    let mut series = c_sub(c_ln(z), c_div((0.5, 0.0), z));
+    for (k, b) in BERNOULLI.iter().enumerate() {
+	let k2 = 2 * (k as u32 + 1);
+	let term = c_div((b / k2 as f64, 0.0), c_powi(z, k2));
+	series = c_sub(series, term);
+    }
+    c_add(acc, series)
+}
+
+// ψ^(n)(z) for n >= 1 using the same shift and asymptotic approach
+fn c_polygamma(n: u32, z: C) -> C {
+    let sign = if n % 2 == 1 { 1.0 } else { -1.0 };
+    let n_fact = factorial_f64(n);
+
+    let mut z = z;
+    let mut acc = (0.0, 0.0);
+    while z.0 < 15.0 {
+	acc = c_add(acc, c_div((n_fact, 0.0), c_powi(z, n + 1)));
+	z = c_add(z, (1.0, 0.0));
+    }
+
+    // (n-1)!/z^n + n!/(2z^(n+1)) + sum B2k (2k+n-1)! / ((2k)! z^(2k+n))
+    let mut series = c_add(
+	c_div((factorial_f64(n - 1), 0.0), c_powi(z, n)),
+	c_div((n_fact / 2.0, 0.0), c_powi(z, n + 1)),
+    );
+    for (k, b) in BERNOULLI.iter().enumerate() {
+	let k2 = 2 * (k as u32 + 1);
+	let coeff = b * factorial_f64(k2 + n - 1) / factorial_f64(k2);
+	series = c_add(series, c_div((coeff, 0.0), c_powi(z, k2 + n)));
+    }
+    c_scale(c_add(acc, series), sign)
+}
+
+// cos(p*pi) and sin(p*pi), exact when p is a multiple of 1/2
+// so that results like sqrt(-4) = 2i carry no rounding noise
+fn cos_sin_pi(p: f64) -> (f64, f64) {
+    let t = p.rem_euclid(2.0);
+    if t == 0.0 {
+	(1.0, 0.0)
+    } else if t == 0.5 {
+	(0.0, 1.0)
+    } else if t == 1.0 {
+	(-1.0, 0.0)
+    } else if t == 1.5 {
+	(0.0, -1.0)
+    } else {
+	let a = t * std::f64::consts::PI;
+	(a.cos(), a.sin())
+    }
+}
+
+/// Format a Number for printing.
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+	    NaN => write!(f, "NaN"),
+	    Integer(n) => write!(f, "{}", n),
+	    Rational(q) => write!(f, "{}", q),
+            Real(x) => write!(f, "{}", x),
+            Complex(x, z) => {
+		if *x == 0.0 {
+		    write!(f, "{}j", z)
+		} else if *z < 0.0 {
+                    write!(f, "{}{}j", x, z)
+                } else {
+                    write!(f, "{}+{}j", x, z)
+                }
+            }
+        }
+    }
+}
+
+/// Numbers compare by value, so `integer(2) == real(2.0)` and a
+/// Rational equals a Real only when the f64 is exactly that
+/// fraction. Complex numbers only equal other Complex numbers.
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+	match (self, other) {
+	    (NaN, NaN) => true,
+	    (Integer(a), Integer(b)) => a == b,
+	    (Rational(a), Rational(b)) => a == b,
+	    (Real(x), Real(y)) => x == y,
+	    (Complex(x, z), Complex(u, v)) => x == u && z == v,
+	    (Real(x), n) | (n, Real(x)) if n.is_exact() => {
+		BigRational::from_f64(*x).map(|q| Some(q) == n.to_rational()).unwrap_or(false)
+	    },
+	    _ => false,
+	}
+    }
+}
+
+impl From<BigInt> for Number {
+    fn from(n: BigInt) -> Number {
+	Integer(n)
+    }
+}
+
+/// Fractions with a denominator of one become an Integer.
+impl From<BigRational> for Number {
+    fn from(q: BigRational) -> Number {
+	if q.is_integer() {
+	    Integer(q.numer().clone())
+	} else {
+	    Rational(q)
+	}
+    }
+}
+
+// Exact numbers stay exact with each other, everything else is
+// done in floating point
+fn both_exact(a: &Number, b: &Number) -> Option<(BigRational, BigRational)> {
+    Some((a.to_rational()?, b.to_rational()?))
 }
 
 // TODO: finish arithmetic for pretty much everything
-impl Add for Number {
+impl<'a> Add<&'a Number> for &'a Number {
     type Output = Number;
-    fn add(self, other: Number) -> Number {
-        match (self, other) {
+    fn add(self, other: &Number) -> Number {
+	if let (Integer(a), Integer(b)) = (self, other) {
+	    return Integer(a + b);
+	}
+	if let Some((a, b)) = both_exact(self, other) {
+	    return Number::from(&a + &b);
+	}
+        match (self.to_float(), other.to_float()) {
             (Real(x), Real(y)) => Real(x + y),
             (Real(x), Complex(z, i)) => Complex(x + z, i),
 	    (Complex(z, i), Real(x)) => Complex(x + z, i),
@@ -316,23 +1036,35 @@ impl Add for Number {
     }
 }
 
-impl Sub for Number {
+impl<'a> Sub<&'a Number> for &'a Number {
     type Output = Number;
-    fn sub(self, other: Number) -> Number {
-        match (self, other) {
+    fn sub(self, other: &Number) -> Number {
+	if let (Integer(a), Integer(b)) = (self, other) {
+	    return Integer(a - b);
+	}
+	if let Some((a, b)) = both_exact(self, other) {
+	    return Number::from(&a - &b);
+	}
+        match (self.to_float(), other.to_float()) {
             (Real(x), Real(y)) => Real(x - y),
-            (Real(x), Complex(z, i)) => Complex(x - z, i),
-	    (Complex(z, i), Real(x)) => Complex(x - z, i),
+            (Real(x), Complex(z, i)) => Complex(x - z, -i),
+	    (Complex(z, i), Real(x)) => Complex(z - x, i),
 	    (Complex(z1, i1), Complex(z2, i2)) => Complex(z1 - z2, i1 - i2),
 	    _ => NaN,
         }
     }
 }
 
-impl Mul for Number {
+impl<'a> Mul<&'a Number> for &'a Number {
     type Output = Number;
-    fn mul(self, other: Number) -> Number {
-        match (self, other) {
+    fn mul(self, other: &Number) -> Number {
+	if let (Integer(a), Integer(b)) = (self, other) {
+	    return Integer(a * b);
+	}
+	if let Some((a, b)) = both_exact(self, other) {
+	    return Number::from(&a * &b);
+	}
+        match (self.to_float(), other.to_float()) {
             (Real(x), Real(y)) => Real(x * y),
 	    (Real(x), Complex(u, v)) => Complex(x*u, x*v),
 	    (Complex(x, y), Real(u)) => Complex(x*u, y*u),
@@ -342,10 +1074,16 @@ impl Mul for Number {
     }
 }
 
-impl Div for Number {
+impl<'a> Div<&'a Number> for &'a Number {
     type Output = Number;
-    fn div(self, other: Number) -> Number {
-        match (self, other) {
+    fn div(self, other: &Number) -> Number {
+	if let Some((a, b)) = both_exact(self, other) {
+	    return match b.recip() {
+		Some(r) => Number::from(&a * &r),
+		None => NaN,
+	    };
+	}
+        match (self.to_float(), other.to_float()) {
             (Real(x), Real(y)) => {
                 if y == 0.0 {
 		    return NaN;
@@ -360,11 +1098,10 @@ impl Div for Number {
 		Complex((u*x)/d, -(u*y)/d)
 	    },
 	    (Complex(u, v), Real(x)) => {
-		let d = x*x;
-		if d == 0.0 {
+		if x == 0.0 {
 		    return NaN;
 		}
-		Complex((u*x)/d, -(v*x)/d)
+		Complex(u/x, v/x)
 	    },
 	    (Complex(u, v), Complex(x, y)) => {
 		let d = (x*x) + (y*y);
@@ -378,20 +1115,63 @@ impl Div for Number {
     }
 }
 
-impl Neg for Number {
+impl Neg for &Number {
     type Output = Number;
     fn neg(self) -> Number {
         match self {
+	    Integer(n) => Integer(-n),
+	    Rational(q) => Rational(-q),
             Real(x) => Real(-x),
             Complex(z, i) => Complex(-z, -i),
-	    _ => NaN,
+	    NaN => NaN,
         }
     }
 }
 
+// The owned operators borrow, so both `a + b` and `&a + &b` work
+
+impl Add for Number {
+    type Output = Number;
+    fn add(self, other: Number) -> Number {
+	&self + &other
+    }
+}
+
+impl Sub for Number {
+    type Output = Number;
+    fn sub(self, other: Number) -> Number {
+	&self - &other
+    }
+}
+
+impl Mul for Number {
+    type Output = Number;
+    fn mul(self, other: Number) -> Number {
+	&self * &other
+    }
+}
+
+impl Div for Number {
+    type Output = Number;
+    fn div(self, other: Number) -> Number {
+	&self / &other
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+    fn neg(self) -> Number {
+	-&self
+    }
+}
+
 
 // Shortcut functions for ease of use
 pub fn nan() -> Number { NaN }
+pub fn integer(n: i64) -> Number { Integer(BigInt::from(n)) }
+pub fn rational(n: i64, d: i64) -> Number {
+    Number::from(BigRational::new(n.into(), d.into()))
+}
 pub fn real(x: f64) -> Number { Real(x) }
 pub fn imag(x: f64) -> Number { Complex(0.0, x) }
 pub fn complex(x: f64, z: f64) -> Number {
@@ -436,6 +1216,172 @@ mod test {
 	let c2 = real(120.0);
 	assert_eq!(c2, c1.factorial());
     }
+
+    #[test]
+    fn try_errors_test() {
+	assert_eq!(real(1.0).try_div(real(0.0)), Err(DivisionByZero));
+	assert!(matches!(real(0.0).try_ln(), Err(DomainError(_))));
+	assert!(matches!(real(1.0).try_atanh(), Err(DomainError(_))));
+	assert!(matches!(real(1000.0).try_exp(), Err(Overflow(_))));
+	assert!(matches!(real(171.0).try_factorial(), Err(Overflow(_))));
+	assert!(matches!(real(-3.0).try_factorial(), Err(DomainError(_))));
+	assert!(matches!(real(f64::MAX).try_mul(real(2.0)), Err(Overflow(_))));
+	assert!(matches!(complex(0.0, 0.0).try_pow(real(-1.0)), Err(DivisionByZero)));
+
+	// a NaN going in is a legitimate NaN coming out
+	assert_eq!(nan().try_sin(), Ok(nan()));
+	assert_eq!(real(170.0).try_factorial().map(|x| x.is_finite()), Ok(true));
+    }
+
+    fn close(a: Number, b: Number) -> bool {
+	(a - b).abs().real() < 1e-12
+    }
+
+    #[test]
+    fn complex_functions_test() {
+	let z = complex(1.0, 1.0);
+	assert!(close(z.sin(), complex(1.2984575814159773, 0.6349639147847361)));
+	assert!(close(z.cos(), complex(0.8337300251311491, -0.9888977057628651)));
+	assert!(close(z.exp(), complex(1.4686939399158851, 2.2873552871788423)));
+	assert!(close(z.ln(), complex(0.34657359027997264, std::f64::consts::FRAC_PI_4)));
+	assert!(close(z.sinh(), complex(0.6349639147847361, 1.2984575814159773)));
+
+	// e^(i*pi) = -1
+	assert!(close(imag(std::f64::consts::PI).exp(), complex(-1.0, 0.0)));
+	assert_eq!(real(-1.0).ln(), complex(0.0, std::f64::consts::PI));
+    }
+
+    #[test]
+    fn complex_inverse_test() {
+	let zs = [complex(0.5, 0.25), complex(-1.5, 2.0), complex(3.0, -0.5)];
+	for z in zs.iter() {
+	    assert!(close(z.asin().sin(), z.clone()), "asin {}", z);
+	    assert!(close(z.acos().cos(), z.clone()), "acos {}", z);
+	    assert!(close(z.atan().tan(), z.clone()), "atan {}", z);
+	    assert!(close(z.asinh().sinh(), z.clone()), "asinh {}", z);
+	    assert!(close(z.acosh().cosh(), z.clone()), "acosh {}", z);
+	    assert!(close(z.atanh().tanh(), z.clone()), "atanh {}", z);
+	    assert!(close(z.sqrt() * z.sqrt(), z.clone()), "sqrt {}", z);
+	}
+
+	// real input outside the real domain promotes to complex
+	let a = real(2.0).asin();
+	assert!(close(a, complex(std::f64::consts::FRAC_PI_2, -1.3169578969248166)));
+	assert!(close(real(0.5).acosh(), complex(0.0, std::f64::consts::FRAC_PI_3)));
+    }
+
+    #[test]
+    fn complex_pow_test() {
+	// principal cube root of -8
+	assert!(close(real(-8.0).powf(1.0 / 3.0), complex(1.0, 1.7320508075688772)));
+	assert!(close(real(-16.0).powf(0.25), complex(std::f64::consts::SQRT_2, std::f64::consts::SQRT_2)));
+	assert_eq!(real(-2.0).powf(3.0), real(-8.0));
+	assert_eq!(complex(1.0, 1.0).powf(2.0), complex(0.0, 2.0));
+	assert!(close(i().pow(i()), complex((-std::f64::consts::FRAC_PI_2).exp(), 0.0)));
+	assert!(close(real(2.0).pow(i()), complex(0.7692389013639721, 0.6389612763136348)));
+	assert!(close(complex(1.0, 2.0).pow(complex(0.5, -1.0)),
+		      (complex(1.0, 2.0).ln() * complex(0.5, -1.0)).exp()));
+
+	// zero to the zero is one, as it is for reals
+	assert_eq!(complex(0.0, 0.0).try_pow(real(0.0)), Ok(complex(1.0, 0.0)));
+	assert_eq!(complex(0.0, 0.0).try_pow(complex(0.0, 0.0)), Ok(complex(1.0, 0.0)));
+	assert_eq!(real(0.0).try_pow(real(0.0)), Ok(real(1.0)));
+	assert!(matches!(complex(0.0, 0.0).try_pow(real(-1.0)), Err(DivisionByZero)));
+    }
+
+    #[test]
+    fn complex_arithmetic_test() {
+	assert_eq!(real(1.0) - complex(2.0, 3.0), complex(-1.0, -3.0));
+	assert_eq!(complex(2.0, 3.0) - real(1.0), complex(1.0, 3.0));
+	assert_eq!(complex(2.0, 4.0) / real(2.0), complex(1.0, 2.0));
+    }
+
+    fn rel_close(a: Number, b: Number) -> bool {
+	(&a - &b).abs().real() <= 1e-12 * b.abs().real().max(1.0)
+    }
+
+    #[test]
+    fn gamma_test() {
+	assert_eq!(real(5.0).gamma(), real(24.0));
+	assert!(rel_close(real(0.5).gamma(), real(std::f64::consts::PI.sqrt())));
+	assert!(rel_close(real(-0.5).gamma(), real(-2.0 * std::f64::consts::PI.sqrt())));
+	assert!(rel_close(real(10.1).gamma(), real(454_760.751_441_586_5)));
+	assert!(rel_close(complex(1.0, 1.0).gamma(), complex(0.498_015_668_118_356, -0.154_949_828_301_810_7)));
+	assert!(matches!(real(-2.0).try_gamma(), Err(DomainError(_))));
+	assert!(matches!(real(0.0).try_gamma(), Err(DomainError(_))));
+	assert!(matches!(real(200.0).try_gamma(), Err(Overflow(_))));
+    }
+
+    #[test]
+    fn non_integer_factorial_test() {
+	// 2.5! = Γ(3.5) = 15 sqrt(pi) / 8
+	let expected = 15.0 * std::f64::consts::PI.sqrt() / 8.0;
+	assert!(rel_close(real(2.5).factorial(), real(expected)));
+	assert!(rel_close(i().factorial(), complex(1.0, 1.0).gamma()));
+	assert!(matches!(real(-1.0).try_factorial(), Err(DomainError(_))));
+    }
+
+    #[test]
+    fn gamma_family_test() {
+	assert!(rel_close(real(200.0).ln_gamma(), real(857.933_669_825_857_5)));
+	assert!(rel_close(real(-0.5).ln_gamma(), real((2.0 * std::f64::consts::PI.sqrt()).ln())));
+	assert!(rel_close(real(2.0).beta(real(3.0)), real(1.0 / 12.0)));
+	assert!(rel_close(real(-0.5).beta(real(2.0)), real(-4.0)));
+
+	// ψ(1) = -γ and ψ(1/2) = -γ - 2 ln 2
+	let euler_gamma = 0.577_215_664_901_532_9;
+	assert!(rel_close(real(1.0).digamma(), real(-euler_gamma)));
+	assert!(rel_close(real(0.5).digamma(), real(-euler_gamma - 2.0 * 2.0_f64.ln())));
+	assert!(rel_close(real(-0.5).digamma(), real(0.036_489_973_978_576_52)));
+
+	// ψ'(1) = pi^2 / 6 and ψ''(1) = -2 ζ(3)
+	let pi2 = std::f64::consts::PI * std::f64::consts::PI;
+	assert!(rel_close(real(1.0).polygamma(1), real(pi2 / 6.0)));
+	assert!(rel_close(real(1.0).polygamma(2), real(-2.0 * 1.202_056_903_159_594_2)));
+	assert!(matches!(real(-3.0).try_digamma(), Err(DomainError(_))));
+    }
+
+    #[test]
+    fn exact_arithmetic_test() {
+	let third = integer(1) / integer(3);
+	assert_eq!(third, rational(1, 3));
+	assert_eq!(third.to_string(), "1/3");
+	assert_eq!(&third + &rational(2, 3), integer(1));
+	assert!(matches!(&third + &rational(2, 3), Integer(_)));
+	assert_eq!(rational(1, 10) + rational(2, 10), rational(3, 10));
+	assert_eq!(integer(7) / integer(0), nan());
+	assert_eq!(integer(1).try_div(integer(0)), Err(DivisionByZero));
+
+	// floats are contagious
+	assert!(matches!(&third * &real(3.0), Real(_)));
+	assert!(matches!(integer(2) * i(), Complex(_, _)));
+
+	// equality is by value across representations
+	assert_eq!(integer(2), real(2.0));
+	assert_eq!(rational(3, 8), real(0.375));
+	assert_ne!(rational(1, 3), real(1.0 / 3.0));
+    }
+
+    #[test]
+    fn exact_functions_test() {
+	let f = integer(25).factorial();
+	assert_eq!(f.to_string(), "15511210043330985984000000");
+	assert_eq!(integer(6).gamma(), integer(120));
+	assert!(matches!(integer(-2).try_factorial(), Err(DomainError(_))));
+
+	assert_eq!(integer(2).pow(integer(-3)), rational(1, 8));
+	assert_eq!(integer(8).pow(rational(2, 3)), integer(4));
+	assert_eq!(rational(9, 4).sqrt(), rational(3, 2));
+	assert!(matches!(integer(0).try_pow(integer(-1)), Err(DivisionByZero)));
+
+	// anything without an exact answer is promoted to floats
+	assert!(matches!(integer(2).sqrt(), Real(_)));
+	assert_eq!(integer(-4).sqrt(), complex(0.0, 2.0));
+	assert_eq!(integer(-8).pow(rational(1, 3)), real(-8.0).pow(rational(1, 3)));
+	assert_eq!(integer(-8).pow(rational(2, 3)), real(-8.0).pow(real(2.0 / 3.0)));
+	assert_eq!(integer(0).sin(), real(0.0));
+	assert!(matches!(rational(1, 2).ln(), Real(_)));
+    }
 }
 
 // end src/number/number.rs
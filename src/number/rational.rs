@@ -0,0 +1,230 @@
+// src/number/rational.rs
+// exact fractions of arbitrary size integers
+
+use std::fmt;
+use std::ops::{Add,Sub,Mul,Div,Neg};
+
+use crate::number::bigint::*;
+
+/// An exact fraction, always kept in lowest terms with a positive
+/// denominator so that equal values compare equal.
+///
+/// ```
+/// use mathy::number::rational::*;
+/// let third = BigRational::new(1.into(), 3.into());
+/// let sum = &third + &BigRational::new(1.into(), 6.into());
+/// assert_eq!(sum.to_string(), "1/2");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigRational {
+    num: BigInt,
+    den: BigInt,
+}
+
+impl BigRational {
+    /// Make the fraction `num / den` in lowest terms. Panics when
+    /// the denominator is zero.
+    pub fn new(num: BigInt, den: BigInt) -> BigRational {
+	assert!(!den.is_zero(), "BigRational with a zero denominator");
+	let g = num.gcd(&den);
+	let (mut num, mut den) = (&num / &g, &den / &g);
+	if den.is_negative() {
+	    num = -num;
+	    den = -den;
+	}
+	BigRational { num, den }
+    }
+
+    pub fn from_integer(n: BigInt) -> BigRational {
+	BigRational { num: n, den: BigInt::one() }
+    }
+
+    /// The exact value of a finite f64, which is always a fraction
+    /// with a power of two denominator.
+    pub fn from_f64(x: f64) -> Option<BigRational> {
+	if !x.is_finite() {
+	    return None;
+	}
+	// scale up until whole, 2^1074 covers the subnormals
+	let mut scale = 0;
+	let mut y = x;
+	while y != y.trunc() {
+	    y *= 2.0;
+	    scale += 1;
+	}
+	Some(BigRational::new(BigInt::from_f64(y)?, BigInt::one().shl(scale)))
+    }
+
+    /// Read a decimal literal like `12`, `0.25` or `1.5e-3` exactly.
+    /// Exponents past a few thousand digits are refused rather than
+    /// building enormous integers.
+    pub fn from_decimal(s: &str) -> Option<BigRational> {
+	let (mantissa, exponent) = match s.find(['e', 'E']) {
+	    Some(i) => (&s[..i], s[i + 1..].parse::<i64>().ok()?),
+	    None => (s, 0),
+	};
+	let (whole, frac) = match mantissa.find('.') {
+	    Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+	    None => (mantissa, ""),
+	};
+	if whole.is_empty() && frac.is_empty() {
+	    return None;
+	}
+	let digits = format!("{}{}", whole, frac);
+	let num = BigInt::parse(&digits)?;
+	let exponent = exponent - frac.len() as i64;
+	if exponent.abs() > 4096 {
+	    return None;
+	}
+	let ten = BigInt::from(10).pow(exponent.unsigned_abs() as u32);
+	Some(if exponent >= 0 {
+	    BigRational::from_integer(&num * &ten)
+	} else {
+	    BigRational::new(num, ten)
+	})
+    }
+
+    pub fn numer(&self) -> &BigInt {
+	&self.num
+    }
+
+    pub fn denom(&self) -> &BigInt {
+	&self.den
+    }
+
+    pub fn is_integer(&self) -> bool {
+	self.den.is_one()
+    }
+
+    pub fn is_zero(&self) -> bool {
+	self.num.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+	self.num.is_negative()
+    }
+
+    pub fn abs(&self) -> BigRational {
+	BigRational { num: self.num.abs(), den: self.den.clone() }
+    }
+
+    /// The reciprocal, or None for zero.
+    pub fn recip(&self) -> Option<BigRational> {
+	if self.is_zero() {
+	    return None;
+	}
+	Some(BigRational::new(self.den.clone(), self.num.clone()))
+    }
+
+    /// Raise to a whole power, or None when dividing by zero.
+    pub fn pow(&self, n: i64) -> Option<BigRational> {
+	let k = n.unsigned_abs() as u32;
+	let p = BigRational { num: self.num.pow(k), den: self.den.pow(k) };
+	if n < 0 { p.recip() } else { Some(p) }
+    }
+
+    /// The exact `k`th root, when both the numerator and the
+    /// denominator have one.
+    pub fn root(&self, k: u32) -> Option<BigRational> {
+	Some(BigRational { num: self.num.root(k)?, den: self.den.root(k)? })
+    }
+
+    /// The nearest f64, keeping precision even when the numerator
+    /// and denominator are too large for an f64 on their own.
+    pub fn to_f64(&self) -> f64 {
+	let shift = 64 - (self.num.bits() as i64 - self.den.bits() as i64);
+	let q = if shift >= 0 {
+	    &self.num.abs().shl(shift as u32) / &self.den
+	} else {
+	    &self.num.abs() / &self.den.shl((-shift) as u32)
+	};
+	let v = q.to_f64() * 2f64.powi((-shift).clamp(-2000, 2000) as i32);
+	if self.is_negative() { -v } else { v }
+    }
+}
+
+impl fmt::Display for BigRational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	if self.is_integer() {
+	    write!(f, "{}", self.num)
+	} else {
+	    write!(f, "{}/{}", self.num, self.den)
+	}
+    }
+}
+
+impl<'a> Add<&'a BigRational> for &'a BigRational {
+    type Output = BigRational;
+    fn add(self, other: &BigRational) -> BigRational {
+	BigRational::new(&(&self.num * &other.den) + &(&other.num * &self.den), &self.den * &other.den)
+    }
+}
+
+impl<'a> Sub<&'a BigRational> for &'a BigRational {
+    type Output = BigRational;
+    fn sub(self, other: &BigRational) -> BigRational {
+	BigRational::new(&(&self.num * &other.den) - &(&other.num * &self.den), &self.den * &other.den)
+    }
+}
+
+impl<'a> Mul<&'a BigRational> for &'a BigRational {
+    type Output = BigRational;
+    fn mul(self, other: &BigRational) -> BigRational {
+	BigRational::new(&self.num * &other.num, &self.den * &other.den)
+    }
+}
+
+/// Panics when dividing by zero, use `recip` to check first.
+impl<'a> Div<&'a BigRational> for &'a BigRational {
+    type Output = BigRational;
+    fn div(self, other: &BigRational) -> BigRational {
+	BigRational::new(&self.num * &other.den, &self.den * &other.num)
+    }
+}
+
+impl Neg for &BigRational {
+    type Output = BigRational;
+    fn neg(self) -> BigRational {
+	BigRational { num: -&self.num, den: self.den.clone() }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frac(n: i64, d: i64) -> BigRational {
+	BigRational::new(n.into(), d.into())
+    }
+
+    #[test]
+    fn test_lowest_terms() {
+	assert_eq!(frac(6, -8), frac(-3, 4));
+	assert_eq!(frac(6, -8).to_string(), "-3/4");
+	assert_eq!(frac(0, -5), BigRational::from_integer(BigInt::zero()));
+	assert_eq!(&frac(1, 3) * &frac(3, 1), frac(1, 1));
+	assert_eq!(&frac(1, 2) / &frac(-1, 4), frac(-2, 1));
+	assert_eq!(frac(2, 3).pow(-2), Some(frac(9, 4)));
+	assert_eq!(frac(0, 1).pow(-1), None);
+	assert_eq!(frac(8, 27).root(3), Some(frac(2, 3)));
+    }
+
+    #[test]
+    fn test_conversions() {
+	assert_eq!(BigRational::from_decimal("0.1"), Some(frac(1, 10)));
+	assert_eq!(BigRational::from_decimal("2.50"), Some(frac(5, 2)));
+	assert_eq!(BigRational::from_decimal("1.5e-3"), Some(frac(3, 2000)));
+	assert_eq!(BigRational::from_decimal("12E2"), Some(frac(1200, 1)));
+	assert_eq!(BigRational::from_decimal("1e99999"), None);
+	assert_eq!(BigRational::from_f64(0.375), Some(frac(3, 8)));
+	assert_eq!(frac(1, 3).to_f64(), 1.0 / 3.0);
+
+	// too big for an f64 on their own, but not together
+	let big = BigInt::from(10).pow(400);
+	let r = BigRational::new(&big * &BigInt::from(2), &big * &BigInt::from(3));
+	assert_eq!(r.to_f64(), 2.0 / 3.0);
+    }
+}
+
+// end src/number/rational.rs
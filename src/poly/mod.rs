@@ -0,0 +1,6 @@
+// src/poly/mod.rs
+
+pub mod polynomial;
+pub mod multivariate;
+
+// end src/poly/mod.rs
@@ -0,0 +1,369 @@
+// src/poly/multivariate.rs
+// sparse polynomials in several variables
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{Add,Sub,Mul,Neg};
+
+use crate::error::*;
+use crate::expr::evaluator::*;
+use crate::expr::expr::*;
+use crate::expr::symbol::*;
+use crate::number::number::*;
+
+use crate::expr::expr::Expr::*;
+
+/// A polynomial in several variables, stored sparsely as a map
+/// from exponent vectors to non-zero coefficients. Exponent `i`
+/// of a term belongs to `vars()[i]`.
+///
+/// Polynomials over different variables can still be combined,
+/// the result is over every variable of both.
+///
+/// ```
+/// use mathy::calc::prelude::*;
+/// let e = Expr::parse("(x + y)^2").unwrap();
+/// let p = MultiPolynomial::from_expr(&e, &['x', 'y']).unwrap();
+/// assert_eq!(p.terms().count(), 3);
+/// assert_eq!(p.coeff(&[1, 1]), real(2.0));
+/// assert_eq!(p.total_degree(), Some(2));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiPolynomial {
+    vars: Vec<Symbol>,
+    terms: BTreeMap<Vec<u32>, Number>,
+}
+
+impl MultiPolynomial {
+    /// The zero polynomial over the given variables.
+    pub fn new<S: Into<Symbol> + Copy>(vars: &[S]) -> MultiPolynomial {
+	MultiPolynomial {
+	    vars: vars.iter().map(|&s| s.into()).collect(),
+	    terms: BTreeMap::new(),
+	}
+    }
+
+    /// Add `c` times the monomial with the given exponents.
+    pub fn term(mut self, exponents: &[u32], c: Number) -> MultiPolynomial {
+	assert_eq!(exponents.len(), self.vars.len(), "wrong number of exponents");
+	self.push(exponents.to_vec(), c);
+	self
+    }
+
+    fn constant(vars: &[Symbol], c: Number) -> MultiPolynomial {
+	MultiPolynomial::new(vars).term(&vec![0; vars.len()], c)
+    }
+
+    /// Convert an expression which is a polynomial in the given
+    /// variables with numeric coefficients. Powers must be whole
+    /// and division is only allowed by constants. Anything else,
+    /// including named constants like `pi`, is an `Unsupported`
+    /// error.
+    pub fn from_expr<S: Into<Symbol> + Copy>(e: &Expr, vars: &[S]) -> Result<MultiPolynomial, MathError> {
+	let vars: Vec<Symbol> = vars.iter().map(|&s| s.into()).collect();
+	convert(e, &vars).ok_or_else(|| {
+	    MathError::Unsupported(format!("{} is not a polynomial", e))
+	})
+    }
+
+    /// Convert back into an `Expr`, with the terms in descending
+    /// lexicographic order of their exponents.
+    pub fn to_expr(&self) -> Expr {
+	let mut out: Option<Expr> = None;
+	for (exps, c) in self.terms.iter().rev() {
+	    let negative = c.imag() == 0.0 && c.real() < 0.0;
+	    let term = self.monomial_expr(exps, if negative { -c } else { c.clone() });
+	    out = Some(match (out, negative) {
+		(None, false) => term,
+		(None, true) => neg(term),
+		(Some(acc), false) => add(acc, term),
+		(Some(acc), true) => sub(acc, term),
+	    });
+	}
+	out.unwrap_or_else(zero)
+    }
+
+    fn monomial_expr(&self, exps: &[u32], c: Number) -> Expr {
+	let mut out: Option<Expr> = None;
+	for (x, &n) in self.vars.iter().zip(exps.iter()) {
+	    let f = match n {
+		0 => continue,
+		1 => var(*x),
+		n => powf(var(*x), n as f64),
+	    };
+	    out = Some(match out {
+		None => f,
+		Some(acc) => mul(acc, f),
+	    });
+	}
+	match out {
+	    None => Expr::Const(c),
+	    Some(m) if c.real_eq(1.0) => m,
+	    Some(m) => mul(Expr::Const(c), m),
+	}
+    }
+
+    // Add to a coefficient, dropping it if it cancels out
+    fn push(&mut self, exps: Vec<u32>, c: Number) {
+	let sum = self.terms.get(&exps).cloned().unwrap_or_else(|| integer(0)) + c;
+	if sum.is_zero() {
+	    self.terms.remove(&exps);
+	} else {
+	    self.terms.insert(exps, sum);
+	}
+    }
+
+    pub fn vars(&self) -> &[Symbol] {
+	&self.vars
+    }
+
+    /// The non-zero terms as exponents and coefficients.
+    pub fn terms(&self) -> impl Iterator<Item = (&[u32], Number)> {
+	self.terms.iter().map(|(e, c)| (e.as_slice(), c.clone()))
+    }
+
+    /// The coefficient of the monomial with the given exponents.
+    pub fn coeff(&self, exponents: &[u32]) -> Number {
+	self.terms.get(exponents).cloned().unwrap_or_else(|| integer(0))
+    }
+
+    pub fn is_zero(&self) -> bool {
+	self.terms.is_empty()
+    }
+
+    /// The highest sum of exponents of any term, or None for
+    /// the zero polynomial.
+    pub fn total_degree(&self) -> Option<u32> {
+	self.terms.keys().map(|e| e.iter().sum()).max()
+    }
+
+    /// The highest power of one variable, or None for the zero
+    /// polynomial. Variables it isn't over have degree zero.
+    pub fn degree_in<S: Into<Symbol>>(&self, sym: S) -> Option<u32> {
+	let i = self.position(sym.into());
+	self.terms.keys().map(|e| i.map(|i| e[i]).unwrap_or(0)).max()
+    }
+
+    fn position(&self, sym: Symbol) -> Option<usize> {
+	self.vars.iter().position(|&v| v == sym)
+    }
+
+    /// Evaluate at the point given by an `Env`, which must bind
+    /// every variable.
+    pub fn eval(&self, env: &Env) -> Result<Number, MathError> {
+	let missing: Vec<Symbol> = self.vars.iter()
+	    .filter(|&&v| env.get(v).is_none())
+	    .copied()
+	    .collect();
+	if !missing.is_empty() {
+	    return Err(MathError::UnboundVariable(missing));
+	}
+	let point: Vec<Number> = self.vars.iter().map(|&v| env.get(v).unwrap()).collect();
+	Ok(self.terms.iter().fold(integer(0), |acc, (exps, c)| {
+	    let m = point.iter().zip(exps.iter())
+		.fold(c.clone(), |m, (x, &n)| m * x.pow(integer(n as i64)));
+	    acc + m
+	}))
+    }
+
+    /// The partial derivative by one variable.
+    pub fn derivative<S: Into<Symbol>>(&self, sym: S) -> MultiPolynomial {
+	let mut out = MultiPolynomial { vars: self.vars.clone(), terms: BTreeMap::new() };
+	let i = match self.position(sym.into()) {
+	    Some(i) => i,
+	    None => return out,
+	};
+	for (exps, c) in self.terms.iter().filter(|(e, _)| e[i] > 0) {
+	    let mut d = exps.clone();
+	    d[i] -= 1;
+	    out.push(d, c * &integer(exps[i] as i64));
+	}
+	out
+    }
+
+    /// The same polynomial over a longer list of variables, which
+    /// must include all of the current ones.
+    fn extend(&self, vars: &[Symbol]) -> MultiPolynomial {
+	let map: Vec<usize> = self.vars.iter()
+	    .map(|v| vars.iter().position(|w| w == v).unwrap())
+	    .collect();
+	let mut out = MultiPolynomial { vars: vars.to_vec(), terms: BTreeMap::new() };
+	for (exps, c) in self.terms.iter() {
+	    let mut e = vec![0; vars.len()];
+	    for (i, &n) in exps.iter().enumerate() {
+		e[map[i]] = n;
+	    }
+	    out.terms.insert(e, c.clone());
+	}
+	out
+    }
+
+    // Put both polynomials over the same variables
+    fn unify(self, other: MultiPolynomial) -> (MultiPolynomial, MultiPolynomial) {
+	if self.vars == other.vars {
+	    return (self, other);
+	}
+	let mut vars = self.vars.clone();
+	for v in other.vars.iter() {
+	    if !vars.contains(v) {
+		vars.push(*v);
+	    }
+	}
+	(self.extend(&vars), other.extend(&vars))
+    }
+
+    fn pow(&self, n: u32) -> MultiPolynomial {
+	(0..n).fold(MultiPolynomial::constant(&self.vars, integer(1)), |acc, _| {
+	    acc * self.clone()
+	})
+    }
+}
+
+// The value of a constant part of the tree. Named constants
+// are left out, since their value would be rounded.
+fn number(e: &Expr) -> Option<Number> {
+    if e.nodes().any(|n| matches!(n, Named(_))) {
+	return None;
+    }
+    try_evaluate(e, &Env::new()).ok()
+}
+
+// Build a polynomial from the expression tree, or None when
+// some part of it isn't polynomial
+fn convert(e: &Expr, vars: &[Symbol]) -> Option<MultiPolynomial> {
+    if let Some(c) = number(e) {
+	return Some(MultiPolynomial::constant(vars, c));
+    }
+    match e {
+	Var(x) => {
+	    let i = vars.iter().position(|v| v == x)?;
+	    let mut exps = vec![0; vars.len()];
+	    exps[i] = 1;
+	    Some(MultiPolynomial::new(vars).term(&exps, integer(1)))
+	},
+	Neg(ref f) => Some(-convert(f, vars)?),
+	Add(ref f, ref g) => Some(convert(f, vars)? + convert(g, vars)?),
+	Sub(ref f, ref g) => Some(convert(f, vars)? - convert(g, vars)?),
+	Mul(ref f, ref g) => Some(convert(f, vars)? * convert(g, vars)?),
+	Div(ref f, ref g) => {
+	    let d = number(g)?;
+	    if d.is_zero() {
+		return None;
+	    }
+	    Some(convert(f, vars)? * MultiPolynomial::constant(vars, integer(1) / d))
+	},
+	Pow(ref b, ref p) => {
+	    let n = number(p)?;
+	    if !n.is_whole() || n.real() < 0.0 {
+		return None;
+	    }
+	    Some(convert(b, vars)?.pow(n.real() as u32))
+	},
+	_ => None,
+    }
+}
+
+// The exponents of the product of two monomials
+fn add_exponents(a: &[u32], b: &[u32]) -> Vec<u32> {
+    a.iter().zip(b.iter()).map(|(x, y)| x + y).collect()
+}
+
+impl fmt::Display for MultiPolynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f, "{}", self.to_expr())
+    }
+}
+
+impl Add for MultiPolynomial {
+    type Output = MultiPolynomial;
+    fn add(self, other: MultiPolynomial) -> MultiPolynomial {
+	let (mut a, b) = self.unify(other);
+	for (exps, c) in b.terms.into_iter() {
+	    a.push(exps, c);
+	}
+	a
+    }
+}
+
+impl Sub for MultiPolynomial {
+    type Output = MultiPolynomial;
+    fn sub(self, other: MultiPolynomial) -> MultiPolynomial {
+	self + (-other)
+    }
+}
+
+impl Neg for MultiPolynomial {
+    type Output = MultiPolynomial;
+    fn neg(mut self) -> MultiPolynomial {
+	for c in self.terms.values_mut() {
+	    *c = -&*c;
+	}
+	self
+    }
+}
+
+impl Mul for MultiPolynomial {
+    type Output = MultiPolynomial;
+    fn mul(self, other: MultiPolynomial) -> MultiPolynomial {
+	let (a, b) = self.unify(other);
+	let mut out = MultiPolynomial { vars: a.vars.clone(), terms: BTreeMap::new() };
+	for (ea, ca) in a.terms.iter() {
+	    for (eb, cb) in b.terms.iter() {
+		out.push(add_exponents(ea, eb), ca * cb);
+	    }
+	}
+	out
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn poly(s: &str) -> MultiPolynomial {
+	MultiPolynomial::from_expr(&Expr::parse(s).unwrap(), &['x', 'y']).unwrap()
+    }
+
+    #[test]
+    fn test_conversion() {
+	let p = poly("(x - y)(x + y) / 2 + 3");
+	assert_eq!(p.coeff(&[2, 0]), real(0.5));
+	assert_eq!(p.coeff(&[0, 2]), real(-0.5));
+	assert_eq!(p.coeff(&[1, 1]), real(0.0));
+	assert_eq!(p.degree_in('y'), Some(2));
+	assert_eq!(poly("x^2 y - 2y + 1").to_expr(), Expr::parse("x^2 y - 2y + 1").unwrap());
+	assert_eq!(poly("x - x").to_expr(), zero());
+
+	let xy: &[char] = &['x', 'y'];
+	assert!(MultiPolynomial::from_expr(&Expr::parse("x z").unwrap(), xy).is_err());
+	assert!(MultiPolynomial::from_expr(&Expr::parse("x / y").unwrap(), xy).is_err());
+	assert!(MultiPolynomial::from_expr(&Expr::parse("e x y").unwrap(), xy).is_err());
+	assert!(MultiPolynomial::from_expr(&Expr::parse("x^0.5").unwrap(), xy).is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+	let p = poly("x + y");
+	let q = MultiPolynomial::from_expr(&Expr::parse("z - 1").unwrap(), &['z']).unwrap();
+	let r = p.clone() * q;
+	assert_eq!(r.vars(), &[Symbol::new("x"), Symbol::new("y"), Symbol::new("z")]);
+	assert_eq!(r.terms().count(), 4);
+	assert!((p.clone() - p).is_zero());
+
+	let env = Env::new().bind('x', real(2.0)).bind('y', real(3.0)).bind('z', real(4.0));
+	assert_eq!(r.eval(&env), Ok(real(15.0)));
+	assert!(matches!(r.eval(&Env::new()), Err(MathError::UnboundVariable(_))));
+    }
+
+    #[test]
+    fn test_derivative() {
+	let p = poly("x^3 y^2 + x y + 7");
+	assert_eq!(p.derivative('x'), poly("3x^2 y^2 + y"));
+	assert_eq!(p.derivative('y'), poly("2x^3 y + x"));
+	assert!(p.derivative('z').is_zero());
+	assert_eq!(p.total_degree(), Some(5));
+    }
+}
+
+// end src/poly/multivariate.rs
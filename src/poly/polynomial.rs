@@ -0,0 +1,449 @@
+// src/poly/polynomial.rs
+// dense polynomials in one variable
+
+use std::fmt;
+use std::ops::{Add,Sub,Mul,Neg};
+
+use crate::error::*;
+use crate::expr::evaluator::*;
+use crate::expr::expr::*;
+use crate::expr::symbol::*;
+use crate::number::number::*;
+
+use crate::expr::expr::Expr::*;
+
+// Powers past this are left as expressions rather than being
+// multiplied out into a dense polynomial
+const MAX_DEGREE: usize = 1000;
+
+/// A polynomial in a single variable, stored densely as its
+/// coefficients from the constant term up. There are never any
+/// trailing zero coefficients, so the zero polynomial has none.
+///
+/// ```
+/// use mathy::calc::prelude::*;
+/// let p = Polynomial::from_expr(&Expr::parse("(x - 1)(x + 2)").unwrap(), 'x').unwrap();
+/// assert_eq!(p.degree(), Some(2));
+/// assert_eq!(p.eval(real(3.0)), real(10.0));
+/// assert_eq!(p.to_expr(), Expr::parse("x^2 + x - 2").unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polynomial {
+    var: Symbol,
+    coeffs: Vec<Number>,
+}
+
+impl Polynomial {
+    /// Make a polynomial from its coefficients, lowest power first.
+    pub fn new<S: Into<Symbol>>(var: S, coeffs: Vec<Number>) -> Polynomial {
+	let mut p = Polynomial { var: var.into(), coeffs };
+	p.trim();
+	p
+    }
+
+    /// Shortcut for `new` with real coefficients.
+    pub fn from_f64<S: Into<Symbol>>(var: S, coeffs: &[f64]) -> Polynomial {
+	Polynomial::new(var, coeffs.iter().map(|c| real(*c)).collect())
+    }
+
+    pub fn zero<S: Into<Symbol>>(var: S) -> Polynomial {
+	Polynomial::new(var, Vec::new())
+    }
+
+    pub fn constant<S: Into<Symbol>>(var: S, c: Number) -> Polynomial {
+	Polynomial::new(var, vec![c])
+    }
+
+    /// The single term `c * var^n`.
+    pub fn monomial<S: Into<Symbol>>(var: S, c: Number, n: usize) -> Polynomial {
+	let mut coeffs = vec![integer(0); n];
+	coeffs.push(c);
+	Polynomial::new(var, coeffs)
+    }
+
+    /// Convert an expression which is a polynomial in `var` with
+    /// numeric coefficients, ie `(x + 1)^2 / 2`. Anything else,
+    /// including other variables or named constants like `pi`
+    /// which would have to be rounded, is an `Unsupported` error.
+    pub fn from_expr<S: Into<Symbol>>(e: &Expr, var: S) -> Result<Polynomial, MathError> {
+	let var = var.into();
+	convert(e, var).ok_or_else(|| {
+	    MathError::Unsupported(format!("{} is not a polynomial in {} with numeric coefficients", e, var))
+	})
+    }
+
+    /// Convert back into an `Expr`, highest power first.
+    pub fn to_expr(&self) -> Expr {
+	let mut out: Option<Expr> = None;
+	for (n, c) in self.coeffs.iter().enumerate().rev() {
+	    if c.is_zero() {
+		continue;
+	    }
+	    let negative = c.imag() == 0.0 && c.real() < 0.0;
+	    let term = monomial_expr(if negative { -c } else { c.clone() }, self.var, n);
+	    out = Some(match (out, negative) {
+		(None, false) => term,
+		(None, true) => neg(term),
+		(Some(acc), false) => add(acc, term),
+		(Some(acc), true) => sub(acc, term),
+	    });
+	}
+	out.unwrap_or_else(zero)
+    }
+
+    // Drop trailing zero coefficients
+    fn trim(&mut self) {
+	while self.coeffs.last().map(|c| c.is_zero()).unwrap_or(false) {
+	    self.coeffs.pop();
+	}
+    }
+
+    pub fn var(&self) -> Symbol {
+	self.var
+    }
+
+    /// The coefficients from the constant term up.
+    pub fn coeffs(&self) -> &[Number] {
+	&self.coeffs
+    }
+
+    /// The coefficient of `var^n`, which is zero past the degree.
+    pub fn coeff(&self, n: usize) -> Number {
+	self.coeffs.get(n).cloned().unwrap_or_else(|| integer(0))
+    }
+
+    /// The highest power with a non-zero coefficient, or None
+    /// for the zero polynomial.
+    pub fn degree(&self) -> Option<usize> {
+	self.coeffs.len().checked_sub(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+	self.coeffs.is_empty()
+    }
+
+    /// The coefficient of the highest power.
+    pub fn leading(&self) -> Number {
+	self.coeffs.last().cloned().unwrap_or_else(|| integer(0))
+    }
+
+    /// Evaluate with Horner's scheme.
+    pub fn eval(&self, x: Number) -> Number {
+	self.coeffs.iter().rev().fold(integer(0), |acc, c| &(&acc * &x) + c)
+    }
+
+    pub fn derivative(&self) -> Polynomial {
+	let coeffs = self.coeffs.iter().enumerate().skip(1)
+	    .map(|(n, c)| c * &integer(n as i64))
+	    .collect();
+	Polynomial::new(self.var, coeffs)
+    }
+
+    /// The antiderivative with a constant term of zero.
+    pub fn integral(&self) -> Polynomial {
+	let mut coeffs = vec![integer(0)];
+	for (n, c) in self.coeffs.iter().enumerate() {
+	    coeffs.push(c / &integer(n as i64 + 1));
+	}
+	Polynomial::new(self.var, coeffs)
+    }
+
+    /// Substitute another polynomial for the variable, so
+    /// `p.compose(q)` is `p(q(x))` in the variable of `q`.
+    pub fn compose(&self, q: &Polynomial) -> Polynomial {
+	self.coeffs.iter().rev().fold(Polynomial::zero(q.var), |acc, c| {
+	    acc * q.clone() + Polynomial::constant(q.var, c.clone())
+	})
+    }
+
+    /// Polynomial long division, giving back the quotient and the
+    /// remainder, whose degree is lower than the divisor's.
+    ///
+    /// ```
+    /// use mathy::calc::prelude::*;
+    /// let p = Polynomial::from_f64('x', &[-1.0, 0.0, 0.0, 1.0]);
+    /// let d = Polynomial::from_f64('x', &[-1.0, 1.0]);
+    /// let (q, r) = p.div_rem(&d).unwrap();
+    /// assert_eq!(q, Polynomial::from_f64('x', &[1.0, 1.0, 1.0]));
+    /// assert!(r.is_zero());
+    /// ```
+    pub fn div_rem(&self, d: &Polynomial) -> Result<(Polynomial, Polynomial), MathError> {
+	let dn = d.degree().ok_or(MathError::DivisionByZero)?;
+	let lead = d.leading();
+	let mut rem = self.coeffs.clone();
+	if rem.len() <= dn {
+	    return Ok((Polynomial::zero(self.var), self.clone()));
+	}
+	let mut quot = vec![integer(0); rem.len() - dn];
+	for k in (0..quot.len()).rev() {
+	    let q = &rem[k + dn] / &lead;
+	    quot[k] = q.clone();
+	    for (i, c) in d.coeffs.iter().enumerate() {
+		rem[k + i] = &rem[k + i] - &(&q * c);
+	    }
+	    // the top coefficient is gone, even with rounding
+	    rem[k + dn] = integer(0);
+	}
+	rem.truncate(dn);
+	Ok((Polynomial::new(self.var, quot), Polynomial::new(self.var, rem)))
+    }
+
+    /// The monic greatest common divisor, found by Euclid's
+    /// algorithm. Remainders which are zero up to rounding are
+    /// treated as zero, so float coefficients still work.
+    pub fn gcd(&self, other: &Polynomial) -> Polynomial {
+	let scale = self.coeffs.iter().chain(other.coeffs.iter())
+	    .map(|c| c.abs().real())
+	    .fold(0.0, f64::max);
+	let tolerance = 1e-10 * scale.max(1.0);
+
+	let (mut a, mut b) = (self.clone(), other.clone());
+	while !b.is_zero() {
+	    let (_, mut r) = a.div_rem(&b).unwrap();
+	    if r.coeffs.iter().all(|c| c.abs().real() <= tolerance) {
+		r = Polynomial::zero(r.var);
+	    }
+	    a = b;
+	    b = r;
+	}
+	if a.is_zero() {
+	    return a;
+	}
+	let lead = a.leading();
+	let coeffs = a.coeffs.iter().map(|c| c / &lead).collect();
+	Polynomial::new(a.var, coeffs)
+    }
+
+    /// Add two polynomials, or give an `Unsupported` error when
+    /// they are in different variables. Constants can be added to
+    /// a polynomial in any variable.
+    ///
+    /// ```
+    /// use mathy::calc::prelude::*;
+    /// let p = Polynomial::from_f64('x', &[1.0, 1.0]);
+    /// let q = Polynomial::from_f64('y', &[0.0, 1.0]);
+    /// assert!(p.checked_add(&q).is_err());
+    /// assert_eq!(p.checked_add(&p), Ok(Polynomial::from_f64('x', &[2.0, 2.0])));
+    /// ```
+    pub fn checked_add(&self, other: &Polynomial) -> Result<Polynomial, MathError> {
+	let var = self.common_var(other)?;
+	let n = self.coeffs.len().max(other.coeffs.len());
+	let coeffs = (0..n).map(|i| self.coeff(i) + other.coeff(i)).collect();
+	Ok(Polynomial::new(var, coeffs))
+    }
+
+    /// Subtract, with the same rules as `checked_add`.
+    pub fn checked_sub(&self, other: &Polynomial) -> Result<Polynomial, MathError> {
+	self.checked_add(&-other.clone())
+    }
+
+    /// Multiply, with the same rules as `checked_add`.
+    pub fn checked_mul(&self, other: &Polynomial) -> Result<Polynomial, MathError> {
+	let var = self.common_var(other)?;
+	if self.is_zero() || other.is_zero() {
+	    return Ok(Polynomial::zero(var));
+	}
+	let mut coeffs = vec![integer(0); self.coeffs.len() + other.coeffs.len() - 1];
+	for (i, a) in self.coeffs.iter().enumerate() {
+	    for (j, b) in other.coeffs.iter().enumerate() {
+		coeffs[i + j] = &coeffs[i + j] + &(a * b);
+	    }
+	}
+	Ok(Polynomial::new(var, coeffs))
+    }
+
+    // The variable of the result of a binary operation. Constants
+    // work with polynomials in any variable.
+    fn common_var(&self, other: &Polynomial) -> Result<Symbol, MathError> {
+	if self.degree().unwrap_or(0) == 0 {
+	    return Ok(other.var);
+	}
+	if other.degree().unwrap_or(0) == 0 || self.var == other.var {
+	    return Ok(self.var);
+	}
+	Err(MathError::Unsupported(format!(
+	    "polynomials in {} and {} can't be combined", self.var, other.var)))
+    }
+}
+
+// The value of a constant part of the tree. Named constants
+// are left out, since their value would be rounded.
+fn number(e: &Expr) -> Option<Number> {
+    if e.nodes().any(|n| matches!(n, Named(_))) {
+	return None;
+    }
+    try_evaluate(e, &Env::new()).ok()
+}
+
+// Build a polynomial straight from the expression tree, without
+// expanding it first, or None when some part of it isn't
+// polynomial in x
+fn convert(e: &Expr, x: Symbol) -> Option<Polynomial> {
+    if let Some(c) = number(e) {
+	return Some(Polynomial::constant(x, c));
+    }
+    match e {
+	Var(s) if *s == x => Some(Polynomial::monomial(x, integer(1), 1)),
+	Neg(ref f) => Some(-convert(f, x)?),
+	Add(ref f, ref g) => convert(f, x)?.checked_add(&convert(g, x)?).ok(),
+	Sub(ref f, ref g) => convert(f, x)?.checked_sub(&convert(g, x)?).ok(),
+	Mul(ref f, ref g) => convert(f, x)?.checked_mul(&convert(g, x)?).ok(),
+	Div(ref f, ref g) => {
+	    let d = number(g)?;
+	    if d.is_zero() {
+		return None;
+	    }
+	    let p = convert(f, x)?;
+	    let coeffs = p.coeffs.iter().map(|c| c / &d).collect();
+	    Some(Polynomial::new(x, coeffs))
+	},
+	Pow(ref b, ref p) => {
+	    let n = number(p)?;
+	    if !n.is_whole() || n.real() < 0.0 {
+		return None;
+	    }
+	    let base = convert(b, x)?;
+	    let n = n.real() as usize;
+	    if base.degree().unwrap_or(0) * n > MAX_DEGREE {
+		return None;
+	    }
+	    (0..n).try_fold(Polynomial::constant(x, integer(1)), |acc, _| acc.checked_mul(&base).ok())
+	},
+	_ => None,
+    }
+}
+
+// c * x^n with the parts which aren't needed left out
+fn monomial_expr(c: Number, x: Symbol, n: usize) -> Expr {
+    let xn = match n {
+	0 => return Expr::Const(c),
+	1 => var(x),
+	n => powf(var(x), n as f64),
+    };
+    if c.real_eq(1.0) {
+	xn
+    } else {
+	mul(Expr::Const(c), xn)
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f, "{}", self.to_expr())
+    }
+}
+
+/// The operators are shorthand for the checked methods.
+///
+/// # Panics
+///
+/// When the polynomials are in different variables, as with
+/// `checked_add`.
+impl Add for Polynomial {
+    type Output = Polynomial;
+    fn add(self, other: Polynomial) -> Polynomial {
+	self.checked_add(&other).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// # Panics
+///
+/// When the polynomials are in different variables.
+impl Sub for Polynomial {
+    type Output = Polynomial;
+    fn sub(self, other: Polynomial) -> Polynomial {
+	self.checked_sub(&other).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+impl Neg for Polynomial {
+    type Output = Polynomial;
+    fn neg(self) -> Polynomial {
+	let coeffs = self.coeffs.iter().map(|c| -c).collect();
+	Polynomial::new(self.var, coeffs)
+    }
+}
+
+/// # Panics
+///
+/// When the polynomials are in different variables.
+impl Mul for Polynomial {
+    type Output = Polynomial;
+    fn mul(self, other: Polynomial) -> Polynomial {
+	self.checked_mul(&other).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn poly(s: &str) -> Polynomial {
+	Polynomial::from_expr(&Expr::parse(s).unwrap(), 'x').unwrap()
+    }
+
+    #[test]
+    fn test_conversion() {
+	let p = poly("(x + 1)^3 / 2");
+	assert_eq!(p.coeffs(), &[real(0.5), real(1.5), real(1.5), real(0.5)]);
+	assert_eq!(poly("2 - x^2").to_expr(), Expr::parse("-x^2 + 2").unwrap());
+	assert_eq!(poly("x - x").to_expr(), zero());
+	assert_eq!(poly("x - x").degree(), None);
+
+	assert!(Polynomial::from_expr(&Expr::parse("x y").unwrap(), 'x').is_err());
+	assert!(Polynomial::from_expr(&Expr::parse("sin(x)").unwrap(), 'x').is_err());
+	assert!(Polynomial::from_expr(&Expr::parse("pi x").unwrap(), 'x').is_err());
+	assert!(Polynomial::from_expr(&Expr::parse("x / (x + 1)").unwrap(), 'x').is_err());
+
+	// built directly, so high powers don't need a huge expansion
+	let p = poly("(x - 1)^17");
+	assert_eq!(p.degree(), Some(17));
+	assert_eq!(p.coeff(8), integer(-24310));
+	assert_eq!(poly("(x + 1)^40").coeff(20), integer(137846528820));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+	let (a, b) = (poly("x + 1"), poly("x - 1"));
+	assert_eq!(a.clone() * b.clone(), poly("x^2 - 1"));
+	assert_eq!(a.clone() + b.clone(), poly("2x"));
+	assert_eq!(a.clone() - a.clone(), Polynomial::zero('x'));
+	assert_eq!(a.clone() * Polynomial::constant('t', real(2.0)), poly("2x + 2"));
+	assert_eq!(poly("x^2").compose(&a), poly("x^2 + 2x + 1"));
+	assert_eq!(poly("x^3 + x").eval(real(2.0)), real(10.0));
+
+	let y = Polynomial::from_f64('y', &[0.0, 1.0]);
+	assert!(matches!(a.checked_mul(&y), Err(MathError::Unsupported(_))));
+	assert!(a.checked_sub(&y).is_err());
+	assert_eq!(a.checked_sub(&b), Ok(Polynomial::constant('x', real(2.0))));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mixed_variables_panic() {
+	let _ = poly("x + 1") * Polynomial::from_f64('y', &[0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_division_and_gcd() {
+	let (q, r) = poly("x^3 + 2x + 5").div_rem(&poly("x^2 + 1")).unwrap();
+	assert_eq!(q, poly("x"));
+	assert_eq!(r, poly("x + 5"));
+	assert_eq!(poly("x").div_rem(&Polynomial::zero('x')), Err(MathError::DivisionByZero));
+
+	assert_eq!(poly("x^2 - 1").gcd(&poly("x^2 - 2x + 1")), poly("x - 1"));
+	assert_eq!(poly("3x^2 + 3x").gcd(&poly("6x^3")), poly("x"));
+	assert_eq!(poly("x^2 + 1").gcd(&poly("x - 1")), poly("1"));
+    }
+
+    #[test]
+    fn test_calculus() {
+	let p = poly("x^3 - 4x + 2");
+	assert_eq!(p.derivative(), poly("3x^2 - 4"));
+	assert_eq!(p.derivative().integral(), poly("x^3 - 4x"));
+	assert_eq!(Polynomial::zero('x').derivative(), Polynomial::zero('x'));
+    }
+}
+
+// end src/poly/polynomial.rs